@@ -0,0 +1,552 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{Error, Limits, PrivateClient, RpcTransport};
+use crate::trading::Order;
+use crate::types::{Currency, Kind, SubaccountId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PositionDirection {
+  Buy,
+  Sell,
+  Zero,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Position {
+  pub instrument_name: String,
+  pub kind: Kind,
+  pub direction: PositionDirection,
+  pub size: f64,
+  #[serde(default)]
+  pub size_currency: Option<f64>,
+  pub average_price: f64,
+  #[serde(default)]
+  pub average_price_usd: Option<f64>,
+  pub mark_price: f64,
+  pub index_price: f64,
+  #[serde(default)]
+  pub settlement_price: Option<f64>,
+  #[serde(default)]
+  pub estimated_liquidation_price: Option<f64>,
+  #[serde(default)]
+  pub leverage: Option<f64>,
+  pub delta: f64,
+  #[serde(default)]
+  pub gamma: Option<f64>,
+  #[serde(default)]
+  pub theta: Option<f64>,
+  #[serde(default)]
+  pub vega: Option<f64>,
+  pub floating_profit_loss: f64,
+  #[serde(default)]
+  pub realized_profit_loss: Option<f64>,
+  pub total_profit_loss: f64,
+  #[serde(default)]
+  pub realized_funding: Option<f64>,
+  #[serde(default)]
+  pub interest_value: Option<f64>,
+  pub initial_margin: f64,
+  pub maintenance_margin: f64,
+  #[serde(default)]
+  pub open_orders_margin: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSummary {
+  pub currency: Currency,
+  pub balance: f64,
+  pub equity: f64,
+  pub available_funds: f64,
+  pub available_withdrawal_funds: f64,
+  pub margin_balance: f64,
+  pub initial_margin: f64,
+  pub maintenance_margin: f64,
+  #[serde(default)]
+  pub projected_initial_margin: Option<f64>,
+  #[serde(default)]
+  pub projected_maintenance_margin: Option<f64>,
+  #[serde(default)]
+  pub session_rpl: Option<f64>,
+  #[serde(default)]
+  pub session_upl: Option<f64>,
+  #[serde(default)]
+  pub total_pl: Option<f64>,
+  #[serde(default)]
+  pub futures_pl: Option<f64>,
+  #[serde(default)]
+  pub options_pl: Option<f64>,
+  #[serde(default)]
+  pub delta_total: Option<f64>,
+  #[serde(default)]
+  pub options_delta: Option<f64>,
+  #[serde(default)]
+  pub options_gamma: Option<f64>,
+  #[serde(default)]
+  pub options_theta: Option<f64>,
+  #[serde(default)]
+  pub options_vega: Option<f64>,
+  #[serde(default)]
+  pub fee_balance: Option<f64>,
+  #[serde(default)]
+  pub margin_model: Option<String>,
+  #[serde(default)]
+  pub portfolio_margining_enabled: Option<bool>,
+  #[serde(default)]
+  pub cross_collateral_enabled: Option<bool>,
+  /// Only returned with `extended`
+  #[serde(default)]
+  pub id: Option<SubaccountId>,
+  /// Only returned with `extended`
+  #[serde(default)]
+  pub username: Option<String>,
+  /// Only returned with `extended`
+  #[serde(default)]
+  pub email: Option<String>,
+  /// Only returned with `extended`
+  #[serde(default)]
+  pub system_name: Option<String>,
+  /// Only returned with `extended`
+  #[serde(default, rename = "type")]
+  pub account_type: Option<String>,
+  /// Only returned with `extended`
+  #[serde(default)]
+  pub limits: Option<Limits>,
+}
+
+/// Summaries of every currency of an account, as returned by `get_account_summaries`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSummaries {
+  pub id: SubaccountId,
+  #[serde(default)]
+  pub username: Option<String>,
+  #[serde(default)]
+  pub email: Option<String>,
+  #[serde(default)]
+  pub system_name: Option<String>,
+  #[serde(default, rename = "type")]
+  pub account_type: Option<String>,
+  pub summaries: Vec<AccountSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubaccountPortfolio {
+  pub currency: Currency,
+  pub balance: f64,
+  pub equity: f64,
+  pub available_funds: f64,
+  pub available_withdrawal_funds: f64,
+  pub margin_balance: f64,
+  pub initial_margin: f64,
+  pub maintenance_margin: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subaccount {
+  pub id: SubaccountId,
+  pub username: String,
+  pub email: String,
+  #[serde(rename = "type")]
+  pub account_type: String,
+  #[serde(default)]
+  pub system_name: Option<String>,
+  #[serde(default)]
+  pub is_password: bool,
+  #[serde(default)]
+  pub login_enabled: bool,
+  #[serde(default)]
+  pub receive_notifications: bool,
+  #[serde(default)]
+  pub security_keys_enabled: bool,
+  #[serde(default)]
+  pub margin_model: Option<String>,
+  /// Balances by lowercase currency, only returned with `with_portfolio`
+  #[serde(default)]
+  pub portfolio: Option<HashMap<String, SubaccountPortfolio>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubaccountDetails {
+  pub uid: SubaccountId,
+  pub positions: Vec<Position>,
+  /// Only returned with `with_open_orders`
+  #[serde(default)]
+  pub open_orders: Option<Vec<Order>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionLogEntry {
+  pub id: i64,
+  pub user_id: SubaccountId,
+  pub username: String,
+  pub user_seq: i64,
+  #[serde(rename = "type")]
+  pub transaction_type: String,
+  pub timestamp: i64,
+  pub currency: Currency,
+  #[serde(default)]
+  pub amount: Option<f64>,
+  pub balance: f64,
+  #[serde(default)]
+  pub cashflow: Option<f64>,
+  #[serde(default)]
+  pub change: Option<f64>,
+  #[serde(default)]
+  pub equity: Option<f64>,
+  #[serde(default)]
+  pub commission: Option<f64>,
+  #[serde(default)]
+  pub instrument_name: Option<String>,
+  #[serde(default)]
+  pub position: Option<f64>,
+  #[serde(default)]
+  pub price: Option<f64>,
+  #[serde(default)]
+  pub side: Option<String>,
+  #[serde(default)]
+  pub order_id: Option<String>,
+  #[serde(default)]
+  pub trade_id: Option<String>,
+  #[serde(default)]
+  pub info: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionLogPage {
+  pub logs: Vec<TransactionLogEntry>,
+  /// ID to pass as `continuation` for the next page; `None` on the last one.
+  #[serde(default)]
+  pub continuation: Option<i64>,
+}
+
+impl<C: RpcTransport> PrivateClient<C> {
+  /// Retrieves the user's position on an instrument.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_position)
+  pub async fn get_position(&mut self, instrument_name: &str) -> Result<Position, Error> {
+    let params = serde_json::json!({ "instrument_name": instrument_name });
+    self.authed_call("private/get_position", params).await
+  }
+
+  /// Retrieves the user's positions.
+  /// - `currency` - Only return positions in this currency, if given.
+  /// - `kind` - Only return positions on instruments of this kind, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_positions)
+  pub async fn get_positions(&mut self, currency: Option<Currency>, kind: Option<Kind>) -> Result<Vec<Position>, Error> {
+    let mut params = serde_json::json!({});
+    if let Some(currency) = currency {
+      params["currency"] = serde_json::to_value(currency)?;
+    }
+    if let Some(kind) = kind {
+      params["kind"] = serde_json::to_value(kind)?;
+    }
+    self.authed_call("private/get_positions", params).await
+  }
+
+  /// Retrieves the user's account summary in a currency.
+  /// - `currency` - The currency of the summary.
+  /// - `extended` - Include account details (id, username, limits...).
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_account_summary)
+  pub async fn get_account_summary(&mut self, currency: Currency, extended: bool) -> Result<AccountSummary, Error> {
+    let params = serde_json::json!({ "currency": currency, "extended": extended });
+    self.authed_call("private/get_account_summary", params).await
+  }
+
+  /// Retrieves the user's account summaries in every currency.
+  /// - `extended` - Include account details (id, username, limits...).
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_account_summaries)
+  pub async fn get_account_summaries(&mut self, extended: bool) -> Result<AccountSummaries, Error> {
+    let params = serde_json::json!({ "extended": extended });
+    self.authed_call("private/get_account_summaries", params).await
+  }
+
+  /// Retrieves the main account and its subaccounts. The returned IDs can be passed to `exchange_token` and `switch_subaccount`.
+  /// - `with_portfolio` - Include balances.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_subaccounts)
+  pub async fn get_subaccounts(&mut self, with_portfolio: bool) -> Result<Vec<Subaccount>, Error> {
+    let params = serde_json::json!({ "with_portfolio": with_portfolio });
+    self.authed_call("private/get_subaccounts", params).await
+  }
+
+  /// Retrieves the positions (and optionally open orders) of every subaccount in a currency.
+  /// - `currency` - The currency of the positions.
+  /// - `with_open_orders` - Include open orders.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_subaccounts_details)
+  pub async fn get_subaccounts_details(&mut self, currency: Currency, with_open_orders: bool) -> Result<Vec<SubaccountDetails>, Error> {
+    let params = serde_json::json!({ "currency": currency, "with_open_orders": with_open_orders });
+    self.authed_call("private/get_subaccounts_details", params).await
+  }
+
+  /// Creates a new subaccount.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-create_subaccount)
+  pub async fn create_subaccount(&mut self) -> Result<Subaccount, Error> {
+    self.authed_call("private/create_subaccount", serde_json::json!({})).await
+  }
+
+  /// Changes the username of a subaccount.
+  /// - `sid` - The ID of the subaccount.
+  /// - `name` - The new username.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-change_subaccount_name)
+  pub async fn change_subaccount_name(&mut self, sid: impl Into<SubaccountId>, name: &str) -> Result<(), Error> {
+    let params = serde_json::json!({ "sid": sid.into(), "name": name });
+    self.authed_request("private/change_subaccount_name", params).await?.value()?;
+    Ok(())
+  }
+
+  /// Enables or disables login to a subaccount.
+  /// - `sid` - The ID of the subaccount.
+  /// - `enabled` - Whether login should be allowed.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-toggle_subaccount_login)
+  pub async fn toggle_subaccount_login(&mut self, sid: impl Into<SubaccountId>, enabled: bool) -> Result<(), Error> {
+    let state = if enabled { "enable" } else { "disable" };
+    let params = serde_json::json!({ "sid": sid.into(), "state": state });
+    self.authed_request("private/toggle_subaccount_login", params).await?.value()?;
+    Ok(())
+  }
+
+  /// Retrieves one page of the transaction log in a currency.
+  /// - `currency` - The currency of the transactions.
+  /// - `start_timestamp` - Earliest timestamp of the transactions (milliseconds since epoch).
+  /// - `end_timestamp` - Latest timestamp of the transactions (milliseconds since epoch).
+  /// - `query` - Filter, e.g. `"trade"`, `"deposit"` or `"transfer"`, if given.
+  /// - `count` - Page size, if given.
+  /// - `continuation` - `continuation` of the previous page, if any.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_transaction_log)
+  pub async fn get_transaction_log(
+    &mut self,
+    currency: Currency,
+    start_timestamp: i64,
+    end_timestamp: i64,
+    query: Option<&str>,
+    count: Option<u32>,
+    continuation: Option<i64>,
+  ) -> Result<TransactionLogPage, Error> {
+    let mut params = serde_json::json!({
+      "currency": currency,
+      "start_timestamp": start_timestamp,
+      "end_timestamp": end_timestamp,
+    });
+    if let Some(query) = query {
+      params["query"] = serde_json::Value::from(query);
+    }
+    if let Some(count) = count {
+      params["count"] = serde_json::Value::from(count);
+    }
+    if let Some(continuation) = continuation {
+      params["continuation"] = serde_json::Value::from(continuation);
+    }
+    self.authed_call("private/get_transaction_log", params).await
+  }
+}
+
+impl PrivateClient {
+  /// Tunes the client-side rate limiter to the account's actual limits, as reported by its extended account summary.
+  /// - `currency` - The currency whose summary to read the limits from, e.g. one the account holds.
+  pub async fn sync_rate_limits(&mut self, currency: Currency) -> Result<Option<Limits>, Error> {
+    let summary = self.get_account_summary(currency, true).await?;
+    if let Some(ref limits) = summary.limits {
+      self.tune_rate_limiter(limits);
+    }
+    Ok(summary.limits)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::MockDeribit;
+
+  /// Based on the `private/get_account_summary` example in the Deribit docs.
+  fn account_summary() -> serde_json::Value {
+    serde_json::json!({
+      "options_gamma": 0.0,
+      "projected_maintenance_margin": 0.0,
+      "total_pl": 0.0,
+      "margin_balance": 302.62729214,
+      "options_theta": 0.0,
+      "projected_initial_margin": 0.0,
+      "options_delta": 0.0,
+      "session_upl": 0.0,
+      "equity": 302.61869214,
+      "initial_margin": 0.0,
+      "session_rpl": 0.0,
+      "portfolio_margining_enabled": false,
+      "maintenance_margin": 0.0,
+      "currency": "BTC",
+      "options_pl": 0.0,
+      "fee_balance": 0,
+      "available_withdrawal_funds": 301.35396172,
+      "futures_pl": 0.0,
+      "options_vega": 0.0,
+      "delta_total": 0.0,
+      "available_funds": 301.38059622,
+      "balance": 302.60065765,
+      "margin_model": "segregated_sm",
+      "cross_collateral_enabled": false,
+    })
+  }
+
+  #[tokio::test]
+  async fn positions_are_parsed() {
+    let mock = MockDeribit::new();
+    // The `private/get_position` example in the Deribit docs
+    mock.respond("private/get_position", serde_json::json!({
+      "average_price": 0,
+      "delta": 0,
+      "direction": "buy",
+      "estimated_liquidation_price": 0,
+      "floating_profit_loss": 0,
+      "index_price": 3555.86,
+      "initial_margin": 0,
+      "instrument_name": "BTC-PERPETUAL",
+      "interest_value": 1.7362511643080387,
+      "leverage": 100,
+      "kind": "future",
+      "maintenance_margin": 0,
+      "mark_price": 3556.62,
+      "open_orders_margin": 0.000165889,
+      "realized_profit_loss": 0,
+      "settlement_price": 3555.44,
+      "size": 0,
+      "size_currency": 0,
+      "total_profit_loss": 0,
+    }));
+    let mut client = mock.private_client().await.unwrap();
+    let position = client.get_position("BTC-PERPETUAL").await.unwrap();
+    assert_eq!((position.kind, position.direction), (Kind::Future, PositionDirection::Buy));
+    assert_eq!((position.mark_price, position.leverage, position.gamma), (3556.62, Some(100.0), None));
+  }
+
+  #[tokio::test]
+  async fn account_summaries_may_carry_limits() {
+    let mock = MockDeribit::new();
+    mock.respond_once("private/get_account_summary", account_summary());
+    let mut extended = account_summary();
+    extended["id"] = serde_json::json!(10);
+    extended["username"] = serde_json::json!("user");
+    extended["type"] = serde_json::json!("main");
+    extended["limits"] = serde_json::json!({
+      "limits_per_currency": false,
+      "non_matching_engine": { "rate": 30, "burst": 400 },
+      "matching_engine": {
+        "trading": { "total": { "rate": 10, "burst": 50 } },
+        "spot": { "rate": 5, "burst": 20 },
+        "quotes": { "rate": 500, "burst": 500 },
+      },
+    });
+    mock.respond("private/get_account_summary", extended);
+    let mut client = mock.private_client().await.unwrap();
+
+    let summary = client.get_account_summary(Currency::BTC, false).await.unwrap();
+    assert_eq!((summary.currency, summary.equity), (Currency::BTC, 302.61869214));
+    assert!(summary.id.is_none() && summary.limits.is_none());
+
+    let limits = client.sync_rate_limits(Currency::ETH).await.unwrap().unwrap();
+    assert_eq!(mock.assert_requested("private/get_account_summary").params, serde_json::json!({
+      "currency": "ETH",
+      "extended": true,
+      "access_token": crate::mock::MOCK_ACCESS_TOKEN,
+    }));
+    assert_eq!(limits.matching_engine_limit().map(|limit| (limit.rate, limit.burst)), Some((10.0, 50.0)));
+    assert_eq!(limits.non_matching_engine.map(|limit| (limit.rate, limit.burst)), Some((30.0, 400.0)));
+  }
+
+  #[tokio::test]
+  async fn subaccounts_are_parsed_with_their_portfolio() {
+    let mock = MockDeribit::new();
+    let portfolio = |currency: &str, balance: f64| serde_json::json!({
+      "available_funds": balance,
+      "available_withdrawal_funds": balance,
+      "balance": balance,
+      "currency": currency,
+      "equity": balance,
+      "initial_margin": 0,
+      "maintenance_margin": 0,
+      "margin_balance": balance,
+    });
+    // Based on the `private/get_subaccounts` example in the Deribit docs
+    mock.respond("private/get_subaccounts", serde_json::json!([
+      {
+        "email": "user_AAA@email.com",
+        "id": 2,
+        "is_password": true,
+        "login_enabled": true,
+        "portfolio": { "eth": portfolio("eth", 0.0), "btc": portfolio("btc", 1.5) },
+        "receive_notifications": false,
+        "system_name": "user_1",
+        "security_keys_enabled": false,
+        "type": "main",
+        "username": "user_1",
+      },
+      {
+        "email": "user_AAA@gmail.com",
+        "id": 7,
+        "is_password": true,
+        "login_enabled": false,
+        "receive_notifications": false,
+        "system_name": "user_1_1",
+        "security_keys_enabled": false,
+        "type": "subaccount",
+        "username": "user_1_1",
+      },
+    ]));
+    let mut client = mock.private_client().await.unwrap();
+    let accounts = client.get_subaccounts(true).await.unwrap();
+    assert_eq!(accounts.iter().map(|account| account.id).collect::<Vec<_>>(), [SubaccountId(2), SubaccountId(7)]);
+    let portfolio = accounts[0].portfolio.as_ref().unwrap();
+    assert_eq!(portfolio["btc"].currency, Currency::BTC);
+    assert_eq!(portfolio["btc"].balance, 1.5);
+    assert!(accounts[1].portfolio.is_none() && !accounts[1].login_enabled);
+  }
+
+  #[tokio::test]
+  async fn transaction_log_pages_carry_their_continuation() {
+    let mock = MockDeribit::new();
+    // Based on the `private/get_transaction_log` example in the Deribit docs
+    mock.respond("private/get_transaction_log", serde_json::json!({
+      "logs": [{
+        "username": "Alice",
+        "user_seq": 3,
+        "user_id": 7,
+        "type": "transfer",
+        "trade_id": null,
+        "timestamp": 1613659830333i64,
+        "side": "-",
+        "price": null,
+        "position": null,
+        "order_id": null,
+        "interest_pl": null,
+        "instrument_name": null,
+        "info": { "transfer_type": "subaccount", "other_user_id": 27, "other_user": "Bob" },
+        "id": 61,
+        "equity": 3000.9275869,
+        "currency": "BTC",
+        "commission": 0,
+        "change": -2.5,
+        "cashflow": -2.5,
+        "balance": 3001.22270418,
+      }],
+      "continuation": 61,
+    }));
+    let mut client = mock.private_client().await.unwrap();
+    let page = client.get_transaction_log(Currency::BTC, 1613657734000, 1613660407000, Some("transfer"), Some(5), None).await.unwrap();
+    assert_eq!(page.continuation, Some(61));
+    let entry = &page.logs[0];
+    assert_eq!((entry.transaction_type.as_str(), entry.user_id, entry.change, entry.price), ("transfer", SubaccountId(7), Some(-2.5), None));
+    assert_eq!(entry.info.as_ref().unwrap()["other_user"], "Bob");
+    let req = mock.assert_requested("private/get_transaction_log");
+    assert_eq!((req.params["query"].as_str(), req.params["count"].as_u64()), (Some("transfer"), Some(5)));
+  }
+}
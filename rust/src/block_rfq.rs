@@ -0,0 +1,533 @@
+use serde::{Deserialize, Serialize};
+
+use crate::block_trade::BlockTrade;
+use crate::core::{Error, PrivateClient, RpcTransport};
+use crate::subscriptions::Subscription;
+use crate::types::{Currency, Direction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockRfqRole {
+  Maker,
+  Taker,
+}
+
+impl BlockRfqRole {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      BlockRfqRole::Maker => "maker",
+      BlockRfqRole::Taker => "taker",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockRfqState {
+  Created,
+  Open,
+  Filled,
+  Traded,
+  Cancelled,
+  Expired,
+  Closed,
+  #[serde(other)]
+  Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteState {
+  Open,
+  Filled,
+  Cancelled,
+  Expired,
+  #[serde(other)]
+  Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionInstruction {
+  AllOrNone,
+  AnyPartOf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RfqTimeInForce {
+  FillOrKill,
+  GoodTilCancelled,
+}
+
+/// Leg of a new RFQ, sized in the instrument's amount units
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RfqLegRequest {
+  pub instrument_name: String,
+  pub direction: Direction,
+  pub amount: f64,
+}
+
+/// Leg of an RFQ, sized relative to the RFQ's `amount`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRfqLeg {
+  pub instrument_name: String,
+  pub direction: Direction,
+  pub ratio: f64,
+}
+
+/// Leg of a quote, with its own price
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteLeg {
+  pub instrument_name: String,
+  pub direction: Direction,
+  pub ratio: f64,
+  pub price: f64,
+}
+
+/// Future or perpetual traded alongside the RFQ to hedge its delta
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRfqHedge {
+  pub instrument_name: String,
+  pub direction: Direction,
+  pub amount: f64,
+  pub price: f64,
+}
+
+/// Best quote on one side of an RFQ, as seen by the taker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RfqQuoteLevel {
+  pub price: f64,
+  pub amount: f64,
+  #[serde(default)]
+  pub execution_instruction: Option<ExecutionInstruction>,
+  #[serde(default)]
+  pub makers: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRfq {
+  pub block_rfq_id: i64,
+  pub state: BlockRfqState,
+  pub role: BlockRfqRole,
+  pub amount: f64,
+  pub legs: Vec<BlockRfqLeg>,
+  pub creation_timestamp: i64,
+  pub expiration_timestamp: i64,
+  #[serde(default)]
+  pub combo_id: Option<String>,
+  #[serde(default)]
+  pub hedge: Option<BlockRfqHedge>,
+  #[serde(default)]
+  pub label: Option<String>,
+  #[serde(default)]
+  pub makers: Option<Vec<String>>,
+  #[serde(default)]
+  pub min_trade_amount: Option<f64>,
+  #[serde(default)]
+  pub taker_rating: Option<String>,
+  /// Best bids, only visible to the taker
+  #[serde(default)]
+  pub bids: Option<Vec<RfqQuoteLevel>>,
+  /// Best asks, only visible to the taker
+  #[serde(default)]
+  pub asks: Option<Vec<RfqQuoteLevel>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRfqQuote {
+  pub block_rfq_quote_id: i64,
+  pub block_rfq_id: i64,
+  pub quote_state: QuoteState,
+  pub direction: Direction,
+  pub amount: f64,
+  pub price: f64,
+  pub legs: Vec<QuoteLeg>,
+  pub creation_timestamp: i64,
+  pub last_update_timestamp: i64,
+  #[serde(default)]
+  pub filled_amount: Option<f64>,
+  #[serde(default)]
+  pub execution_instruction: Option<ExecutionInstruction>,
+  #[serde(default)]
+  pub hedge: Option<BlockRfqHedge>,
+  #[serde(default)]
+  pub label: Option<String>,
+  #[serde(default)]
+  pub expires_at: Option<i64>,
+  #[serde(default)]
+  pub replaced: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRfqsPage {
+  pub block_rfqs: Vec<BlockRfq>,
+  #[serde(default)]
+  pub continuation: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcceptedBlockRfq {
+  pub block_trades: Vec<BlockTrade>,
+}
+
+/// A new RFQ, as sent to `create_block_rfq`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockRfqRequest {
+  pub legs: Vec<RfqLegRequest>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub hedge: Option<BlockRfqHedge>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub label: Option<String>,
+  /// Only these makers receive the RFQ; all of them if `None`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub makers: Option<Vec<String>>,
+  /// Disclose the taker's identity to the makers
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub non_anonymous: Option<bool>,
+}
+
+impl BlockRfqRequest {
+  pub fn new(legs: Vec<RfqLegRequest>) -> Self {
+    BlockRfqRequest { legs, hedge: None, label: None, makers: None, non_anonymous: None }
+  }
+
+  pub fn hedge(mut self, hedge: BlockRfqHedge) -> Self {
+    self.hedge = Some(hedge);
+    self
+  }
+
+  pub fn label(mut self, label: &str) -> Self {
+    self.label = Some(label.to_string());
+    self
+  }
+
+  pub fn makers(mut self, makers: &[&str]) -> Self {
+    self.makers = Some(makers.iter().map(|m| m.to_string()).collect());
+    self
+  }
+
+  pub fn non_anonymous(mut self, non_anonymous: bool) -> Self {
+    self.non_anonymous = Some(non_anonymous);
+    self
+  }
+}
+
+/// A maker's quote, as sent to `add_block_rfq_quote` and `edit_block_rfq_quote`.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuoteRequest {
+  pub amount: f64,
+  pub legs: Vec<QuoteLeg>,
+  /// Price of the whole structure; derived from the leg prices if `None`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub price: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub hedge: Option<BlockRfqHedge>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub label: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub execution_instruction: Option<ExecutionInstruction>,
+  /// Milliseconds since epoch
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub expires_at: Option<i64>,
+}
+
+impl QuoteRequest {
+  pub fn new(amount: f64, legs: Vec<QuoteLeg>) -> Self {
+    QuoteRequest { amount, legs, price: None, hedge: None, label: None, execution_instruction: None, expires_at: None }
+  }
+
+  pub fn price(mut self, price: f64) -> Self {
+    self.price = Some(price);
+    self
+  }
+
+  pub fn hedge(mut self, hedge: BlockRfqHedge) -> Self {
+    self.hedge = Some(hedge);
+    self
+  }
+
+  pub fn label(mut self, label: &str) -> Self {
+    self.label = Some(label.to_string());
+    self
+  }
+
+  pub fn execution_instruction(mut self, execution_instruction: ExecutionInstruction) -> Self {
+    self.execution_instruction = Some(execution_instruction);
+    self
+  }
+
+  pub fn expires_at(mut self, expires_at: i64) -> Self {
+    self.expires_at = Some(expires_at);
+    self
+  }
+}
+
+/// Filters for `get_block_rfqs`. All fields are optional.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BlockRfqQuery {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub currency: Option<Currency>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub state: Option<BlockRfqState>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub role: Option<BlockRfqRole>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub block_rfq_id: Option<i64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub count: Option<u32>,
+  /// Token from the previous page's `continuation`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub continuation: Option<String>,
+}
+
+impl<C: RpcTransport> PrivateClient<C> {
+  /// Sends a new RFQ to the makers, as taker. Requires `block_rfq:read_write`.
+  /// - `request` - The legs and options of the RFQ.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-create_block_rfq)
+  pub async fn create_block_rfq(&mut self, request: &BlockRfqRequest) -> Result<BlockRfq, Error> {
+    self.scope().block_rfq().require_write("block_rfq:read_write")?;
+    let params = serde_json::to_value(request)?;
+    self.authed_call("private/create_block_rfq", params).await
+  }
+
+  /// Cancels an open RFQ, as taker. Requires `block_rfq:read_write`.
+  /// - `block_rfq_id` - The ID of the RFQ.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-cancel_block_rfq)
+  pub async fn cancel_block_rfq(&mut self, block_rfq_id: i64) -> Result<BlockRfq, Error> {
+    self.scope().block_rfq().require_write("block_rfq:read_write")?;
+    let params = serde_json::json!({ "block_rfq_id": block_rfq_id });
+    self.authed_call("private/cancel_block_rfq", params).await
+  }
+
+  /// Trades against the quotes of an RFQ, as taker. Requires `block_rfq:read_write`.
+  /// - `block_rfq_id` - The ID of the RFQ.
+  /// - `legs` - The legs of the RFQ, as returned by `create_block_rfq`.
+  /// - `direction` - Side of the taker on the whole structure.
+  /// - `amount` - Amount to trade.
+  /// - `price` - Worst acceptable price of the whole structure.
+  /// - `time_in_force` - Whether the unfilled amount stays open.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-accept_block_rfq)
+  pub async fn accept_block_rfq(&mut self, block_rfq_id: i64, legs: &[BlockRfqLeg], direction: Direction, amount: f64, price: f64, time_in_force: RfqTimeInForce) -> Result<AcceptedBlockRfq, Error> {
+    self.scope().block_rfq().require_write("block_rfq:read_write")?;
+    let params = serde_json::json!({
+      "block_rfq_id": block_rfq_id,
+      "legs": legs,
+      "direction": direction,
+      "amount": amount,
+      "price": price,
+      "time_in_force": time_in_force,
+    });
+    self.authed_call("private/accept_block_rfq", params).await
+  }
+
+  /// Retrieves one page of the user's RFQs. Requires `block_rfq:read`.
+  /// - `query` - Filters, page size and continuation.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_block_rfqs)
+  pub async fn get_block_rfqs(&mut self, query: &BlockRfqQuery) -> Result<BlockRfqsPage, Error> {
+    self.scope().block_rfq().require_read("block_rfq:read")?;
+    let params = serde_json::to_value(query)?;
+    self.authed_call("private/get_block_rfqs", params).await
+  }
+
+  /// Quotes one side of an RFQ, as maker. Requires `block_rfq:read_write`.
+  /// - `block_rfq_id` - The ID of the RFQ.
+  /// - `direction` - Side of the maker on the whole structure.
+  /// - `quote` - Amount, leg prices and options.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-add_block_rfq_quote)
+  pub async fn add_block_rfq_quote(&mut self, block_rfq_id: i64, direction: Direction, quote: &QuoteRequest) -> Result<BlockRfqQuote, Error> {
+    self.scope().block_rfq().require_write("block_rfq:read_write")?;
+    let mut params = serde_json::to_value(quote)?;
+    params["block_rfq_id"] = serde_json::Value::from(block_rfq_id);
+    params["direction"] = serde_json::to_value(direction)?;
+    self.authed_call("private/add_block_rfq_quote", params).await
+  }
+
+  /// Replaces the amount and prices of a quote, as maker. Requires `block_rfq:read_write`.
+  /// - `block_rfq_quote_id` - The ID of the quote.
+  /// - `quote` - The new amount, leg prices and options.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-edit_block_rfq_quote)
+  pub async fn edit_block_rfq_quote(&mut self, block_rfq_quote_id: i64, quote: &QuoteRequest) -> Result<BlockRfqQuote, Error> {
+    self.scope().block_rfq().require_write("block_rfq:read_write")?;
+    let mut params = serde_json::to_value(quote)?;
+    params["block_rfq_quote_id"] = serde_json::Value::from(block_rfq_quote_id);
+    self.authed_call("private/edit_block_rfq_quote", params).await
+  }
+
+  /// Cancels a quote, as maker. Requires `block_rfq:read_write`.
+  /// - `block_rfq_quote_id` - The ID of the quote.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-cancel_block_rfq_quote)
+  pub async fn cancel_block_rfq_quote(&mut self, block_rfq_quote_id: i64) -> Result<BlockRfqQuote, Error> {
+    self.scope().block_rfq().require_write("block_rfq:read_write")?;
+    let params = serde_json::json!({ "block_rfq_quote_id": block_rfq_quote_id });
+    self.authed_call("private/cancel_block_rfq_quote", params).await
+  }
+
+  /// Cancels all of the maker's quotes, returning how many were cancelled. Requires `block_rfq:read_write`.
+  /// - `block_rfq_id` - Only cancel quotes on this RFQ, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-cancel_all_block_rfq_quotes)
+  pub async fn cancel_all_block_rfq_quotes(&mut self, block_rfq_id: Option<i64>) -> Result<u64, Error> {
+    self.scope().block_rfq().require_write("block_rfq:read_write")?;
+    let mut params = serde_json::json!({});
+    if let Some(block_rfq_id) = block_rfq_id {
+      params["block_rfq_id"] = serde_json::Value::from(block_rfq_id);
+    }
+    self.authed_call("private/cancel_all_block_rfq_quotes", params).await
+  }
+
+  /// Retrieves the maker's open quotes. Requires `block_rfq:read`.
+  /// - `block_rfq_id` - Only return quotes on this RFQ, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_block_rfq_quotes)
+  pub async fn get_block_rfq_quotes(&mut self, block_rfq_id: Option<i64>) -> Result<Vec<BlockRfqQuote>, Error> {
+    self.scope().block_rfq().require_read("block_rfq:read")?;
+    let mut params = serde_json::json!({});
+    if let Some(block_rfq_id) = block_rfq_id {
+      params["block_rfq_id"] = serde_json::Value::from(block_rfq_id);
+    }
+    self.authed_call("private/get_block_rfq_quotes", params).await
+  }
+}
+
+impl PrivateClient {
+  /// Streams RFQ updates of a currency: as maker, every RFQ sent to the user; as taker, the user's own RFQs
+  /// with their best quotes. Requires `block_rfq:read`.
+  /// - `role` - Subscribes to `block_rfq.maker.{currency}` or `block_rfq.taker.{currency}`.
+  /// - `currency` - The currency of the RFQs, or `Currency::Any`.
+  ///
+  /// ```ignore
+  /// let mut rfqs = client.block_rfq_updates(BlockRfqRole::Maker, Currency::BTC).await?;
+  /// while let Some(update) = rfqs.recv().await {
+  ///   let rfq = update?.data;
+  ///   if rfq.state == BlockRfqState::Open {
+  ///     client.add_block_rfq_quote(rfq.block_rfq_id, Direction::Sell, &quote_for(&rfq)).await?;
+  ///   }
+  /// }
+  /// ```
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#block_rfq-maker-currency)
+  pub async fn block_rfq_updates(&mut self, role: BlockRfqRole, currency: Currency) -> Result<Subscription<BlockRfq>, Error> {
+    self.scope().block_rfq().require_read("block_rfq:read")?;
+    let channel = format!("block_rfq.{}.{}", role.as_str(), currency);
+    self.subscribe(&[&channel]).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::MockDeribit;
+
+  /// Shaped like the `block_rfq.taker.{currency}` example in the Deribit docs.
+  fn rfq(role: &str, state: &str) -> serde_json::Value {
+    serde_json::json!({
+      "block_rfq_id": 507,
+      "state": state,
+      "role": role,
+      "amount": 20000,
+      "min_trade_amount": 10000,
+      "legs": [
+        { "instrument_name": "BTC-15NOV24", "direction": "buy", "ratio": 1 },
+        { "instrument_name": "BTC-PERPETUAL", "direction": "sell", "ratio": 1 },
+      ],
+      "combo_id": "BTC-FS-15NOV24_PERP",
+      "creation_timestamp": 1731062187555i64,
+      "expiration_timestamp": 1731062487555i64,
+      "makers": ["MAKER1"],
+      "bids": [{ "price": 69600.0, "amount": 20000, "execution_instruction": "all_or_none" }],
+      "asks": [],
+    })
+  }
+
+  /// Shaped like the `private/add_block_rfq_quote` example in the Deribit docs.
+  fn quote() -> serde_json::Value {
+    serde_json::json!({
+      "block_rfq_quote_id": 8,
+      "block_rfq_id": 507,
+      "quote_state": "open",
+      "direction": "buy",
+      "amount": 20000,
+      "price": 69600.0,
+      "legs": [
+        { "instrument_name": "BTC-15NOV24", "direction": "buy", "ratio": 1, "price": 70000.0 },
+        { "instrument_name": "BTC-PERPETUAL", "direction": "sell", "ratio": 1, "price": 400.0 },
+      ],
+      "execution_instruction": "all_or_none",
+      "creation_timestamp": 1731062190000i64,
+      "last_update_timestamp": 1731062190000i64,
+    })
+  }
+
+  #[tokio::test]
+  async fn takers_see_their_quotes_and_accept_them() {
+    let mock = MockDeribit::new();
+    mock.respond("private/create_block_rfq", rfq("taker", "created"));
+    mock.respond("private/accept_block_rfq", serde_json::json!({ "block_trades": [] }));
+    let mut client = mock.private_client().await.unwrap();
+    let mut updates = client.block_rfq_updates(BlockRfqRole::Taker, Currency::BTC).await.unwrap();
+    let req = mock.assert_requested("private/subscribe");
+    assert_eq!(req.params["channels"], serde_json::json!(["block_rfq.taker.BTC"]));
+
+    let request = BlockRfqRequest::new(vec![
+      RfqLegRequest { instrument_name: "BTC-15NOV24".to_string(), direction: Direction::Buy, amount: 20000.0 },
+      RfqLegRequest { instrument_name: "BTC-PERPETUAL".to_string(), direction: Direction::Sell, amount: 20000.0 },
+    ]).makers(&["MAKER1"]);
+    let created = client.create_block_rfq(&request).await.unwrap();
+    assert_eq!((created.block_rfq_id, created.state), (507, BlockRfqState::Created));
+
+    mock.notify("block_rfq.taker.BTC", rfq("taker", "open"));
+    let update = updates.recv().await.unwrap().unwrap();
+    assert_eq!(update.channel, "block_rfq.taker.BTC");
+    let rfq = update.data;
+    assert_eq!((rfq.state, rfq.role), (BlockRfqState::Open, BlockRfqRole::Taker));
+    let best_bid = &rfq.bids.as_ref().unwrap()[0];
+    assert_eq!((best_bid.price, best_bid.execution_instruction), (69600.0, Some(ExecutionInstruction::AllOrNone)));
+
+    client.accept_block_rfq(rfq.block_rfq_id, &rfq.legs, Direction::Sell, rfq.amount, best_bid.price, RfqTimeInForce::FillOrKill).await.unwrap();
+    let req = mock.assert_requested("private/accept_block_rfq");
+    assert_eq!((req.params["block_rfq_id"].as_i64(), req.params["direction"].as_str()), (Some(507), Some("sell")));
+    assert_eq!((req.params["price"].as_f64(), req.params["time_in_force"].as_str()), (Some(69600.0), Some("fill_or_kill")));
+    assert_eq!(req.params["legs"][1], serde_json::json!({ "instrument_name": "BTC-PERPETUAL", "direction": "sell", "ratio": 1.0 }));
+  }
+
+  #[tokio::test]
+  async fn makers_quote_the_rfqs_sent_to_them() {
+    let mock = MockDeribit::new();
+    mock.respond("private/add_block_rfq_quote", quote());
+    mock.respond("private/cancel_all_block_rfq_quotes", serde_json::json!(1));
+    let mut client = mock.private_client().await.unwrap();
+    let mut updates = client.block_rfq_updates(BlockRfqRole::Maker, Currency::Any).await.unwrap();
+    let req = mock.assert_requested("private/subscribe");
+    assert_eq!(req.params["channels"], serde_json::json!(["block_rfq.maker.any"]));
+
+    let mut sent = rfq("maker", "open");
+    sent.as_object_mut().unwrap().remove("bids");
+    mock.notify("block_rfq.maker.any", sent);
+    let rfq = updates.recv().await.unwrap().unwrap().data;
+    assert_eq!(rfq.role, BlockRfqRole::Maker);
+    assert!(rfq.bids.is_none());
+
+    let legs = vec![
+      QuoteLeg { instrument_name: "BTC-15NOV24".to_string(), direction: Direction::Buy, ratio: 1.0, price: 70000.0 },
+      QuoteLeg { instrument_name: "BTC-PERPETUAL".to_string(), direction: Direction::Sell, ratio: 1.0, price: 400.0 },
+    ];
+    let request = QuoteRequest::new(rfq.amount, legs).execution_instruction(ExecutionInstruction::AllOrNone);
+    let quote = client.add_block_rfq_quote(rfq.block_rfq_id, Direction::Buy, &request).await.unwrap();
+    assert_eq!((quote.block_rfq_quote_id, quote.quote_state), (8, QuoteState::Open));
+    let req = mock.assert_requested("private/add_block_rfq_quote");
+    assert_eq!((req.params["block_rfq_id"].as_i64(), req.params["direction"].as_str()), (Some(507), Some("buy")));
+    assert_eq!(req.params["execution_instruction"], "all_or_none");
+    assert_eq!(req.params["legs"][0]["price"], 70000.0);
+
+    assert_eq!(client.cancel_all_block_rfq_quotes(Some(507)).await.unwrap(), 1);
+    assert_eq!(mock.assert_requested("private/cancel_all_block_rfq_quotes").params["block_rfq_id"], 507);
+  }
+}
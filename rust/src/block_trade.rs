@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::{Error, PrivateClient, RpcTransport};
+use crate::trading::Trade;
+use crate::types::{Currency, Direction};
+
+/// Signature of a verified block trade, handed to the counterparty so it can execute the trade
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BlockTradeSignature(pub String);
+
+impl std::fmt::Display for BlockTradeSignature {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockTradeRole {
+  Maker,
+  Taker,
+}
+
+/// Leg of a block trade. `direction` is always from the maker's point of view.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockTradeLeg {
+  pub instrument_name: String,
+  pub direction: Direction,
+  pub price: f64,
+  pub amount: f64,
+}
+
+/// Terms of a block trade, agreed by both parties. Both must use the same `timestamp`, `nonce` and `trades`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockTradeRequest {
+  /// Milliseconds since epoch; the trade must be executed within 60 seconds of it.
+  pub timestamp: i64,
+  pub nonce: String,
+  pub role: BlockTradeRole,
+  pub trades: Vec<BlockTradeLeg>,
+}
+
+impl BlockTradeRequest {
+  /// New terms, timestamped now.
+  /// - `nonce` - Unique string identifying the trade.
+  /// - `role` - The role of the current user.
+  /// - `trades` - The legs of the trade.
+  pub fn new(nonce: &str, role: BlockTradeRole, trades: Vec<BlockTradeLeg>) -> Self {
+    let timestamp = std::time::SystemTime::now()
+      .duration_since(std::time::UNIX_EPOCH)
+      .map(|d| d.as_millis() as i64)
+      .unwrap_or_default();
+    BlockTradeRequest { timestamp, nonce: nonce.to_string(), role, trades }
+  }
+
+  /// The same terms, seen by the counterparty.
+  pub fn counterparty(&self) -> Self {
+    let role = match self.role {
+      BlockTradeRole::Maker => BlockTradeRole::Taker,
+      BlockTradeRole::Taker => BlockTradeRole::Maker,
+    };
+    BlockTradeRequest { role, ..self.clone() }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockTrade {
+  pub id: String,
+  pub timestamp: i64,
+  pub trades: Vec<Trade>,
+  #[serde(default)]
+  pub app_name: Option<String>,
+  #[serde(default)]
+  pub broker_code: Option<String>,
+  #[serde(default)]
+  pub broker_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockTradeState {
+  pub value: String,
+  pub timestamp: i64,
+}
+
+/// Block trade waiting for approval by one of the parties
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingBlockTrade {
+  pub timestamp: i64,
+  pub nonce: String,
+  pub role: BlockTradeRole,
+  pub trades: Vec<BlockTradeLeg>,
+  #[serde(default)]
+  pub user_id: Option<i64>,
+  #[serde(default)]
+  pub username: Option<String>,
+  pub state: BlockTradeState,
+  #[serde(default)]
+  pub counterparty_state: Option<BlockTradeState>,
+  #[serde(default)]
+  pub app_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Signed {
+  signature: BlockTradeSignature,
+}
+
+impl<C: RpcTransport> PrivateClient<C> {
+  /// Verifies the terms of a block trade and signs them. Requires `block_trade:read_write`.
+  /// - `request` - The terms of the trade, with the current user's role.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-verify_block_trade)
+  pub async fn verify_block_trade(&mut self, request: &BlockTradeRequest) -> Result<BlockTradeSignature, Error> {
+    self.scope().block_trade().require_write("block_trade:read_write")?;
+    let params = serde_json::to_value(request)?;
+    let signed = self.authed_call::<Signed>("private/verify_block_trade", params).await?;
+    Ok(signed.signature)
+  }
+
+  /// Executes a block trade verified by the counterparty. Requires `block_trade:read_write`.
+  /// - `request` - The terms of the trade, with the current user's role.
+  /// - `counterparty_signature` - The signature returned to the counterparty by `verify_block_trade`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-execute_block_trade)
+  pub async fn execute_block_trade(&mut self, request: &BlockTradeRequest, counterparty_signature: &BlockTradeSignature) -> Result<BlockTrade, Error> {
+    self.scope().block_trade().require_write("block_trade:read_write")?;
+    let mut params = serde_json::to_value(request)?;
+    params["counterparty_signature"] = serde_json::to_value(counterparty_signature)?;
+    self.authed_call("private/execute_block_trade", params).await
+  }
+
+  /// Retrieves an executed block trade. Requires `block_trade:read`.
+  /// - `id` - The ID of the block trade.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_block_trade)
+  pub async fn get_block_trade(&mut self, id: &str) -> Result<BlockTrade, Error> {
+    self.scope().block_trade().require_read("block_trade:read")?;
+    let params = serde_json::json!({ "id": id });
+    self.authed_call("private/get_block_trade", params).await
+  }
+
+  /// Retrieves the user's last block trades in a currency, most recent first. Requires `block_trade:read`.
+  /// - `currency` - The currency of the instruments.
+  /// - `count` - Number of block trades to return.
+  /// - `start_id` - Only return block trades up to this ID, if given.
+  /// - `end_id` - Only return block trades from this ID, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_last_block_trades_by_currency)
+  pub async fn get_last_block_trades_by_currency(&mut self, currency: Currency, count: u32, start_id: Option<&str>, end_id: Option<&str>) -> Result<Vec<BlockTrade>, Error> {
+    self.scope().block_trade().require_read("block_trade:read")?;
+    let mut params = serde_json::json!({ "currency": currency, "count": count });
+    if let Some(start_id) = start_id {
+      params["start_id"] = serde_json::Value::from(start_id);
+    }
+    if let Some(end_id) = end_id {
+      params["end_id"] = serde_json::Value::from(end_id);
+    }
+    self.authed_call("private/get_last_block_trades_by_currency", params).await
+  }
+
+  /// Approves a pending block trade. Requires `block_trade:read_write`.
+  /// - `timestamp` - The timestamp of the trade, as shown by `get_pending_block_trades`.
+  /// - `nonce` - The nonce of the trade.
+  /// - `role` - The current user's role in the trade.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-approve_block_trade)
+  pub async fn approve_block_trade(&mut self, timestamp: i64, nonce: &str, role: BlockTradeRole) -> Result<(), Error> {
+    self.scope().block_trade().require_write("block_trade:read_write")?;
+    let params = serde_json::json!({ "timestamp": timestamp, "nonce": nonce, "role": role });
+    self.authed_request("private/approve_block_trade", params).await?.value()?;
+    Ok(())
+  }
+
+  /// Rejects a pending block trade. Requires `block_trade:read_write`.
+  /// - `timestamp` - The timestamp of the trade, as shown by `get_pending_block_trades`.
+  /// - `nonce` - The nonce of the trade.
+  /// - `role` - The current user's role in the trade.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-reject_block_trade)
+  pub async fn reject_block_trade(&mut self, timestamp: i64, nonce: &str, role: BlockTradeRole) -> Result<(), Error> {
+    self.scope().block_trade().require_write("block_trade:read_write")?;
+    let params = serde_json::json!({ "timestamp": timestamp, "nonce": nonce, "role": role });
+    self.authed_request("private/reject_block_trade", params).await?.value()?;
+    Ok(())
+  }
+
+  /// Retrieves block trades waiting for approval. Requires `block_trade:read`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_pending_block_trades)
+  pub async fn get_pending_block_trades(&mut self) -> Result<Vec<PendingBlockTrade>, Error> {
+    self.scope().block_trade().require_read("block_trade:read")?;
+    self.authed_call("private/get_pending_block_trades", serde_json::json!({})).await
+  }
+
+  /// Invalidates a signature returned by `verify_block_trade`, so the counterparty can no longer execute the trade.
+  /// Requires `block_trade:read_write`.
+  /// - `signature` - The signature to invalidate.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-invalidate_block_trade_signature)
+  pub async fn invalidate_block_trade_signature(&mut self, signature: &BlockTradeSignature) -> Result<(), Error> {
+    self.scope().block_trade().require_write("block_trade:read_write")?;
+    let params = serde_json::json!({ "signature": signature });
+    self.authed_request("private/invalidate_block_trade_signature", params).await?.value()?;
+    Ok(())
+  }
+}
@@ -0,0 +1,478 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+use crate::account::{AccountSummary, Position};
+use crate::core::{parse_json, Backpressure, Error, Notification, Pattern, PrivateClient, SocketClient};
+use crate::market_data::Ticker;
+use crate::order_book::{BookChange, BookChangeKind, Level};
+use crate::subscriptions::{Subscription, Update};
+use crate::trading::{Order, Trade};
+use crate::types::{Currency, Direction, Kind};
+
+/// How often notifications are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interval {
+  /// Every change; only for authorized connections
+  Raw,
+  /// Aggregated every 100 milliseconds
+  Ms100,
+  /// Aggregated every 2 seconds
+  Agg2,
+}
+
+impl Interval {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Interval::Raw => "raw",
+      Interval::Ms100 => "100ms",
+      Interval::Agg2 => "agg2",
+    }
+  }
+
+  pub fn parse(interval: &str) -> Option<Self> {
+    match interval {
+      "raw" => Some(Interval::Raw),
+      "100ms" => Some(Interval::Ms100),
+      "agg2" => Some(Interval::Agg2),
+      _ => None,
+    }
+  }
+}
+
+/// Instruments covered by a trades or user channel.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Target {
+  /// A single instrument, e.g. `"BTC-PERPETUAL"`
+  Instrument(String),
+  /// Every instrument of a kind (`None` for any kind) and currency (or `Currency::Any`)
+  Kind(Option<Kind>, Currency),
+}
+
+impl fmt::Display for Target {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Target::Instrument(instrument_name) => f.write_str(instrument_name),
+      Target::Kind(kind, currency) => write!(f, "{}.{}", kind_str(*kind), currency),
+    }
+  }
+}
+
+fn kind_str(kind: Option<Kind>) -> &'static str {
+  kind.map_or("any", |kind| kind.as_str())
+}
+
+fn parse_kind(kind: &str) -> Result<Option<Kind>, Error> {
+  match kind {
+    "any" => Ok(None),
+    kind => Kind::parse(kind).map(Some).ok_or(Error::Logic("Unknown instrument kind in channel")),
+  }
+}
+
+fn parse_interval(interval: &str) -> Result<Interval, Error> {
+  Interval::parse(interval).ok_or(Error::Logic("Unknown interval in channel"))
+}
+
+/// Parses `{instrument}.{interval}` or `{kind}.{currency}.{interval}`.
+fn parse_target(parts: &[&str]) -> Result<(Target, Interval), Error> {
+  match parts {
+    [instrument_name, interval] => Ok((Target::Instrument(instrument_name.to_string()), parse_interval(interval)?)),
+    [kind, currency, interval] => Ok((Target::Kind(parse_kind(kind)?, Currency::parse(currency)), parse_interval(interval)?)),
+    _ => Err(Error::Logic("Malformed channel")),
+  }
+}
+
+/// A subscription channel. Converts to and from the wire name with `to_string` and `parse`.
+///
+/// ```ignore
+/// let channel = Channel::Ticker { instrument_name: "BTC-PERPETUAL".into(), interval: Interval::Ms100 };
+/// assert_eq!(channel.to_string(), "ticker.BTC-PERPETUAL.100ms");
+/// assert_eq!("ticker.BTC-PERPETUAL.100ms".parse::<Channel>()?, channel);
+/// ```
+///
+/// Source: [Deribit docs](https://docs.deribit.com/#subscriptions)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Channel {
+  /// `book.{instrument_name}.{interval}`
+  Book { instrument_name: String, interval: Interval },
+  /// `book.{instrument_name}.{group}.{depth}.{interval}`
+  GroupedBook { instrument_name: String, group: String, depth: u32, interval: Interval },
+  /// `ticker.{instrument_name}.{interval}`
+  Ticker { instrument_name: String, interval: Interval },
+  /// `trades.{instrument_name}.{interval}` or `trades.{kind}.{currency}.{interval}`
+  Trades { target: Target, interval: Interval },
+  /// `incremental_ticker.{instrument_name}`
+  IncrementalTicker { instrument_name: String },
+  /// `deribit_price_index.{index_name}`
+  PriceIndex { index_name: String },
+  /// `perpetual.{instrument_name}.{interval}`
+  Perpetual { instrument_name: String, interval: Interval },
+  /// `markprice.options.{index_name}`
+  MarkPriceOptions { index_name: String },
+  /// `estimated_expiration_price.{index_name}`
+  EstimatedExpirationPrice { index_name: String },
+  /// `user.orders.{instrument_name}.{interval}` or `user.orders.{kind}.{currency}.{interval}`
+  UserOrders { target: Target, interval: Interval },
+  /// `user.trades.{instrument_name}.{interval}` or `user.trades.{kind}.{currency}.{interval}`
+  UserTrades { target: Target, interval: Interval },
+  /// `user.portfolio.{currency}`
+  UserPortfolio { currency: Currency },
+  /// `user.changes.{instrument_name}.{interval}` or `user.changes.{kind}.{currency}.{interval}`
+  UserChanges { target: Target, interval: Interval },
+  /// `user.mmp_trigger.{index_name}`
+  UserMmpTrigger { index_name: String },
+  /// `instrument.state.{kind}.{currency}`
+  InstrumentState { kind: Option<Kind>, currency: Currency },
+  /// `platform_state`
+  PlatformState,
+}
+
+impl fmt::Display for Channel {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Channel::Book { instrument_name, interval } => write!(f, "book.{}.{}", instrument_name, interval.as_str()),
+      Channel::GroupedBook { instrument_name, group, depth, interval } => write!(f, "book.{}.{}.{}.{}", instrument_name, group, depth, interval.as_str()),
+      Channel::Ticker { instrument_name, interval } => write!(f, "ticker.{}.{}", instrument_name, interval.as_str()),
+      Channel::Trades { target, interval } => write!(f, "trades.{}.{}", target, interval.as_str()),
+      Channel::IncrementalTicker { instrument_name } => write!(f, "incremental_ticker.{}", instrument_name),
+      Channel::PriceIndex { index_name } => write!(f, "deribit_price_index.{}", index_name),
+      Channel::Perpetual { instrument_name, interval } => write!(f, "perpetual.{}.{}", instrument_name, interval.as_str()),
+      Channel::MarkPriceOptions { index_name } => write!(f, "markprice.options.{}", index_name),
+      Channel::EstimatedExpirationPrice { index_name } => write!(f, "estimated_expiration_price.{}", index_name),
+      Channel::UserOrders { target, interval } => write!(f, "user.orders.{}.{}", target, interval.as_str()),
+      Channel::UserTrades { target, interval } => write!(f, "user.trades.{}.{}", target, interval.as_str()),
+      Channel::UserPortfolio { currency } => write!(f, "user.portfolio.{}", currency),
+      Channel::UserChanges { target, interval } => write!(f, "user.changes.{}.{}", target, interval.as_str()),
+      Channel::UserMmpTrigger { index_name } => write!(f, "user.mmp_trigger.{}", index_name),
+      Channel::InstrumentState { kind, currency } => write!(f, "instrument.state.{}.{}", kind_str(*kind), currency),
+      Channel::PlatformState => f.write_str("platform_state"),
+    }
+  }
+}
+
+impl FromStr for Channel {
+  type Err = Error;
+
+  fn from_str(channel: &str) -> Result<Self, Error> {
+    let parts: Vec<&str> = channel.split('.').collect();
+    let channel = match parts.as_slice() {
+      ["book", instrument_name, interval] => Channel::Book { instrument_name: instrument_name.to_string(), interval: parse_interval(interval)? },
+      ["book", instrument_name, group, depth, interval] => Channel::GroupedBook {
+        instrument_name: instrument_name.to_string(),
+        group: group.to_string(),
+        depth: depth.parse().map_err(|_| Error::Logic("Malformed book depth in channel"))?,
+        interval: parse_interval(interval)?,
+      },
+      ["ticker", instrument_name, interval] => Channel::Ticker { instrument_name: instrument_name.to_string(), interval: parse_interval(interval)? },
+      ["trades", rest @ ..] => {
+        let (target, interval) = parse_target(rest)?;
+        Channel::Trades { target, interval }
+      }
+      ["incremental_ticker", instrument_name] => Channel::IncrementalTicker { instrument_name: instrument_name.to_string() },
+      ["deribit_price_index", index_name] => Channel::PriceIndex { index_name: index_name.to_string() },
+      ["perpetual", instrument_name, interval] => Channel::Perpetual { instrument_name: instrument_name.to_string(), interval: parse_interval(interval)? },
+      ["markprice", "options", index_name] => Channel::MarkPriceOptions { index_name: index_name.to_string() },
+      ["estimated_expiration_price", index_name] => Channel::EstimatedExpirationPrice { index_name: index_name.to_string() },
+      ["user", "orders", rest @ ..] => {
+        let (target, interval) = parse_target(rest)?;
+        Channel::UserOrders { target, interval }
+      }
+      ["user", "trades", rest @ ..] => {
+        let (target, interval) = parse_target(rest)?;
+        Channel::UserTrades { target, interval }
+      }
+      ["user", "portfolio", currency] => Channel::UserPortfolio { currency: Currency::parse(currency) },
+      ["user", "changes", rest @ ..] => {
+        let (target, interval) = parse_target(rest)?;
+        Channel::UserChanges { target, interval }
+      }
+      ["user", "mmp_trigger", index_name] => Channel::UserMmpTrigger { index_name: index_name.to_string() },
+      ["instrument", "state", kind, currency] => Channel::InstrumentState { kind: parse_kind(kind)?, currency: Currency::parse(currency) },
+      ["platform_state"] => Channel::PlatformState,
+      _ => return Err(Error::Logic("Unknown channel")),
+    };
+    Ok(channel)
+  }
+}
+
+impl From<Channel> for String {
+  fn from(channel: Channel) -> String {
+    channel.to_string()
+  }
+}
+
+/// Payload of `book.{instrument_name}.{group}.{depth}.{interval}` notifications: the full top of the book, not a diff
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupedBook {
+  pub instrument_name: String,
+  pub timestamp: i64,
+  pub change_id: i64,
+  pub bids: Vec<Level>,
+  pub asks: Vec<Level>,
+}
+
+/// Payload of `incremental_ticker` notifications. Changes only carry the fields that changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncrementalTicker {
+  #[serde(rename = "type")]
+  pub kind: BookChangeKind,
+  pub instrument_name: String,
+  pub timestamp: i64,
+  /// The other ticker fields, as in `Ticker`
+  #[serde(flatten)]
+  pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublicTrade {
+  pub trade_id: String,
+  pub trade_seq: i64,
+  pub instrument_name: String,
+  pub timestamp: i64,
+  pub direction: Direction,
+  pub price: f64,
+  pub amount: f64,
+  #[serde(default)]
+  pub contracts: Option<f64>,
+  pub index_price: f64,
+  pub mark_price: f64,
+  pub tick_direction: i64,
+  #[serde(default)]
+  pub iv: Option<f64>,
+  #[serde(default)]
+  pub liquidation: Option<String>,
+  #[serde(default)]
+  pub block_trade_id: Option<String>,
+  #[serde(default)]
+  pub combo_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceIndex {
+  pub index_name: String,
+  pub price: f64,
+  pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Perpetual {
+  /// Current interest rate
+  pub interest: f64,
+  pub index_price: f64,
+  pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkPrice {
+  pub instrument_name: String,
+  pub mark_price: f64,
+  pub iv: f64,
+  pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EstimatedExpirationPrice {
+  /// Seconds left to expiration
+  pub seconds: i64,
+  pub price: f64,
+  pub is_estimated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserChanges {
+  pub instrument_name: String,
+  pub trades: Vec<Trade>,
+  pub positions: Vec<Position>,
+  pub orders: Vec<Order>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MmpTrigger {
+  pub index_name: String,
+  /// Milliseconds since epoch; 0 if frozen until reset manually
+  pub frozen_until: i64,
+  #[serde(default)]
+  pub mmp_group: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstrumentStateChange {
+  pub instrument_name: String,
+  /// e.g. `"created"`, `"started"`, `"settled"`, `"closed"` or `"terminated"`
+  pub state: String,
+  pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlatformState {
+  #[serde(default)]
+  pub price_index: Option<String>,
+  #[serde(default)]
+  pub locked: Option<bool>,
+  #[serde(default)]
+  pub maintenance: Option<bool>,
+  #[serde(default)]
+  pub allow_unauthenticated_public_requests: Option<bool>,
+}
+
+/// A notification payload, typed according to its channel.
+#[derive(Debug, Clone)]
+pub enum ChannelData {
+  Book(BookChange),
+  GroupedBook(GroupedBook),
+  Ticker(Box<Ticker>),
+  Trades(Vec<PublicTrade>),
+  IncrementalTicker(IncrementalTicker),
+  PriceIndex(PriceIndex),
+  Perpetual(Perpetual),
+  MarkPriceOptions(Vec<MarkPrice>),
+  EstimatedExpirationPrice(EstimatedExpirationPrice),
+  UserOrders(Vec<Order>),
+  UserTrades(Vec<Trade>),
+  UserPortfolio(Box<AccountSummary>),
+  UserChanges(UserChanges),
+  UserMmpTrigger(MmpTrigger),
+  InstrumentState(InstrumentStateChange),
+  PlatformState(PlatformState),
+}
+
+/// Raw channels send a single item, aggregated ones send a list.
+fn one_or_many<T: serde::de::DeserializeOwned>(data: serde_json::Value) -> Result<Vec<T>, Error> {
+  if data.is_array() {
+    parse_json(data)
+  } else {
+    Ok(vec![parse_json(data)?])
+  }
+}
+
+impl Channel {
+  /// Parse a notification payload received on this channel.
+  pub fn parse_data(&self, data: serde_json::Value) -> Result<ChannelData, Error> {
+    let data = match self {
+      Channel::Book { .. } => ChannelData::Book(parse_json(data)?),
+      Channel::GroupedBook { .. } => ChannelData::GroupedBook(parse_json(data)?),
+      Channel::Ticker { .. } => ChannelData::Ticker(parse_json(data)?),
+      Channel::Trades { .. } => ChannelData::Trades(parse_json(data)?),
+      Channel::IncrementalTicker { .. } => ChannelData::IncrementalTicker(parse_json(data)?),
+      Channel::PriceIndex { .. } => ChannelData::PriceIndex(parse_json(data)?),
+      Channel::Perpetual { .. } => ChannelData::Perpetual(parse_json(data)?),
+      Channel::MarkPriceOptions { .. } => ChannelData::MarkPriceOptions(parse_json(data)?),
+      Channel::EstimatedExpirationPrice { .. } => ChannelData::EstimatedExpirationPrice(parse_json(data)?),
+      Channel::UserOrders { .. } => ChannelData::UserOrders(one_or_many(data)?),
+      Channel::UserTrades { .. } => ChannelData::UserTrades(parse_json(data)?),
+      Channel::UserPortfolio { .. } => ChannelData::UserPortfolio(parse_json(data)?),
+      Channel::UserChanges { .. } => ChannelData::UserChanges(parse_json(data)?),
+      Channel::UserMmpTrigger { .. } => ChannelData::UserMmpTrigger(parse_json(data)?),
+      Channel::InstrumentState { .. } => ChannelData::InstrumentState(parse_json(data)?),
+      Channel::PlatformState => ChannelData::PlatformState(parse_json(data)?),
+    };
+    Ok(data)
+  }
+}
+
+/// Patterns matching the notifications of a subscribed channel: the channel itself, plus the per-instrument
+/// channels Deribit may deliver on when subscribing to every kind of a currency, or to `any` currency.
+pub(crate) fn patterns(channel: &str) -> Vec<Pattern> {
+  let mut patterns = vec![Pattern::exact(channel)];
+  let expanded = match channel.parse::<Channel>() {
+    Ok(Channel::Trades { target: Target::Kind(None, currency), interval }) => Some(("trades", currency, interval)),
+    Ok(Channel::UserOrders { target: Target::Kind(None, currency), interval }) => Some(("user.orders", currency, interval)),
+    Ok(Channel::UserTrades { target: Target::Kind(None, currency), interval }) => Some(("user.trades", currency, interval)),
+    Ok(Channel::UserChanges { target: Target::Kind(None, currency), interval }) => Some(("user.changes", currency, interval)),
+    _ => None,
+  };
+  if let Some((prefix, currency, interval)) = expanded {
+    let interval = interval.as_str();
+    match currency {
+      Currency::Any => patterns.push(Pattern::glob(&format!("{}.*.{}", prefix, interval))),
+      currency => {
+        // e.g. BTC-PERPETUAL and BTC_USDC-PERPETUAL
+        patterns.push(Pattern::glob(&format!("{}.{}-*.{}", prefix, currency, interval)));
+        patterns.push(Pattern::glob(&format!("{}.{}_*.{}", prefix, currency, interval)));
+      }
+    }
+  }
+  patterns
+}
+
+pub(crate) fn parse_channel_update(notif: Notification) -> Result<Update<ChannelData>, Error> {
+  let channel = notif.params.channel.parse::<Channel>()?;
+  let data = channel.parse_data(notif.params.data)?;
+  Ok(Update { channel: notif.params.channel, data })
+}
+
+impl SocketClient {
+  /// Subscribe to typed **public** channels. Each notification is parsed according to its channel.
+  /// - `channels` - The channels to subscribe to.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-subscribe)
+  pub async fn subscribe_channels(&self, channels: &[Channel]) -> Result<Subscription<ChannelData>, Error> {
+    let channels: Vec<String> = channels.iter().map(|c| c.to_string()).collect();
+    let channels: Vec<&str> = channels.iter().map(|c| c.as_str()).collect();
+    crate::subscriptions::subscribe_with(self, "public/subscribe", "public/unsubscribe", &channels, None, parse_channel_update, Backpressure::for_channels(&channels)).await
+  }
+}
+
+impl PrivateClient {
+  /// Subscribe to typed public or private channels. Each notification is parsed according to its channel.
+  /// - `channels` - The channels to subscribe to.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-subscribe)
+  pub async fn subscribe_channels(&mut self, channels: &[Channel]) -> Result<Subscription<ChannelData>, Error> {
+    if self.auth().expired() {
+      self.refresh_token().await?;
+    }
+    let channels: Vec<String> = channels.iter().map(|c| c.to_string()).collect();
+    let channels: Vec<&str> = channels.iter().map(|c| c.as_str()).collect();
+    crate::subscriptions::subscribe_with(&self.client, "private/subscribe", "private/unsubscribe", &channels, Some(self.shared_auth().clone()), parse_channel_update, Backpressure::for_channels(&channels)).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn channels_round_trip_through_their_names() {
+    let names = [
+      "book.BTC-PERPETUAL.raw",
+      "book.ETH-PERPETUAL.none.10.100ms",
+      "ticker.BTC_USDC-PERPETUAL.agg2",
+      "trades.BTC-PERPETUAL.100ms",
+      "trades.option.ETH.raw",
+      "trades.any.any.100ms",
+      "incremental_ticker.BTC-27DEC24",
+      "deribit_price_index.btc_usd",
+      "perpetual.BTC-PERPETUAL.raw",
+      "markprice.options.btc_usd",
+      "estimated_expiration_price.eth_usd",
+      "user.orders.BTC-PERPETUAL.raw",
+      "user.orders.future.BTC.100ms",
+      "user.trades.any.USDC.raw",
+      "user.portfolio.BTC",
+      "user.changes.option.any.100ms",
+      "user.mmp_trigger.btc_usd",
+      "instrument.state.future.ETH",
+      "platform_state",
+    ];
+    for name in names {
+      let channel: Channel = name.parse().unwrap();
+      assert_eq!(channel.to_string(), name);
+    }
+  }
+
+  #[test]
+  fn channels_parse_into_their_parts() {
+    let channel: Channel = "user.orders.option.any.raw".parse().unwrap();
+    assert_eq!(channel, Channel::UserOrders { target: Target::Kind(Some(Kind::Option), Currency::Any), interval: Interval::Raw });
+    let channel: Channel = "book.BTC-PERPETUAL.1.20.agg2".parse().unwrap();
+    assert_eq!(channel, Channel::GroupedBook { instrument_name: "BTC-PERPETUAL".into(), group: "1".into(), depth: 20, interval: Interval::Agg2 });
+  }
+
+  #[test]
+  fn malformed_channels_are_rejected() {
+    for name in ["ticker.BTC-PERPETUAL.1s", "book.BTC-PERPETUAL.none.many.raw", "user.orders.raw", "trades.swap.BTC.raw", "quotes.BTC-PERPETUAL"] {
+      assert!(name.parse::<Channel>().is_err(), "{} should not parse", name);
+    }
+  }
+}
@@ -0,0 +1,313 @@
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{Error, PrivateClient, RpcTransport};
+use crate::trading::{OrderRequest, OrderResponse};
+use crate::types::{Currency, Direction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComboState {
+  Active,
+  Inactive,
+  Rfq,
+}
+
+/// Leg of an existing combo. `amount` is the signed ratio: positive legs are bought when the combo is bought.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComboLeg {
+  pub instrument_name: String,
+  pub amount: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Combo {
+  /// The combo instrument name, e.g. `"BTC-FS-29DEC23_PERP"`
+  pub id: String,
+  pub instrument_id: i64,
+  pub state: ComboState,
+  pub state_timestamp: i64,
+  pub creation_timestamp: i64,
+  pub legs: Vec<ComboLeg>,
+}
+
+/// Leg of a trade, as sent to `create_combo` and `get_leg_prices`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeLeg {
+  pub instrument_name: String,
+  pub direction: Direction,
+  pub amount: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegPrice {
+  pub instrument_name: String,
+  pub direction: Direction,
+  pub price: f64,
+  pub ratio: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegPrices {
+  pub amount: f64,
+  pub legs: Vec<LegPrice>,
+}
+
+/// Combo book endpoints, available on any transport.
+pub trait ComboApi: RpcTransport {
+  /// Retrieves the active combos of a currency.
+  /// - `currency` - The currency of the combos, or `Currency::Any`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_combos)
+  fn get_combos(&self, currency: Currency) -> impl Future<Output = Result<Vec<Combo>, Error>> + Send {
+    let params = serde_json::json!({ "currency": currency });
+    self.call("public/get_combos", params)
+  }
+
+  /// Retrieves the IDs of the combos of a currency.
+  /// - `currency` - The currency of the combos.
+  /// - `state` - Only return combos in this state, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_combo_ids)
+  fn get_combo_ids(&self, currency: Currency, state: Option<ComboState>) -> impl Future<Output = Result<Vec<String>, Error>> + Send {
+    async move {
+      let mut params = serde_json::json!({ "currency": currency });
+      if let Some(state) = state {
+        params["state"] = serde_json::to_value(state)?;
+      }
+      self.call("public/get_combo_ids", params).await
+    }
+  }
+
+  /// Retrieves a combo by ID.
+  /// - `combo_id` - e.g. `"BTC-FS-29DEC23_PERP"`
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_combo_details)
+  fn get_combo_details(&self, combo_id: &str) -> impl Future<Output = Result<Combo, Error>> + Send {
+    let params = serde_json::json!({ "combo_id": combo_id });
+    self.call("public/get_combo_details", params)
+  }
+
+  /// Splits a combo price into individual leg prices.
+  /// - `legs` - The legs of the combo, with their amounts.
+  /// - `price` - The price of the whole combo.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_leg_prices)
+  fn get_leg_prices(&self, legs: &[TradeLeg], price: f64) -> impl Future<Output = Result<LegPrices, Error>> + Send {
+    let params = serde_json::json!({ "legs": legs, "price": price });
+    self.call("public/get_leg_prices", params)
+  }
+}
+
+impl<C: RpcTransport> ComboApi for C {}
+
+impl<C: RpcTransport> PrivateClient<C> {
+  /// Creates a combo book from the given legs, or returns the existing one matching them.
+  /// - `trades` - The legs of the combo.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-create_combo)
+  pub async fn create_combo(&mut self, trades: &[TradeLeg]) -> Result<Combo, Error> {
+    let params = serde_json::json!({ "trades": trades });
+    self.authed_call("private/create_combo", params).await
+  }
+}
+
+/// A combo instrument resolved from a `ComboBuilder`
+#[derive(Debug, Clone)]
+pub struct ResolvedCombo {
+  pub combo: Combo,
+  /// The exchange lists the combo with every leg reversed: buying it sells the requested legs, and vice versa.
+  pub inverted: bool,
+}
+
+/// Assembles the legs of a spread and finds (or creates) the matching combo instrument.
+///
+/// ```ignore
+/// let combo = ComboBuilder::new()
+///   .leg("BTC-PERPETUAL", Direction::Sell, 1)
+///   .leg("BTC-27DEC24", Direction::Buy, 1);
+/// combo.buy(&mut client, OrderRequest::limit(10.0, 250.0)).await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ComboBuilder {
+  legs: Vec<(String, Direction, u32)>,
+}
+
+impl ComboBuilder {
+  pub fn new() -> Self {
+    ComboBuilder::default()
+  }
+
+  /// Add a leg, traded in `direction` when the combo is bought.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  /// - `direction` - Side of the leg when buying the combo.
+  /// - `ratio` - Size of the leg relative to the others.
+  pub fn leg(mut self, instrument_name: &str, direction: Direction, ratio: u32) -> Self {
+    self.legs.push((instrument_name.to_string(), direction, ratio));
+    self
+  }
+
+  /// The legs, as sent to `create_combo` and `get_leg_prices`.
+  pub fn trade_legs(&self) -> Vec<TradeLeg> {
+    self.legs.iter()
+      .map(|(instrument_name, direction, ratio)| TradeLeg { instrument_name: instrument_name.clone(), direction: *direction, amount: *ratio as f64 })
+      .collect()
+  }
+
+  /// Legs as signed ratios, sorted by instrument, for comparison with `Combo::legs`.
+  fn signed_legs(&self) -> Vec<ComboLeg> {
+    let mut legs: Vec<ComboLeg> = self.legs.iter()
+      .map(|(instrument_name, direction, ratio)| {
+        let sign = if *direction == Direction::Buy { 1 } else { -1 };
+        ComboLeg { instrument_name: instrument_name.clone(), amount: sign * *ratio as i64 }
+      })
+      .collect();
+    legs.sort_by(|a, b| a.instrument_name.cmp(&b.instrument_name));
+    legs
+  }
+
+  /// Whether `combo` has the requested legs, either as is (`Some(false)`) or reversed (`Some(true)`).
+  fn matches(&self, combo: &Combo) -> Option<bool> {
+    let wanted = self.signed_legs();
+    let mut legs = combo.legs.clone();
+    legs.sort_by(|a, b| a.instrument_name.cmp(&b.instrument_name));
+    let inverted: Vec<ComboLeg> = wanted.iter()
+      .map(|leg| ComboLeg { instrument_name: leg.instrument_name.clone(), amount: -leg.amount })
+      .collect();
+    if legs == wanted {
+      Some(false)
+    } else if legs == inverted {
+      Some(true)
+    } else {
+      None
+    }
+  }
+
+  /// Find the listed combo with these legs, creating it if there is none.
+  pub async fn resolve<C: RpcTransport>(&self, client: &mut PrivateClient<C>) -> Result<ResolvedCombo, Error> {
+    let Some((first, _, _)) = self.legs.first() else {
+      return Err(Error::Logic("A combo needs at least one leg"));
+    };
+    // Linear instruments are named e.g. `BTC_USDC-PERPETUAL` and list their combos under the settlement currency
+    let underlying = first.split('-').next().unwrap_or(first);
+    let currency = Currency::parse(underlying.split_once('_').map_or(underlying, |(_, settlement)| settlement));
+    for combo in client.get_combos(currency).await? {
+      if let Some(inverted) = self.matches(&combo) {
+        return Ok(ResolvedCombo { combo, inverted });
+      }
+    }
+    let combo = client.create_combo(&self.trade_legs()).await?;
+    match self.matches(&combo) {
+      Some(inverted) => Ok(ResolvedCombo { combo, inverted }),
+      None => Err(Error::Logic("The created combo doesn't match the requested legs")),
+    }
+  }
+
+  /// Resolve the combo and buy it, i.e. trade every leg in its given direction.
+  pub async fn buy<C: RpcTransport>(&self, client: &mut PrivateClient<C>, order: OrderRequest) -> Result<OrderResponse, Error> {
+    self.place(client, Direction::Buy, order).await
+  }
+
+  /// Resolve the combo and sell it, i.e. trade every leg against its given direction.
+  pub async fn sell<C: RpcTransport>(&self, client: &mut PrivateClient<C>, order: OrderRequest) -> Result<OrderResponse, Error> {
+    self.place(client, Direction::Sell, order).await
+  }
+
+  async fn place<C: RpcTransport>(&self, client: &mut PrivateClient<C>, direction: Direction, order: OrderRequest) -> Result<OrderResponse, Error> {
+    let resolved = self.resolve(client).await?;
+    let direction = if resolved.inverted { direction.opposite() } else { direction };
+    let mut order = order;
+    if resolved.inverted {
+      order.price = order.price.map(|price| -price);
+    }
+    match direction {
+      Direction::Buy => client.buy(&resolved.combo.id, order).await,
+      Direction::Sell => client.sell(&resolved.combo.id, order).await,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::MockDeribit;
+
+  /// Based on the `private/create_combo` example in the Deribit docs.
+  fn future_spread() -> serde_json::Value {
+    serde_json::json!({
+      "state_timestamp": 1650960943922i64,
+      "state": "active",
+      "legs": [
+        { "instrument_name": "BTC-29APR22", "amount": 1 },
+        { "instrument_name": "BTC-PERPETUAL", "amount": -1 },
+      ],
+      "instrument_id": 3,
+      "id": "BTC-FS-29APR22_PERP",
+      "creation_timestamp": 1650960943000i64,
+    })
+  }
+
+  #[tokio::test]
+  async fn the_combo_is_created_once_and_then_reused() {
+    let mock = MockDeribit::new();
+    mock.respond_once("public/get_combos", serde_json::json!([]));
+    mock.respond("public/get_combos", serde_json::json!([future_spread()]));
+    mock.respond("private/create_combo", future_spread());
+    let mut client = mock.private_client().await.unwrap();
+    let builder = ComboBuilder::new()
+      .leg("BTC-PERPETUAL", Direction::Sell, 1)
+      .leg("BTC-29APR22", Direction::Buy, 1);
+
+    let created = builder.resolve(&mut client).await.unwrap();
+    assert_eq!(created.combo.id, "BTC-FS-29APR22_PERP");
+    assert!(!created.inverted);
+    let req = mock.assert_requested("public/get_combos");
+    assert_eq!(req.params["currency"], "BTC");
+    let req = mock.assert_requested("private/create_combo");
+    assert_eq!(req.params["trades"], serde_json::json!([
+      { "instrument_name": "BTC-PERPETUAL", "direction": "sell", "amount": 1.0 },
+      { "instrument_name": "BTC-29APR22", "direction": "buy", "amount": 1.0 },
+    ]));
+
+    let reused = builder.resolve(&mut client).await.unwrap();
+    assert_eq!(reused.combo.id, "BTC-FS-29APR22_PERP");
+    assert_eq!(mock.requests_to("public/get_combos").len(), 2);
+    assert_eq!(mock.requests_to("private/create_combo").len(), 1);
+  }
+
+  #[tokio::test]
+  async fn reversed_legs_trade_the_listed_combo_the_other_way() {
+    let mock = MockDeribit::new();
+    mock.respond("public/get_combos", serde_json::json!([future_spread()]));
+    // The combo order the exchange would place, shaped like the `order` of the `private/sell` example in the Deribit docs
+    mock.respond("private/sell", serde_json::json!({
+      "order": {
+        "order_id": "BTC-FS-1",
+        "order_state": "open",
+        "order_type": "limit",
+        "instrument_name": "BTC-FS-29APR22_PERP",
+        "direction": "sell",
+        "price": -250.0,
+        "amount": 10.0,
+        "time_in_force": "good_til_cancelled",
+        "creation_timestamp": 1650960944000i64,
+        "last_update_timestamp": 1650960944000i64,
+      },
+      "trades": [],
+    }));
+    let mut client = mock.private_client().await.unwrap();
+    let builder = ComboBuilder::new()
+      .leg("BTC-PERPETUAL", Direction::Buy, 1)
+      .leg("BTC-29APR22", Direction::Sell, 1);
+
+    assert!(builder.resolve(&mut client).await.unwrap().inverted);
+    let placed = builder.buy(&mut client, OrderRequest::limit(10.0, 250.0)).await.unwrap();
+    assert_eq!(placed.order.direction, Direction::Sell);
+    mock.assert_not_requested("private/create_combo");
+    mock.assert_not_requested("private/buy");
+    let req = mock.assert_requested("private/sell");
+    assert_eq!(req.params["instrument_name"], "BTC-FS-29APR22_PERP");
+    assert_eq!(req.params["price"], -250.0);
+  }
+}
@@ -1,7 +1,8 @@
 use std::sync::{Arc, Mutex};
 
-use serde::{Deserialize, Serialize};
-use crate::core::{parse_json, Error, SocketClient, Response, Scope};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use crate::core::{parse_json, Error, RpcTransport, SocketClient, Response, Scope};
+use crate::types::SubaccountId;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthResponse {
@@ -38,6 +39,10 @@ impl Auth {
   }
 }
 
+/// The credentials of a session, shared by every clone of its `PrivateClient` and by the connection that restores them
+/// after a reconnect, so a token refreshed anywhere is seen everywhere.
+pub type SharedAuth = Arc<Mutex<Auth>>;
+
 impl SocketClient {
   /// Authenticate an existing public client session. Returns the authentication details; use `authenticated` to get a `PrivateClient`.
   /// - `client_id` - The client ID provided by Deribit.
@@ -45,15 +50,9 @@ impl SocketClient {
   /// - `scope` - The scope of the new private session, e.g. `deribit::Scope::default()`.
   ///
   /// Source: [Deribit docs](https://docs.deribit.com/#public-auth)
-  pub async fn authenticate(&mut self, client_id: &str, client_secret: &str, scope: Scope) -> Result<Auth, Error> {
-    let params = serde_json::json!({
-      "grant_type": "client_credentials",
-      "client_id": client_id,
-      "client_secret": client_secret,
-      "scope": scope.dump(),
-    });
-    let resp = self.request("public/auth", params).await?.value()?;
-    let auth = parse_json::<AuthResponse>(resp)?.parse();
+  pub async fn authenticate(&self, client_id: &str, client_secret: &str, scope: Scope) -> Result<Auth, Error> {
+    let auth = authenticate(self, client_id, client_secret, scope).await?;
+    self.remember_auth(&Arc::new(Mutex::new(auth.clone())));
     Ok(auth)
   }
 
@@ -63,16 +62,38 @@ impl SocketClient {
   /// - `scope` - The scope (i.e. permissions) of the new private session, e.g. `deribit::Scope::default()`.
   ///
   /// Source: [Deribit docs](https://docs.deribit.com/#public-auth)
-  pub async fn authenticated(mut self, client_id: &str, client_secret: &str, scope: Scope) -> Result<PrivateClient, Error> {
-    let auth = self.authenticate(client_id, client_secret, scope).await?;
-    let client = Arc::new(Mutex::new(self));
-    Ok(PrivateClient { client, auth })
+  pub async fn authenticated(self, client_id: &str, client_secret: &str, scope: Scope) -> Result<PrivateClient, Error> {
+    PrivateClient::login(self, client_id, client_secret, scope).await
   }
 }
 
-pub struct PrivateClient {
-  pub client: Arc<Mutex<SocketClient>>,
-  pub auth: Auth,
+async fn authenticate<C: RpcTransport>(client: &C, client_id: &str, client_secret: &str, scope: Scope) -> Result<Auth, Error> {
+  let params = serde_json::json!({
+    "grant_type": "client_credentials",
+    "client_id": client_id,
+    "client_secret": client_secret,
+    "scope": scope.dump(),
+  });
+  let resp = client.request("public/auth", params).await?.value()?;
+  Ok(parse_json::<AuthResponse>(resp)?.parse())
+}
+
+/// Authenticated client over any transport, a `SocketClient` by default.
+/// Cheap to clone: clones share the connection, so requests from many tasks are pipelined over one socket,
+/// and the credentials, so a token refreshed by one clone (or by a reconnect) is used by all of them.
+#[derive(Clone)]
+pub struct PrivateClient<C: RpcTransport = SocketClient> {
+  pub client: C,
+  auth: SharedAuth,
+}
+
+/// Public methods, e.g. market data, are available directly on the private client.
+impl<C: RpcTransport> std::ops::Deref for PrivateClient<C> {
+  type Target = C;
+
+  fn deref(&self) -> &C {
+    &self.client
+  }
 }
 
 impl PrivateClient {
@@ -91,55 +112,118 @@ impl PrivateClient {
     let client = SocketClient::connect(url).await?;
     client.authenticated(client_id, client_secret, scope).await
   }
-  
-  /// Send an unauthenticated request. For **public** methods only.
-  /// - `method` - The API method to call, e.g. `"public/get_instruments"`
-  /// - `params` - The parameters for the request, as a JSON object.
-  pub async fn request(&mut self, method: &str, params: serde_json::Value) -> Result<Response, Error> {
-    self.client.as_ref().lock().unwrap().request(method, params).await
-  }
 
   /// Send an unauthenticated message without waiting for a response.
   /// This is useful for methods that don't return a response, like `private/logout`.
   /// - `method` - The API method to call, e.g. `"private/logout"`
   /// - `params` - The parameters for the request, as a JSON object.
   /// - `id` - The ID of the request. This is generally used to match the response to the request.
-  pub async fn send(&mut self, method: &str, params: serde_json::Value, id: u64) -> Result<(), Error> {
-    self.client.as_ref().lock().unwrap().send(method, params, id).await
+  pub async fn send(&self, method: &str, params: serde_json::Value, id: u64) -> Result<(), Error> {
+    self.client.send(method, params, id).await
+  }
+
+  /// Ask the server to send a heartbeat every `interval`; see `SocketClient::enable_heartbeat`.
+  /// - `interval` - Time between heartbeats; Deribit requires at least 10 seconds.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-set_heartbeat)
+  pub async fn enable_heartbeat(&self, interval: std::time::Duration) -> Result<(), Error> {
+    self.client.enable_heartbeat(interval).await
+  }
+
+  /// Gracefully closes the connection.
+  /// - `invalidate_token` - If true, the access token will be invalidated.
+  /// 
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-logout)
+  pub async fn logout(&self, invalidate_token: bool) -> Result<(), Error> {
+    let params = serde_json::json!({
+      "invalidate_token": invalidate_token,
+      "access_token": self.access_token(),
+    });
+    self.send("private/logout", params, 0).await?; // the server doesn't reply to this method
+    Ok(())
+  }
+}
+
+impl<C: RpcTransport> PrivateClient<C> {
+  /// Authenticate over an existing transport with client credentials.
+  /// - `client` - e.g. a `SocketClient` or an `HttpClient`.
+  /// - `client_id` - The client ID provided by Deribit.
+  /// - `client_secret` - The client secret provided by Deribit.
+  /// - `scope` - The scope (i.e. permissions) of the new private session, e.g. `deribit::Scope::default()`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-auth)
+  pub async fn login(client: C, client_id: &str, client_secret: &str, scope: Scope) -> Result<Self, Error> {
+    let auth = Arc::new(Mutex::new(authenticate(&client, client_id, client_secret, scope).await?));
+    client.remember_auth(&auth);
+    Ok(PrivateClient { client, auth })
+  }
+
+  /// The session's current credentials.
+  pub fn auth(&self) -> Auth {
+    self.auth.lock().unwrap().clone()
+  }
+
+  /// The session's current access token.
+  pub fn access_token(&self) -> String {
+    self.auth.lock().unwrap().response.access_token.clone()
+  }
+
+  /// The permissions granted to the session.
+  pub fn scope(&self) -> Scope {
+    self.auth.lock().unwrap().response.scope.clone()
+  }
+
+  /// The credentials shared by the clones of this client, for work that outlives a borrow of it, e.g. unsubscribing on drop.
+  pub(crate) fn shared_auth(&self) -> &SharedAuth {
+    &self.auth
+  }
+
+  /// Send an unauthenticated request. For **public** methods only.
+  /// - `method` - The API method to call, e.g. `"public/get_instruments"`
+  /// - `params` - The parameters for the request, as a JSON object.
+  pub async fn request(&self, method: &str, params: serde_json::Value) -> Result<Response, Error> {
+    self.client.request(method, params).await
   }
 
   /// Refresh the current access token using the stored refresh token.
   /// 
   /// Source: [Deribit docs](https://docs.deribit.com/#public-auth)
-  pub async fn refresh_token(&mut self) -> Result<&Auth, Error> {
+  pub async fn refresh_token(&mut self) -> Result<Auth, Error> {
     let params = serde_json::json!({
       "grant_type": "refresh_token",
-      "refresh_token": self.auth.response.refresh_token,
+      "refresh_token": self.auth.lock().unwrap().response.refresh_token,
     });
     let resp = self.request("public/auth", params).await?.value()?;
-    self.auth = parse_json::<AuthResponse>(resp)?.parse();
-    Ok(&self.auth)
+    let auth = parse_json::<AuthResponse>(resp)?.parse();
+    *self.auth.lock().unwrap() = auth.clone();
+    self.client.remember_auth(&self.auth);
+    Ok(auth)
   }
 
   /// Send an authenticated request using the current access token.
   pub async fn authed_request(&mut self, method: &str, params: serde_json::Value) -> Result<Response, Error> {
-    if self.auth.expired() {
+    if self.auth.lock().unwrap().expired() {
       self.refresh_token().await?;
     }
-    let mut params = params;
-    params["access_token"] = serde_json::Value::String(self.auth.response.access_token.to_string());
-    self.request(method, params).await
+    let access_token = self.access_token();
+    self.client.authed_request(method, params, &access_token).await
+  }
+
+  /// Send an authenticated request and parse its result into `T`.
+  pub async fn authed_call<T: DeserializeOwned>(&mut self, method: &str, params: serde_json::Value) -> Result<T, Error> {
+    let resp = self.authed_request(method, params).await?.value()?;
+    parse_json::<T>(resp)
   }
 
   /// Exchanges the current access token for a subaccount's token. Doesn't change the current authentication context; use `swtich_subaccount` for that.
-  /// - `subject_id` - The ID of the subaccount to exchange the token for. Can be found on https://deribit.com/account/BTC/subaccounts.
+  /// - `subject_id` - The ID of the subaccount to exchange the token for, as returned by `get_subaccounts`.
   /// - `scope` - Optional scope to request. Permissions cannot exceed those of the current session.
   /// 
   /// Source: [Deribit docs](https://docs.deribit.com/#public-exchange_token)
-  pub async fn exchange_token(&mut self, subject_id: i64, scope: Option<Scope>) -> Result<Auth, Error> {
+  pub async fn exchange_token(&self, subject_id: impl Into<SubaccountId>, scope: Option<Scope>) -> Result<Auth, Error> {
     let mut params = serde_json::json!({
-      "refresh_token": self.auth.response.refresh_token,
-      "subject_id": subject_id,
+      "refresh_token": self.auth.lock().unwrap().response.refresh_token,
+      "subject_id": subject_id.into(),
     });
     if let Some(scope) = scope {
       params["scope"] = serde_json::Value::String(scope.dump());
@@ -150,22 +234,24 @@ impl PrivateClient {
   }
 
   /// Switches the current authentication context to a subaccount
-  /// - `subject_id` - The ID of the subaccount to switch to. Can be found on https://deribit.com/account/BTC/subaccounts.
+  /// - `subject_id` - The ID of the subaccount to switch to, as returned by `get_subaccounts`.
   /// - `scope` - Optional scope to request. Permissions cannot exceed those of the current session.
   /// 
   /// Source: [Deribit docs](https://docs.deribit.com/#public-exchange_token)
-  pub async fn switch_subaccount(&mut self, subject_id: i64, scope: Option<Scope>) -> Result<&Auth, Error> {
-    self.auth = self.exchange_token(subject_id, scope).await?;
-    Ok(&self.auth)
+  pub async fn switch_subaccount(&mut self, subject_id: impl Into<SubaccountId>, scope: Option<Scope>) -> Result<Auth, Error> {
+    let auth = self.exchange_token(subject_id, scope).await?;
+    *self.auth.lock().unwrap() = auth.clone();
+    self.client.remember_auth(&self.auth);
+    Ok(auth)
   }
 
   /// Forks the current access token to a new session with the given name. Doesn't change the current authentication context; use `fork_session` for that.
   /// - `session_name` - The name of the new session. This can be any nonempty string, but should be unique for each session.
   ///
   /// Source: [Deribit docs](https://docs.deribit.com/#public-fork_token)
-  pub async fn fork_token(&mut self, session_name: &str) -> Result<Auth, Error> {
+  pub async fn fork_token(&self, session_name: &str) -> Result<Auth, Error> {
     let params = serde_json::json!({
-      "refresh_token": self.auth.response.refresh_token,
+      "refresh_token": self.auth.lock().unwrap().response.refresh_token,
       "session_name": session_name,
     });
     let val = self.request("public/fork_token", params).await?.value()?;
@@ -177,22 +263,45 @@ impl PrivateClient {
   /// - `session_name` - The name of the new session. This can be any nonempty string, but should be unique for each session.
   /// 
   /// Source: [Deribit docs](https://docs.deribit.com/#public-fork_token)
-  pub async fn fork_session(&mut self, session_name: &str) -> Result<PrivateClient, Error> {
+  pub async fn fork_session(&self, session_name: &str) -> Result<PrivateClient<C>, Error> {
     let auth = self.fork_token(session_name).await?;
-    let client = Arc::clone(&self.client);
-    Ok(PrivateClient { client, auth })
+    Ok(PrivateClient { client: self.client.clone(), auth: Arc::new(Mutex::new(auth)) })
   }
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::{MockDeribit, MOCK_ACCESS_TOKEN, MOCK_SCOPE};
 
-  /// Gracefully closes the connection.
-  /// - `invalidate_token` - If true, the access token will be invalidated.
-  /// 
-  /// Source: [Deribit docs](https://docs.deribit.com/#private-logout)
-  pub async fn logout(&mut self, invalidate_token: bool) -> Result<(), Error> {
-    let params = serde_json::json!({
-      "invalidate_token": invalidate_token,
-      "access_token": self.auth.response.access_token,
-    });
-    self.send("private/logout", params, 0).await?; // the server doesn't reply to this method
-    Ok(())
+  #[tokio::test]
+  async fn subaccount_ids_are_sent_as_plain_numbers() {
+    let mock = MockDeribit::new();
+    mock.on("public/exchange_token", |req| Ok(serde_json::json!({
+      "access_token": format!("token_for_{}", req.params["subject_id"]),
+      "refresh_token": format!("refresh_for_{}", req.params["subject_id"]),
+      "expires_in": 31536000,
+      "scope": MOCK_SCOPE,
+      "token_type": "bearer",
+    })));
+    mock.respond("private/get_positions", serde_json::json!([]));
+    let mut client = mock.private_client().await.unwrap();
+    let other = client.clone();
+
+    let exchanged = client.exchange_token(10, None).await.unwrap();
+    assert_eq!(exchanged.response.access_token, "token_for_10");
+    assert_eq!(mock.assert_requested("public/exchange_token").params["subject_id"], serde_json::json!(10));
+    assert_eq!(client.access_token(), MOCK_ACCESS_TOKEN);
+
+    let id = SubaccountId::from(7);
+    assert_eq!((id.to_string(), serde_json::to_value(id).unwrap()), ("7".to_string(), serde_json::json!(7)));
+    assert_eq!(serde_json::from_value::<SubaccountId>(serde_json::json!(7)).unwrap(), id);
+    client.switch_subaccount(id, Some(Scope::named("sub"))).await.unwrap();
+    let req = mock.assert_requested("public/exchange_token");
+    assert_eq!(req.params["subject_id"], serde_json::json!(7));
+    assert!(req.params["scope"].as_str().unwrap().contains("session:sub"));
+    // Every clone switches along
+    assert_eq!(other.access_token(), "token_for_7");
+    client.authed_request("private/get_positions", serde_json::json!({ "currency": "BTC" })).await.unwrap();
+    assert_eq!(mock.assert_requested("private/get_positions").access_token(), Some("token_for_7"));
   }
-}
\ No newline at end of file
+}
@@ -1,9 +1,12 @@
-use tokio::{net::TcpStream, sync::{mpsc, oneshot}};
-use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio_tungstenite::connect_async;
 use futures_util::{stream::SplitSink, SinkExt, StreamExt};
-use serde::{Serialize};
+use serde::{de::DeserializeOwned, Serialize};
 
-use crate::core::{Error, Notification, Response, ResponseHandler};
+use crate::core::{now_us, parse_json, ConnectionState, Connector, Error, Limits, ListenerId, NotificationSender, Pattern, RateLimitPolicy, RateLimiter, Reconnect, Response, ResponseHandler, Session, SharedAuth, Transport};
 
 pub const TESTNET: &str = "wss://test.deribit.com/ws/api/v2";
 pub const MAINNET: &str = "wss://www.deribit.com/ws/api/v2";
@@ -16,63 +19,380 @@ pub struct JsonRpcRequest<'a> {
   pub params: serde_json::Value,
 }
 
+/// Removes a request from the pending map when dropped, so that timed out or cancelled requests don't leak.
+struct Pending<'a> {
+  handler: &'a ResponseHandler,
+  id: u64,
+}
+
+impl Drop for Pending<'_> {
+  fn drop(&mut self) {
+    self.handler.cancel(self.id);
+  }
+}
+
+pub(crate) type Sink = SplitSink<Box<dyn Transport>, tungstenite::Message>;
 
+/// Number of outgoing messages queued for the writer task before `send` waits.
+const OUTBOX_CAPACITY: usize = 1024;
+
+/// WebSocket JSON-RPC client. Cheap to clone: clones share the connection, and requests from any number of tasks
+/// are pipelined over it by a single writer task.
+#[derive(Clone)]
 pub struct SocketClient {
-  pub write: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, tungstenite::Message>,
+  /// Messages to write, with the ID of the request they carry
+  outbox: mpsc::Sender<(u64, tungstenite::Message)>,
+  pub(crate) sinks: mpsc::UnboundedSender<Sink>,
   pub handler: ResponseHandler,
+  pub(crate) session: Arc<std::sync::Mutex<Session>>,
+  pub(crate) state: Arc<watch::Sender<ConnectionState>>,
 }
 
 impl SocketClient {
 
   /// Start a new public client session with the given WebSocket stream.
-  /// - `socket` - The WebSocket stream to use for communication.
-  pub fn start(socket: WebSocketStream<MaybeTlsStream<TcpStream>>) -> Self {
-
-    let (write, mut read) = socket.split();
-    let handler = ResponseHandler::new();
-    let handler_clone = handler.clone();
-
-    tokio::spawn(async move {
-      while let Some(Ok(tungstenite::Message::Text(msg))) = read.next().await {
-        handler_clone.handle(&msg);
-      }
-    });
-
-    Self { write, handler }
+  /// The client can't reconnect on its own, since it doesn't know where the stream came from; use `connect` for that.
+  /// - `socket` - The connection to use for communication, e.g. a `WebSocketStream` or a `deribit::mock::MockTransport`.
+  pub fn start(socket: impl Transport) -> Self {
+    Self::spawn(Box::new(socket), Session::default())
   }
-  
-  /// Start an aunthenticated client session.
+
+  /// Start an aunthenticated client session, reconnecting with the default backoff if the connection drops.
   /// - `url` - The WebSocket URL to connect to, e.g. `deribit::TESTNET` or `deribit::MAINNET`.
   pub async fn connect(url: &str) -> Result<Self, Error> {
-    let (socket, _) = connect_async(url).await
-    .map_err(|e| Error::WebSocket(e))?;
-    Ok(Self::start(socket))
+    Self::connect_with(url, Reconnect::default()).await
+  }
+
+  /// Start an aunthenticated client session with a custom reconnection policy.
+  /// - `url` - The WebSocket URL to connect to, e.g. `deribit::TESTNET` or `deribit::MAINNET`.
+  /// - `reconnect` - How to retry when the connection drops, e.g. `deribit::Reconnect::never()`.
+  pub async fn connect_with(url: &str, reconnect: Reconnect) -> Result<Self, Error> {
+    let url = url.to_string();
+    Self::connect_via(move || {
+      let url = url.clone();
+      async move { Ok(connect_async(url).await?.0) }
+    }, reconnect).await
   }
 
+  /// Start an aunthenticated client session over connections opened by `connect`, which is called again to reconnect
+  /// whenever the connection drops.
+  /// - `connect` - Opens a connection, e.g. through a proxy, or to a `deribit::mock::MockDeribit`.
+  /// - `reconnect` - How to retry when the connection drops, e.g. `deribit::Reconnect::default()`.
+  pub async fn connect_via<F, Fut, T>(connect: F, reconnect: Reconnect) -> Result<Self, Error>
+  where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<T, Error>> + Send + 'static,
+    T: Transport,
+  {
+    let socket = connect().await?;
+    let connector: Connector = Box::new(move || {
+      let connecting = connect();
+      Box::pin(async move { Ok(Box::new(connecting.await?) as Box<dyn Transport>) })
+    });
+    let session = Session { connector: Some(connector), reconnect, ..Session::default() };
+    Ok(Self::spawn(Box::new(socket), session))
+  }
+
+  fn spawn(socket: Box<dyn Transport>, session: Session) -> Self {
+    let (write, read) = socket.split();
+    let (outbox, outbox_rx) = mpsc::channel(OUTBOX_CAPACITY);
+    let (sinks, sinks_rx) = mpsc::unbounded_channel();
+    let _ = sinks.send(write);
+    let handler = ResponseHandler::new();
+    tokio::spawn(write_loop(outbox_rx, sinks_rx, handler.clone()));
+    let (state, _) = watch::channel(ConnectionState::Connected);
+    let client = Self {
+      outbox,
+      sinks,
+      handler,
+      session: Arc::new(std::sync::Mutex::new(session)),
+      state: Arc::new(state),
+    };
+    tokio::spawn(client.clone().run(read));
+    if let Some(deliveries) = client.handler.notification_task() {
+      tokio::spawn(deliveries);
+    }
+    client
+  }
 
   /// Send an unauthenticated request without waiting for the reply. For **public** methods only.
   /// - `method` - The API method to call, e.g. `"public/get_instruments"`
   /// - `params` - The parameters for the request, as a JSON object.
-  pub async fn send(&mut self, method: &str, params: serde_json::Value, id: u64) -> Result<(), Error> {
+  pub async fn send(&self, method: &str, params: serde_json::Value, id: u64) -> Result<(), Error> {
     let msg = serde_json::to_string(&JsonRpcRequest { jsonrpc: "2.0", id, method, params })?;
-    self.write.send(tungstenite::Message::Text(msg)).await?;
-    Ok(())
+    self.outbox.send((id, tungstenite::Message::Text(msg))).await
+      .map_err(|_| Error::Disconnected)
+  }
+
+  /// Send an unauthenticated request and wait for its reply, up to the client's default timeout. For **public** methods only.
+  /// Fails with `Error::Disconnected` if the connection drops before the reply arrives, or `Error::Timeout` if it takes too long.
+  /// Dropping the returned future cancels the request.
+  /// - `method` - The API method to call, e.g. `"public/get_instruments"`
+  /// - `params` - The parameters for the request, as a JSON object.
+  pub async fn request(&self, method: &str, params: serde_json::Value) -> Result<Response, Error> {
+    let timeout = self.session.lock().unwrap().timeout;
+    self.request_within(method, params, timeout).await
+  }
+
+  /// Send an unauthenticated request and parse its result into `T`. For **public** methods only.
+  /// - `method` - The API method to call, e.g. `"public/get_instruments"`
+  /// - `params` - The parameters for the request, as a JSON object.
+  pub async fn call<T: DeserializeOwned>(&self, method: &str, params: serde_json::Value) -> Result<T, Error> {
+    let resp = self.request(method, params).await?.value()?;
+    parse_json::<T>(resp)
   }
 
-  /// Send an unauthenticated request and wait for its reply. For **public** methods only.
+  /// Send an unauthenticated request and wait for its reply, overriding the client's default timeout. For **public** methods only.
   /// - `method` - The API method to call, e.g. `"public/get_instruments"`
   /// - `params` - The parameters for the request, as a JSON object.
-  pub async fn request(&mut self, method: &str, params: serde_json::Value) -> Result<Response, Error> {
+  /// - `timeout` - How long to wait for the reply before failing with `Error::Timeout`.
+  pub async fn request_timeout(&self, method: &str, params: serde_json::Value, timeout: Duration) -> Result<Response, Error> {
+    self.request_within(method, params, Some(timeout)).await
+  }
+
+  /// Requests are only sent while `Connected`: while reconnecting they fail with `Error::Disconnected`,
+  /// so nothing reaches a new connection before its session (auth, cancel-on-disconnect, subscriptions) is restored.
+  async fn request_within(&self, method: &str, params: serde_json::Value, timeout: Option<Duration>) -> Result<Response, Error> {
+    if *self.state.borrow() != ConnectionState::Connected {
+      return Err(Error::Disconnected);
+    }
+    self.dispatch(method, params, timeout).await
+  }
+
+  /// Send a request and wait for its reply, whatever the connection state. Used to restore the session after a reconnect.
+  pub(crate) async fn dispatch(&self, method: &str, params: serde_json::Value, timeout: Option<Duration>) -> Result<Response, Error> {
     let (tx, rx) = oneshot::channel();
     let id = self.handler.request(tx);
-    self.send(method, params, id).await?;
-    Ok(rx.await?)
+    let _pending = Pending { handler: &self.handler, id };
+    let reply = async {
+      self.acquire(method).await?;
+      let sent_us = now_us();
+      self.send(method, params, id).await?;
+      let mut resp = rx.await??;
+      if let Some(metrics) = resp.metrics.as_mut() {
+        metrics.sent_us = sent_us;
+      }
+      Ok(resp)
+    };
+    match timeout {
+      Some(timeout) => tokio::time::timeout(timeout, reply).await.map_err(|_| Error::Timeout)?,
+      None => reply.await,
+    }
+  }
+
+  /// Wait for (or, with `RateLimitPolicy::FailFast`, fail without) a rate limiter credit for `method`.
+  async fn acquire(&self, method: &str) -> Result<(), Error> {
+    loop {
+      let (wait, policy) = {
+        let mut session = self.session.lock().unwrap();
+        let Some(limiter) = session.rate_limiter.as_mut() else { return Ok(()) };
+        match limiter.try_acquire(method) {
+          Ok(()) => return Ok(()),
+          Err(wait) => (wait, limiter.policy),
+        }
+      };
+      match policy {
+        RateLimitPolicy::Queue => tokio::time::sleep(wait).await,
+        RateLimitPolicy::FailFast => return Err(Error::RateLimited(wait)),
+      }
+    }
+  }
+
+  /// Replace the client-side rate limiter applied by `request`. `None` disables rate limiting.
+  /// Clients start with `RateLimiter::default()`, i.e. Deribit's default limits, queueing requests over the limit.
+  /// - `limiter` - e.g. `Some(RateLimiter::new(limit, limit, RateLimitPolicy::FailFast)?)`
+  pub fn set_rate_limiter(&self, limiter: Option<RateLimiter>) {
+    self.session.lock().unwrap().rate_limiter = limiter;
   }
 
-  /// Register a listener for the specified channel. Actual subscription must be sent to the API separately.
-  /// - `channel` - The channel ID to listen to
-  /// - `sender` - notifications will be sent here
-  pub fn listen(&self, channel: String, sender: mpsc::Sender<Notification>) {
-    self.handler.subscribe(channel, sender);
+  /// Adopt the account's actual limits, e.g. from `get_account_summary`. Does nothing if rate limiting is disabled.
+  /// - `limits` - The `limits` field of an extended account summary.
+  pub fn tune_rate_limiter(&self, limits: &Limits) {
+    if let Some(limiter) = self.session.lock().unwrap().rate_limiter.as_mut() {
+      limiter.tune(limits);
+    }
   }
-}
\ No newline at end of file
+
+  /// Set the default timeout for `request`. `None` waits forever.
+  /// - `timeout` - How long to wait for a reply, e.g. `Some(deribit::DEFAULT_TIMEOUT)`.
+  pub fn set_timeout(&self, timeout: Option<Duration>) {
+    self.session.lock().unwrap().timeout = timeout;
+  }
+
+  /// Register a listener for the specified channel or pattern. Actual subscription must be sent to the API separately.
+  /// Exact channels are re-subscribed after a reconnect.
+  /// - `channel` - The channel ID to listen to, e.g. `"ticker.BTC-PERPETUAL.100ms"`, or a `Pattern` such as `"user.orders.*.raw"`
+  /// - `sender` - notifications will be sent here, e.g. from `deribit::core::notification_channel`
+  pub fn listen(&self, channel: impl Into<Pattern>, sender: NotificationSender) -> ListenerId {
+    self.handler.subscribe(channel, sender)
+  }
+
+  /// Ask the server to send a heartbeat every `interval`. Test requests are answered automatically,
+  /// and the connection is treated as dead (and reconnected) if no message arrives for two intervals.
+  /// - `interval` - Time between heartbeats; Deribit requires at least 10 seconds.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-set_heartbeat)
+  pub async fn enable_heartbeat(&self, interval: Duration) -> Result<(), Error> {
+    let params = serde_json::json!({ "interval": interval.as_secs() });
+    self.request("public/set_heartbeat", params).await?.value()?;
+    self.session.lock().unwrap().heartbeat = Some(interval);
+    Ok(())
+  }
+
+  /// Stop the server heartbeats and the dead-connection detection built on them.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-disable_heartbeat)
+  pub async fn disable_heartbeat(&self) -> Result<(), Error> {
+    self.request("public/disable_heartbeat", serde_json::json!({})).await?.value()?;
+    self.session.lock().unwrap().heartbeat = None;
+    Ok(())
+  }
+
+  /// The current heartbeat interval, if heartbeats are enabled.
+  pub fn heartbeat(&self) -> Option<Duration> {
+    self.session.lock().unwrap().heartbeat
+  }
+
+  /// Watch the state of the underlying connection, e.g. to pause trading while reconnecting.
+  pub fn state(&self) -> watch::Receiver<ConnectionState> {
+    self.state.subscribe()
+  }
+
+  /// Remember the credentials the connection is authenticated with, so they can be restored (and renewed) after a reconnect.
+  pub(crate) fn remember_auth(&self, auth: &SharedAuth) {
+    self.session.lock().unwrap().auth = Some(auth.clone());
+  }
+}
+
+/// Writes queued messages to the current connection. A new sink is sent by the read loop after every reconnect.
+/// A request that can't be written fails with the write error.
+async fn write_loop(mut outbox: mpsc::Receiver<(u64, tungstenite::Message)>, mut sinks: mpsc::UnboundedReceiver<Sink>, handler: ResponseHandler) {
+  let Some(mut sink) = sinks.recv().await else {
+    return;
+  };
+  loop {
+    tokio::select! {
+      biased;
+      next = sinks.recv() => match next {
+        Some(next) => sink = next,
+        None => return,
+      },
+      msg = outbox.recv() => match msg {
+        Some((id, msg)) => {
+          if let Err(err) = sink.send(msg).await {
+            handler.fail(id, err.into());
+          }
+        }
+        None => return,
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::MockDeribit;
+
+  #[tokio::test]
+  async fn requests_time_out_and_are_forgotten() {
+    let mock = MockDeribit::new();
+    mock.ignore("public/get_time");
+    let client = mock.client();
+    let err = client.request_timeout("public/get_time", serde_json::json!({}), Duration::from_millis(20)).await.unwrap_err();
+    assert!(matches!(err, Error::Timeout), "{:?}", err);
+    assert!(client.handler.requests.lock().unwrap().is_empty());
+
+    // The client's default timeout applies to `request`
+    client.set_timeout(Some(Duration::from_millis(20)));
+    let err = client.request("public/get_time", serde_json::json!({})).await.unwrap_err();
+    assert!(matches!(err, Error::Timeout), "{:?}", err);
+    assert!(client.handler.requests.lock().unwrap().is_empty());
+    assert_eq!(mock.requests_to("public/get_time").len(), 2);
+
+    // Other requests are unaffected
+    client.request("public/test", serde_json::json!({})).await.unwrap().value().unwrap();
+  }
+
+  #[tokio::test]
+  async fn cancelled_requests_are_forgotten() {
+    let mock = MockDeribit::new();
+    mock.ignore("public/get_time");
+    let client = mock.client();
+    client.set_timeout(None);
+    let request = tokio::spawn({
+      let client = client.clone();
+      async move { client.request("public/get_time", serde_json::json!({})).await }
+    });
+    mock.wait_for("public/get_time", 1, Duration::from_secs(1)).await.expect("the request was not sent");
+    assert_eq!(client.handler.requests.lock().unwrap().len(), 1);
+
+    request.abort();
+    assert!(request.await.unwrap_err().is_cancelled());
+    assert!(client.handler.requests.lock().unwrap().is_empty());
+  }
+
+  #[tokio::test]
+  async fn pending_requests_fail_when_the_connection_drops() {
+    let mock = MockDeribit::new();
+    mock.ignore("public/get_time");
+    let client = mock.client();
+    let request = tokio::spawn({
+      let client = client.clone();
+      async move { client.request("public/get_time", serde_json::json!({})).await }
+    });
+    mock.wait_for("public/get_time", 1, Duration::from_secs(1)).await.expect("the request was not sent");
+    mock.disconnect();
+    let err = request.await.unwrap().unwrap_err();
+    assert!(matches!(err, Error::Disconnected), "{:?}", err);
+    assert!(client.handler.requests.lock().unwrap().is_empty());
+  }
+
+  #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+  async fn clones_pipeline_requests_from_many_tasks() {
+    let mock = MockDeribit::new();
+    mock.on("public/get_index_price", |req| Ok(req.params["index_name"].clone()));
+    // A request still waiting for its reply doesn't hold up the others
+    mock.ignore("public/get_time");
+    let client = mock.client();
+    client.set_timeout(None);
+    let stalled = tokio::spawn({
+      let client = client.clone();
+      async move { client.request("public/get_time", serde_json::json!({})).await }
+    });
+
+    let tasks: Vec<_> = (0..200).map(|n| {
+      let client = client.clone();
+      tokio::spawn(async move {
+        let resp = client.request("public/get_index_price", serde_json::json!({ "index_name": n })).await?;
+        Ok::<_, Error>((n, resp.value()?))
+      })
+    }).collect();
+    for task in tasks {
+      let (n, reply) = task.await.unwrap().unwrap();
+      assert_eq!(reply, n);
+    }
+
+    let mut ids: Vec<u64> = mock.requests().iter().map(|req| req.id).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), 201);
+    assert!(!stalled.is_finished());
+    stalled.abort();
+  }
+
+  #[tokio::test]
+  async fn replies_carry_request_metrics() {
+    let mock = MockDeribit::new();
+    mock.server_times(1550147385946088, 1550147385946179);
+    let client = mock.client();
+    let before = now_us();
+    let resp = client.request("public/get_time", serde_json::json!({})).await.unwrap();
+    let after = now_us();
+    let metrics = resp.metrics.unwrap();
+    assert!(before <= metrics.sent_us && metrics.sent_us <= metrics.received_us && metrics.received_us <= after, "{:?}", metrics);
+    assert_eq!(metrics.server_us(), Some(91));
+    assert_eq!(metrics.network_us(), Some(metrics.round_trip_us() - 91));
+    assert_eq!(resp.us_diff, Some(91));
+  }
+}
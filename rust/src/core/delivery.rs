@@ -0,0 +1,347 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use tokio::sync::Notify;
+
+use crate::core::Notification;
+
+/// What to do with a notification when its listener's queue is full.
+#[derive(Debug, Clone, Copy)]
+pub enum Backpressure {
+  /// Hold back notifications until the listener catches up. Nothing is ever dropped; replies and heartbeats keep
+  /// flowing, but a slow listener delays the notifications of every other listener, which queue up in memory.
+  Block,
+  /// Drop the oldest queued notification to make room.
+  DropOldest,
+  /// Drop the incoming notification.
+  DropNewest,
+  /// Keep only the latest notification per channel, replacing the queued one in place. Suits snapshots such as
+  /// tickers and grouped books, but not raw book diffs, which must all be applied.
+  Conflate,
+  /// Like `Conflate`, keyed by the given function instead of the channel.
+  ConflateBy(fn(&Notification) -> String),
+}
+
+impl Backpressure {
+  /// Policy suited to a channel: private `user.*` and `block_rfq.*` channels never drop; snapshot channels are
+  /// conflated; deltas and events such as `incremental_ticker` and `platform_state` are never conflated, and drop
+  /// the newest notifications when full.
+  pub fn for_channel(channel: &str) -> Self {
+    let name = channel.split('.').next().unwrap_or(channel);
+    let parts = channel.split('.').count();
+    match name {
+      "user" | "block_rfq" => Backpressure::Block,
+      "ticker" | "deribit_price_index" | "perpetual" | "markprice" | "estimated_expiration_price" => Backpressure::Conflate,
+      // book.{instrument}.{group}.{depth}.{interval} sends full snapshots, book.{instrument}.{interval} sends diffs
+      "book" if parts == 5 => Backpressure::Conflate,
+      _ => Backpressure::DropNewest,
+    }
+  }
+
+  /// The strictest policy suited to every channel of a subscription: conflated only if every channel is a snapshot.
+  pub fn for_channels(channels: &[&str]) -> Self {
+    let policies: Vec<Backpressure> = channels.iter().map(|c| Backpressure::for_channel(c)).collect();
+    if policies.iter().any(|p| matches!(p, Backpressure::Block)) {
+      Backpressure::Block
+    } else if !policies.is_empty() && policies.iter().all(|p| matches!(p, Backpressure::Conflate)) {
+      Backpressure::Conflate
+    } else {
+      Backpressure::DropNewest
+    }
+  }
+
+  fn key(&self, notif: &Notification) -> Option<String> {
+    match self {
+      Backpressure::Conflate => Some(notif.params.channel.clone()),
+      Backpressure::ConflateBy(key) => Some(key(notif)),
+      _ => None,
+    }
+  }
+}
+
+/// Outcome of handing a notification to a listener.
+#[derive(Debug)]
+pub enum Delivery {
+  Queued,
+  /// A notification was dropped: the incoming one, or the oldest queued one.
+  Dropped,
+  /// The queue is full and the policy is `Block`; wait with `NotificationSender::deliver`.
+  Full(Box<Notification>),
+  /// The receiver is gone.
+  Closed,
+}
+
+#[derive(Debug)]
+struct State {
+  queue: VecDeque<(Option<String>, Notification)>,
+  receiver: Option<Waker>,
+  receiver_closed: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+  state: Mutex<State>,
+  capacity: usize,
+  policy: Backpressure,
+  senders: AtomicUsize,
+  dropped: AtomicU64,
+  space: Notify,
+}
+
+/// Queue of notifications for one listener, with the given capacity and backpressure policy.
+pub fn notification_channel(capacity: usize, policy: Backpressure) -> (NotificationSender, NotificationReceiver) {
+  let shared = Arc::new(Shared {
+    state: Mutex::new(State { queue: VecDeque::new(), receiver: None, receiver_closed: false }),
+    capacity: capacity.max(1),
+    policy,
+    senders: AtomicUsize::new(1),
+    dropped: AtomicU64::new(0),
+    space: Notify::new(),
+  });
+  (NotificationSender { shared: shared.clone() }, NotificationReceiver { shared })
+}
+
+/// Sending half of a `notification_channel`, registered with the client as a listener.
+#[derive(Debug)]
+pub struct NotificationSender {
+  shared: Arc<Shared>,
+}
+
+impl Clone for NotificationSender {
+  fn clone(&self) -> Self {
+    self.shared.senders.fetch_add(1, Ordering::Relaxed);
+    NotificationSender { shared: self.shared.clone() }
+  }
+}
+
+impl Drop for NotificationSender {
+  fn drop(&mut self) {
+    if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+      // Last sender gone: wake the receiver so it sees the end of the stream
+      if let Some(waker) = self.shared.state.lock().unwrap().receiver.take() {
+        waker.wake();
+      }
+    }
+  }
+}
+
+impl NotificationSender {
+  pub fn policy(&self) -> Backpressure {
+    self.shared.policy
+  }
+
+  /// Queue a notification without waiting, applying the backpressure policy if the queue is full.
+  pub fn try_deliver(&self, notif: Notification) -> Delivery {
+    let shared = &self.shared;
+    let mut state = shared.state.lock().unwrap();
+    if state.receiver_closed {
+      return Delivery::Closed;
+    }
+    let key = shared.policy.key(&notif);
+    if let Some(ref key) = key {
+      if let Some(queued) = state.queue.iter_mut().find(|(k, _)| k.as_ref() == Some(key)) {
+        queued.1 = notif;
+        shared.dropped.fetch_add(1, Ordering::Relaxed);
+        return Delivery::Dropped;
+      }
+    }
+    let mut outcome = Delivery::Queued;
+    if state.queue.len() >= shared.capacity {
+      match shared.policy {
+        Backpressure::Block => return Delivery::Full(Box::new(notif)),
+        Backpressure::DropNewest => {
+          shared.dropped.fetch_add(1, Ordering::Relaxed);
+          return Delivery::Dropped;
+        }
+        Backpressure::DropOldest | Backpressure::Conflate | Backpressure::ConflateBy(_) => {
+          state.queue.pop_front();
+          shared.dropped.fetch_add(1, Ordering::Relaxed);
+          outcome = Delivery::Dropped;
+        }
+      }
+    }
+    state.queue.push_back((key, notif));
+    if let Some(waker) = state.receiver.take() {
+      waker.wake();
+    }
+    outcome
+  }
+
+  /// Queue a notification, waiting for room if the policy is `Block`. Never returns `Delivery::Full`.
+  pub async fn deliver(&self, notif: Notification) -> Delivery {
+    let mut notif = notif;
+    loop {
+      let space = self.shared.space.notified();
+      match self.try_deliver(notif) {
+        Delivery::Full(back) => {
+          notif = *back;
+          space.await;
+        }
+        outcome => return outcome,
+      }
+    }
+  }
+
+  pub fn is_closed(&self) -> bool {
+    self.shared.state.lock().unwrap().receiver_closed
+  }
+}
+
+/// Receiving half of a `notification_channel`.
+#[derive(Debug)]
+pub struct NotificationReceiver {
+  shared: Arc<Shared>,
+}
+
+impl NotificationReceiver {
+  /// Take the next notification, or `None` once every sender is gone and the queue is drained.
+  pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Notification>> {
+    let mut state = self.shared.state.lock().unwrap();
+    if let Some((_, notif)) = state.queue.pop_front() {
+      drop(state);
+      self.shared.space.notify_one();
+      return Poll::Ready(Some(notif));
+    }
+    if self.shared.senders.load(Ordering::Acquire) == 0 {
+      return Poll::Ready(None);
+    }
+    state.receiver = Some(cx.waker().clone());
+    Poll::Pending
+  }
+
+  pub async fn recv(&mut self) -> Option<Notification> {
+    std::future::poll_fn(|cx| self.poll_recv(cx)).await
+  }
+
+  /// Number of notifications dropped or replaced by the backpressure policy so far.
+  pub fn dropped(&self) -> u64 {
+    self.shared.dropped.load(Ordering::Relaxed)
+  }
+
+  pub fn policy(&self) -> Backpressure {
+    self.shared.policy
+  }
+}
+
+impl Drop for NotificationReceiver {
+  fn drop(&mut self) {
+    let mut state = self.shared.state.lock().unwrap();
+    state.receiver_closed = true;
+    state.queue.clear();
+    drop(state);
+    // Unblock a sender waiting for room
+    self.shared.space.notify_one();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use futures_util::FutureExt;
+
+  use super::*;
+  use crate::core::response::NotificationParams;
+
+  fn notification(channel: &str, value: i64) -> Notification {
+    Notification {
+      jsonrpc: "2.0".to_string(),
+      params: NotificationParams { channel: channel.to_string(), data: serde_json::json!({ "value": value }) },
+    }
+  }
+
+  /// The `(channel, value)` of every queued notification.
+  fn drain(receiver: &mut NotificationReceiver) -> Vec<(String, i64)> {
+    let mut received = Vec::new();
+    while let Some(Some(notif)) = receiver.recv().now_or_never() {
+      received.push((notif.params.channel, notif.params.data["value"].as_i64().unwrap()));
+    }
+    received
+  }
+
+  #[tokio::test]
+  async fn block_waits_for_room() {
+    let (sender, mut receiver) = notification_channel(1, Backpressure::Block);
+    assert!(matches!(sender.try_deliver(notification("user.orders.any.any.raw", 1)), Delivery::Queued));
+    assert!(matches!(sender.try_deliver(notification("user.orders.any.any.raw", 2)), Delivery::Full(_)));
+
+    let blocked = tokio::spawn(async move { sender.deliver(notification("user.orders.any.any.raw", 2)).await });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!blocked.is_finished());
+    assert_eq!(receiver.recv().await.unwrap().params.data["value"], 1);
+    assert!(matches!(blocked.await.unwrap(), Delivery::Queued));
+    assert_eq!(drain(&mut receiver), [("user.orders.any.any.raw".to_string(), 2)]);
+    assert_eq!(receiver.dropped(), 0);
+  }
+
+  #[test]
+  fn drop_policies_count_what_they_drop() {
+    let (sender, mut oldest) = notification_channel(2, Backpressure::DropOldest);
+    for value in 1..=4 {
+      sender.try_deliver(notification("trades.BTC-PERPETUAL.raw", value));
+    }
+    assert_eq!(drain(&mut oldest).iter().map(|(_, value)| *value).collect::<Vec<_>>(), [3, 4]);
+    assert_eq!(oldest.dropped(), 2);
+
+    let (sender, mut newest) = notification_channel(2, Backpressure::DropNewest);
+    for value in 1..=4 {
+      sender.try_deliver(notification("trades.BTC-PERPETUAL.raw", value));
+    }
+    assert_eq!(drain(&mut newest).iter().map(|(_, value)| *value).collect::<Vec<_>>(), [1, 2]);
+    assert_eq!(newest.dropped(), 2);
+  }
+
+  #[test]
+  fn conflate_keeps_the_latest_per_channel() {
+    let (sender, mut receiver) = notification_channel(8, Backpressure::Conflate);
+    sender.try_deliver(notification("ticker.BTC-PERPETUAL.100ms", 1));
+    sender.try_deliver(notification("ticker.ETH-PERPETUAL.100ms", 2));
+    assert!(matches!(sender.try_deliver(notification("ticker.BTC-PERPETUAL.100ms", 3)), Delivery::Dropped));
+    assert_eq!(drain(&mut receiver), [("ticker.BTC-PERPETUAL.100ms".to_string(), 3), ("ticker.ETH-PERPETUAL.100ms".to_string(), 2)]);
+    assert_eq!(receiver.dropped(), 1);
+  }
+
+  #[test]
+  fn conflate_by_keeps_the_latest_per_key() {
+    fn parity(notif: &Notification) -> String {
+      (notif.params.data["value"].as_i64().unwrap() % 2).to_string()
+    }
+    let (sender, mut receiver) = notification_channel(8, Backpressure::ConflateBy(parity));
+    for value in 1..=5 {
+      sender.try_deliver(notification("markprice.options.btc_usd", value));
+    }
+    assert_eq!(drain(&mut receiver).iter().map(|(_, value)| *value).collect::<Vec<_>>(), [5, 4]);
+    assert_eq!(receiver.dropped(), 3);
+  }
+
+  #[tokio::test]
+  async fn a_blocked_listener_does_not_stall_replies() {
+    let mock = crate::mock::MockDeribit::new();
+    let mut client = mock.private_client().await.unwrap();
+    let mut orders = client.subscribe::<serde_json::Value>(&["user.orders.any.any.raw"]).await.unwrap();
+    for value in 0..crate::subscriptions::DEFAULT_BUFFER as i64 + 8 {
+      mock.notify("user.orders.any.any.raw", serde_json::json!({ "value": value }));
+    }
+    mock.respond("public/test", serde_json::json!({ "version": "1.2.26" }));
+    tokio::time::timeout(Duration::from_secs(1), client.client.request("public/test", serde_json::json!({}))).await
+      .expect("the reply waited for the listener")
+      .unwrap();
+
+    for value in 0..crate::subscriptions::DEFAULT_BUFFER as i64 + 8 {
+      assert_eq!(orders.recv().await.unwrap().unwrap().data["value"], value);
+    }
+  }
+
+  #[test]
+  fn order_and_fill_channels_never_drop() {
+    for channel in ["user.orders.BTC-PERPETUAL.raw", "user.trades.any.any.100ms", "block_rfq.maker.BTC"] {
+      assert!(matches!(Backpressure::for_channel(channel), Backpressure::Block), "{}", channel);
+    }
+    for channel in ["incremental_ticker.BTC-PERPETUAL", "platform_state", "book.BTC-PERPETUAL.raw"] {
+      assert!(matches!(Backpressure::for_channel(channel), Backpressure::DropNewest), "{}", channel);
+    }
+    assert!(matches!(Backpressure::for_channels(&["ticker.BTC-PERPETUAL.100ms", "user.orders.any.any.raw"]), Backpressure::Block));
+    assert!(matches!(Backpressure::for_channels(&["ticker.BTC-PERPETUAL.100ms", "deribit_price_index.btc_usd"]), Backpressure::Conflate));
+  }
+}
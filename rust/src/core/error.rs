@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::core::DeribitErrorCode;
+
 /// Represents an error returned in a JSON-RPC response from Deribit
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiError {
@@ -10,25 +12,46 @@ pub struct ApiError {
   pub data: Option<Value>,
 }
 
+impl ApiError {
+  pub fn kind(&self) -> DeribitErrorCode {
+    DeribitErrorCode::from_code(self.code)
+  }
+}
+
 #[derive(Debug)]
 pub enum Error {
   Api(ApiError),
   Json(serde_json::Error),
-  WebSocket(tungstenite::Error),
+  WebSocket(Box<tungstenite::Error>),
+  /// The HTTP request failed, or the server answered with an error status and no JSON-RPC error.
+  Http(Box<reqwest::Error>),
   Channel(tokio::sync::oneshot::error::RecvError),
   Io(std::io::Error),
   Logic(&'static str),
+  /// The connection was lost before the reply arrived.
+  Disconnected,
+  /// No reply arrived within the request timeout.
+  Timeout,
+  /// The session lacks the given scope, e.g. `"wallet:read_write"`. Checked before sending the request.
+  Scope(&'static str),
+  /// The client-side rate limiter has no credits left and its policy is `RateLimitPolicy::FailFast`. Holds the time until the next credit.
+  RateLimited(std::time::Duration),
 }
 
 impl std::fmt::Display for Error {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
-      Error::Api(err) => write!(f, "API error: {}", err.message),
+      Error::Api(err) => write!(f, "API error {}: {}", err.kind(), err.message),
       Error::Json(err) => write!(f, "JSON error: {}", err),
       Error::WebSocket(err) => write!(f, "WebSocket error: {}", err),
+      Error::Http(err) => write!(f, "HTTP error: {}", err),
       Error::Channel(err) => write!(f, "Channel error: {}", err),
       Error::Io(err) => write!(f, "I/O error: {}", err),
       Error::Logic(msg) => write!(f, "Logic error: {}", msg),
+      Error::Disconnected => write!(f, "Disconnected"),
+      Error::Timeout => write!(f, "Request timed out"),
+      Error::Scope(scope) => write!(f, "Missing scope: {}", scope),
+      Error::RateLimited(wait) => write!(f, "Rate limited, next credit in {:?}", wait),
     }
   }
 }
@@ -38,14 +61,75 @@ impl std::error::Error for Error {
     match self {
       Error::Api(_) => None,
       Error::Json(err) => Some(err),
-      Error::WebSocket(err) => Some(err),
+      Error::WebSocket(err) => Some(err.as_ref()),
+      Error::Http(err) => Some(err.as_ref()),
       Error::Channel(err) => Some(err),
       Error::Io(err) => Some(err),
       Error::Logic(_) => None,
+      Error::Disconnected => None,
+      Error::Timeout => None,
+      Error::Scope(_) => None,
+      Error::RateLimited(_) => None,
     }
   }
 }
 
+impl Error {
+  /// The Deribit error code, for errors returned by the API.
+  pub fn api_code(&self) -> Option<DeribitErrorCode> {
+    match self {
+      Error::Api(err) => Some(err.kind()),
+      _ => None,
+    }
+  }
+
+  /// Whether sending the same request again later may succeed: transient API errors, rate limits, timeouts and
+  /// dropped connections. A request that timed out may still have been executed; use `is_retryable_for` before
+  /// resending order entry.
+  pub fn is_retryable(&self) -> bool {
+    match self {
+      Error::Api(err) => err.kind().is_retryable(),
+      Error::Disconnected | Error::Timeout | Error::WebSocket(_) | Error::Io(_) | Error::RateLimited(_) => true,
+      Error::Http(err) => err.is_connect() || err.status().is_some_and(|status| status.is_server_error()),
+      _ => false,
+    }
+  }
+
+  /// Whether the `method` request with `params` that failed with this error can safely be sent again. Like
+  /// `is_retryable`, except that order entry that may have reached the server before the timeout or disconnect is only
+  /// retryable with a `label`, so the caller can check whether the order exists before sending it again.
+  pub fn is_retryable_for(&self, method: &str, params: &serde_json::Value) -> bool {
+    let order_entry = matches!(method, "private/buy" | "private/sell" | "private/edit" | "private/edit_by_label");
+    if order_entry && self.may_have_reached_server() && params.get("label").is_none() {
+      return false;
+    }
+    self.is_retryable()
+  }
+
+  /// Whether the request may have been executed even though no reply arrived.
+  fn may_have_reached_server(&self) -> bool {
+    match self {
+      Error::Disconnected | Error::Timeout | Error::WebSocket(_) | Error::Io(_) => true,
+      Error::Http(err) => !err.is_connect(),
+      _ => false,
+    }
+  }
+
+  /// Whether the request failed for lack of valid credentials or permissions, i.e. re-authenticating may help.
+  pub fn is_auth_error(&self) -> bool {
+    match self {
+      Error::Api(err) => err.kind().is_auth_error(),
+      Error::Scope(_) => true,
+      _ => false,
+    }
+  }
+
+  /// Whether the request was rejected by Deribit's rate limits, or held back by the client-side rate limiter.
+  pub fn is_rate_limit(&self) -> bool {
+    matches!(self, Error::RateLimited(_)) || self.api_code().is_some_and(|code| code.is_rate_limit())
+  }
+}
+
 impl From<ApiError> for Error {
   fn from(err: ApiError) -> Self {
     Error::Api(err)
@@ -60,7 +144,14 @@ impl From<serde_json::Error> for Error {
 
 impl From<tungstenite::Error> for Error {
   fn from(err: tungstenite::Error) -> Self {
-    Error::WebSocket(err)
+    Error::WebSocket(Box::new(err))
+  }
+}
+
+/// Timeouts map to `Error::Timeout`, like on the WebSocket client.
+impl From<reqwest::Error> for Error {
+  fn from(err: reqwest::Error) -> Self {
+    if err.is_timeout() { Error::Timeout } else { Error::Http(Box::new(err)) }
   }
 }
 
@@ -74,4 +165,45 @@ impl From<std::io::Error> for Error {
   fn from(err: std::io::Error) -> Self {
     Error::Io(err)
   }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn unlabelled_order_entry_is_not_retried_after_a_timeout() {
+    let unlabelled = serde_json::json!({ "instrument_name": "BTC-PERPETUAL", "amount": 10 });
+    let labelled = serde_json::json!({ "instrument_name": "BTC-PERPETUAL", "amount": 10, "label": "entry-1" });
+    for err in [Error::Timeout, Error::Disconnected] {
+      assert!(!err.is_retryable_for("private/buy", &unlabelled));
+      assert!(!err.is_retryable_for("private/edit", &unlabelled));
+      assert!(err.is_retryable_for("private/sell", &labelled));
+      assert!(err.is_retryable_for("public/get_order_book", &unlabelled));
+    }
+    // Never sent, so safe to send again
+    assert!(Error::RateLimited(std::time::Duration::from_secs(1)).is_retryable_for("private/buy", &unlabelled));
+    let busy = ApiError { code: DeribitErrorCode::TooManyRequests.code(), message: "too_many_requests".to_string(), data: None };
+    assert!(Error::Api(busy).is_retryable_for("private/buy", &unlabelled));
+  }
+
+  #[test]
+  fn transient_errors_are_retryable() {
+    assert!(Error::Timeout.is_retryable());
+    assert!(Error::RateLimited(std::time::Duration::from_secs(1)).is_retryable());
+    let busy = ApiError { code: 10028, message: "too_many_requests".to_string(), data: None };
+    assert!(Error::Api(busy).is_retryable());
+    let no_funds = ApiError { code: 10009, message: "not_enough_funds".to_string(), data: None };
+    assert!(!Error::Api(no_funds).is_retryable());
+    assert!(!Error::Logic("invalid").is_retryable());
+  }
+
+  #[test]
+  fn auth_codes_follow_the_deribit_table() {
+    assert_eq!(DeribitErrorCode::from_code(13004), DeribitErrorCode::Unauthorized);
+    assert_eq!(DeribitErrorCode::Unauthorized.as_str(), "invalid_credentials");
+    assert_eq!(DeribitErrorCode::from_code(13009), DeribitErrorCode::InvalidToken);
+    assert_eq!(DeribitErrorCode::InvalidToken.as_str(), "unauthorized");
+    let expired = ApiError { code: 13009, message: "unauthorized".to_string(), data: None };
+    assert!(Error::Api(expired).is_auth_error());
+  }
+}
@@ -0,0 +1,344 @@
+/// Error codes documented by Deribit, as found in `ApiError::code`.
+///
+/// Source: [Deribit docs](https://docs.deribit.com/#rpc-error-codes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeribitErrorCode {
+  AuthorizationRequired,
+  Error,
+  QtyTooLow,
+  OrderOverlap,
+  OrderNotFound,
+  PriceTooLow,
+  PriceTooLow4Idx,
+  PriceTooHigh,
+  PriceTooHigh4Idx,
+  NotEnoughFunds,
+  AlreadyClosed,
+  PriceNotAllowed,
+  BookClosed,
+  PmeMaxTotalOpenOrders,
+  PmeMaxFutureOpenOrders,
+  PmeMaxOptionOpenOrders,
+  PmeMaxFutureOpenOrdersSize,
+  PmeMaxOptionOpenOrdersSize,
+  NonPmeMaxFuturePositionSize,
+  LockedByAdmin,
+  InvalidOrUnsupportedInstrument,
+  InvalidAmount,
+  InvalidQuantity,
+  InvalidPrice,
+  InvalidMaxShow,
+  InvalidOrderId,
+  PricePrecisionExceeded,
+  NonIntegerContractAmount,
+  TooManyRequests,
+  NotOwnerOfOrder,
+  MustBeWebsocketRequest,
+  InvalidArgsForInstrument,
+  WholeCostTooLow,
+  NotImplemented,
+  TriggerPriceTooHigh,
+  TriggerPriceTooLow,
+  InvalidMaxShowAmount,
+  NonPmeTotalShortOptionsPositionsSize,
+  PmeMaxRiskReducingOrders,
+  /// The request can't be processed right now and should be retried
+  Retry,
+  SettlementInProgress,
+  PriceWrongTick,
+  TriggerPriceWrongTick,
+  CanNotCancelLiquidationOrder,
+  CanNotEditLiquidationOrder,
+  MatchingEngineQueueFull,
+  NotOnThisServer,
+  CancelOnDisconnectFailed,
+  TooManyConcurrentRequests,
+  DisabledWhilePositionLock,
+  AlreadyFilled,
+  MaxSpotOpenOrders,
+  PostOnlyPriceModificationNotPossible,
+  MaxSpotOrderQuantity,
+  InvalidArguments,
+  OtherReject,
+  OtherError,
+  NoMoreTriggers,
+  InvalidTriggerPrice,
+  OutdatedInstrumentForIvOrder,
+  NoAdvForFutures,
+  NoAdvPostonly,
+  NotAdvOrder,
+  PermissionDenied,
+  BadArgument,
+  NotOpenOrder,
+  InvalidEvent,
+  OutdatedInstrument,
+  UnsupportedArgCombination,
+  WrongMaxShowForOption,
+  BadArguments,
+  BadRequest,
+  SystemMaintenance,
+  SubscribeErrorUnsubscribed,
+  TransferNotFound,
+  /// A post-only order would have taken liquidity and `reject_post_only` was set
+  PostOnlyReject,
+  UnauthenticatedPublicRequestsTemporarilyDisabled,
+  InvalidAddr,
+  InvalidTransferAddress,
+  AddressAlreadyExist,
+  MaxAddrCountExceeded,
+  InternalServerError,
+  DisabledDepositAddressCreation,
+  AddressBelongsToUser,
+  NoDepositAddress,
+  BadTfa,
+  TooManySubaccounts,
+  WrongSubaccountName,
+  LoginOverLimit,
+  RegistrationOverLimit,
+  CountryIsBanned,
+  TransferNotAllowed,
+  TfaOverLimit,
+  TfaUsed,
+  InvalidLogin,
+  AccountNotActivated,
+  AccountBlocked,
+  TfaRequired,
+  /// Wrong client credentials; `invalid_credentials` on the wire
+  Unauthorized,
+  PwdMatchError,
+  SecurityError,
+  UserNotFound,
+  RequestFailed,
+  /// Wrong or expired access token, or a token used outside its scope; `unauthorized` on the wire
+  InvalidToken,
+  ValueRequired,
+  ValueTooShort,
+  UnavailableInSubaccount,
+  InvalidPhoneNumber,
+  CannotSendSms,
+  InvalidSmsCode,
+  InvalidInput,
+  InvalidContentType,
+  OrderbookClosed,
+  NotFound,
+  Forbidden,
+  MethodSwitchedOffByAdmin,
+  TemporarilyUnavailable,
+  /// Market maker protection was triggered and quoting is frozen
+  MmpTrigger,
+  VerificationRequired,
+  NonUniqueOrderLabel,
+  NoMoreSecurityKeysAllowed,
+  ActiveComboLimitReached,
+  UnavailableForComboBooks,
+  IncompleteKycData,
+  MmpRequired,
+  CodNotEnabled,
+  /// Quotes are frozen after a market maker protection trigger
+  QuotesFrozen,
+  ScopeExceeded,
+  Unavailable,
+  RequestCancelledByUser,
+  Replaced,
+  RawSubscriptionsNotAvailableForUnauthorized,
+  CouponAlreadyUsed,
+  KycTransferAlreadyInitiated,
+  ParseError,
+  InvalidRequest,
+  MethodNotFound,
+  InvalidParams,
+  MissingParams,
+  /// A code not covered here
+  Other(i64),
+}
+
+use DeribitErrorCode::*;
+
+const CODES: &[(DeribitErrorCode, i64, &str)] = &[
+  (AuthorizationRequired, 10000, "authorization_required"),
+  (Error, 10001, "error"),
+  (QtyTooLow, 10002, "qty_too_low"),
+  (OrderOverlap, 10003, "order_overlap"),
+  (OrderNotFound, 10004, "order_not_found"),
+  (PriceTooLow, 10005, "price_too_low"),
+  (PriceTooLow4Idx, 10006, "price_too_low4idx"),
+  (PriceTooHigh, 10007, "price_too_high"),
+  (PriceTooHigh4Idx, 10008, "price_too_high4idx"),
+  (NotEnoughFunds, 10009, "not_enough_funds"),
+  (AlreadyClosed, 10010, "already_closed"),
+  (PriceNotAllowed, 10011, "price_not_allowed"),
+  (BookClosed, 10012, "book_closed"),
+  (PmeMaxTotalOpenOrders, 10013, "pme_max_total_open_orders"),
+  (PmeMaxFutureOpenOrders, 10014, "pme_max_future_open_orders"),
+  (PmeMaxOptionOpenOrders, 10015, "pme_max_option_open_orders"),
+  (PmeMaxFutureOpenOrdersSize, 10016, "pme_max_future_open_orders_size"),
+  (PmeMaxOptionOpenOrdersSize, 10017, "pme_max_option_open_orders_size"),
+  (NonPmeMaxFuturePositionSize, 10018, "non_pme_max_future_position_size"),
+  (LockedByAdmin, 10019, "locked_by_admin"),
+  (InvalidOrUnsupportedInstrument, 10020, "invalid_or_unsupported_instrument"),
+  (InvalidAmount, 10021, "invalid_amount"),
+  (InvalidQuantity, 10022, "invalid_quantity"),
+  (InvalidPrice, 10023, "invalid_price"),
+  (InvalidMaxShow, 10024, "invalid_max_show"),
+  (InvalidOrderId, 10025, "invalid_order_id"),
+  (PricePrecisionExceeded, 10026, "price_precision_exceeded"),
+  (NonIntegerContractAmount, 10027, "non_integer_contract_amount"),
+  (TooManyRequests, 10028, "too_many_requests"),
+  (NotOwnerOfOrder, 10029, "not_owner_of_order"),
+  (MustBeWebsocketRequest, 10030, "must_be_websocket_request"),
+  (InvalidArgsForInstrument, 10031, "invalid_args_for_instrument"),
+  (WholeCostTooLow, 10032, "whole_cost_too_low"),
+  (NotImplemented, 10033, "not_implemented"),
+  (TriggerPriceTooHigh, 10034, "trigger_price_too_high"),
+  (TriggerPriceTooLow, 10035, "trigger_price_too_low"),
+  (InvalidMaxShowAmount, 10036, "invalid_max_show_amount"),
+  (NonPmeTotalShortOptionsPositionsSize, 10037, "non_pme_total_short_options_positions_size"),
+  (PmeMaxRiskReducingOrders, 10038, "pme_max_risk_reducing_orders"),
+  (Retry, 10040, "retry"),
+  (SettlementInProgress, 10041, "settlement_in_progress"),
+  (PriceWrongTick, 10043, "price_wrong_tick"),
+  (TriggerPriceWrongTick, 10044, "trigger_price_wrong_tick"),
+  (CanNotCancelLiquidationOrder, 10045, "can_not_cancel_liquidation_order"),
+  (CanNotEditLiquidationOrder, 10046, "can_not_edit_liquidation_order"),
+  (MatchingEngineQueueFull, 10047, "matching_engine_queue_full"),
+  (NotOnThisServer, 10048, "not_on_this_server"),
+  (CancelOnDisconnectFailed, 10049, "cancel_on_disconnect_failed"),
+  (TooManyConcurrentRequests, 10066, "too_many_concurrent_requests"),
+  (DisabledWhilePositionLock, 10072, "disabled_while_position_lock"),
+  (AlreadyFilled, 11008, "already_filled"),
+  (MaxSpotOpenOrders, 11013, "max_spot_open_orders"),
+  (PostOnlyPriceModificationNotPossible, 11021, "post_only_price_modification_not_possible"),
+  (MaxSpotOrderQuantity, 11022, "max_spot_order_quantity"),
+  (InvalidArguments, 11029, "invalid_arguments"),
+  (OtherReject, 11030, "other_reject"),
+  (OtherError, 11031, "other_error"),
+  (NoMoreTriggers, 11035, "no_more_triggers"),
+  (InvalidTriggerPrice, 11036, "invalid_trigger_price"),
+  (OutdatedInstrumentForIvOrder, 11037, "outdated_instrument_for_IV_order"),
+  (NoAdvForFutures, 11038, "no_adv_for_futures"),
+  (NoAdvPostonly, 11039, "no_adv_postonly"),
+  (NotAdvOrder, 11041, "not_adv_order"),
+  (PermissionDenied, 11042, "permission_denied"),
+  (BadArgument, 11043, "bad_argument"),
+  (NotOpenOrder, 11044, "not_open_order"),
+  (InvalidEvent, 11045, "invalid_event"),
+  (OutdatedInstrument, 11046, "outdated_instrument"),
+  (UnsupportedArgCombination, 11047, "unsupported_arg_combination"),
+  (WrongMaxShowForOption, 11048, "wrong_max_show_for_option"),
+  (BadArguments, 11049, "bad_arguments"),
+  (BadRequest, 11050, "bad_request"),
+  (SystemMaintenance, 11051, "system_maintenance"),
+  (SubscribeErrorUnsubscribed, 11052, "subscribe_error_unsubscribed"),
+  (TransferNotFound, 11053, "transfer_not_found"),
+  (PostOnlyReject, 11054, "post_only_reject"),
+  (UnauthenticatedPublicRequestsTemporarilyDisabled, 11056, "unauthenticated_public_requests_temporarily_disabled"),
+  (InvalidAddr, 11090, "invalid_addr"),
+  (InvalidTransferAddress, 11091, "invalid_transfer_address"),
+  (AddressAlreadyExist, 11092, "address_already_exist"),
+  (MaxAddrCountExceeded, 11093, "max_addr_count_exceeded"),
+  (InternalServerError, 11094, "internal_server_error"),
+  (DisabledDepositAddressCreation, 11095, "disabled_deposit_address_creation"),
+  (AddressBelongsToUser, 11096, "address_belongs_to_user"),
+  (NoDepositAddress, 11097, "no_deposit_address"),
+  (BadTfa, 12000, "bad_tfa"),
+  (TooManySubaccounts, 12001, "too_many_subaccounts"),
+  (WrongSubaccountName, 12002, "wrong_subaccount_name"),
+  (LoginOverLimit, 12003, "login_over_limit"),
+  (RegistrationOverLimit, 12004, "registration_over_limit"),
+  (CountryIsBanned, 12005, "country_is_banned"),
+  (TransferNotAllowed, 12100, "transfer_not_allowed"),
+  (TfaOverLimit, 12998, "tfa_over_limit"),
+  (TfaUsed, 12999, "tfa_used"),
+  (InvalidLogin, 13000, "invalid_login"),
+  (AccountNotActivated, 13001, "account_not_activated"),
+  (AccountBlocked, 13002, "account_blocked"),
+  (TfaRequired, 13003, "tfa_required"),
+  (Unauthorized, 13004, "invalid_credentials"),
+  (PwdMatchError, 13005, "pwd_match_error"),
+  (SecurityError, 13006, "security_error"),
+  (UserNotFound, 13007, "user_not_found"),
+  (RequestFailed, 13008, "request_failed"),
+  (InvalidToken, 13009, "unauthorized"),
+  (ValueRequired, 13010, "value_required"),
+  (ValueTooShort, 13011, "value_too_short"),
+  (UnavailableInSubaccount, 13012, "unavailable_in_subaccount"),
+  (InvalidPhoneNumber, 13013, "invalid_phone_number"),
+  (CannotSendSms, 13014, "cannot_send_sms"),
+  (InvalidSmsCode, 13015, "invalid_sms_code"),
+  (InvalidInput, 13016, "invalid_input"),
+  (InvalidContentType, 13018, "invalid_content_type"),
+  (OrderbookClosed, 13019, "orderbook_closed"),
+  (NotFound, 13020, "not_found"),
+  (Forbidden, 13021, "forbidden"),
+  (MethodSwitchedOffByAdmin, 13025, "method_switched_off_by_admin"),
+  (TemporarilyUnavailable, 13028, "temporarily_unavailable"),
+  (MmpTrigger, 13030, "mmp_trigger"),
+  (VerificationRequired, 13031, "verification_required"),
+  (NonUniqueOrderLabel, 13032, "non_unique_order_label"),
+  (NoMoreSecurityKeysAllowed, 13034, "no_more_security_keys_allowed"),
+  (ActiveComboLimitReached, 13035, "active_combo_limit_reached"),
+  (UnavailableForComboBooks, 13036, "unavailable_for_combo_books"),
+  (IncompleteKycData, 13037, "incomplete_KYC_data"),
+  (MmpRequired, 13040, "mmp_required"),
+  (CodNotEnabled, 13042, "cod_not_enabled"),
+  (QuotesFrozen, 13043, "quotes_frozen"),
+  (ScopeExceeded, 13403, "scope_exceeded"),
+  (Unavailable, 13503, "unavailable"),
+  (RequestCancelledByUser, 13666, "request_cancelled_by_user"),
+  (Replaced, 13777, "replaced"),
+  (RawSubscriptionsNotAvailableForUnauthorized, 13778, "raw_subscriptions_not_available_for_unauthorized"),
+  (CouponAlreadyUsed, 13781, "coupon_already_used"),
+  (KycTransferAlreadyInitiated, 13791, "KYC_transfer_already_initiated"),
+  (ParseError, -32700, "parse_error"),
+  (InvalidRequest, -32600, "invalid_request"),
+  (MethodNotFound, -32601, "method_not_found"),
+  (InvalidParams, -32602, "invalid_params"),
+  (MissingParams, -32000, "missing_params"),
+];
+
+impl DeribitErrorCode {
+  pub fn from_code(code: i64) -> Self {
+    CODES.iter()
+      .find(|(_, c, _)| *c == code)
+      .map_or(Other(code), |(kind, _, _)| *kind)
+  }
+
+  pub fn code(&self) -> i64 {
+    match self {
+      Other(code) => *code,
+      kind => CODES.iter().find(|(k, _, _)| k == kind).map_or(0, |(_, code, _)| *code),
+    }
+  }
+
+  /// The documented name, e.g. `"not_enough_funds"`. `"unknown"` for `Other`.
+  pub fn as_str(&self) -> &'static str {
+    CODES.iter().find(|(k, _, _)| k == self).map_or("unknown", |(_, _, name)| name)
+  }
+
+  /// Transient conditions that may succeed if the same request is sent again later.
+  pub fn is_retryable(&self) -> bool {
+    matches!(self,
+      TooManyRequests | TooManyConcurrentRequests | Retry | SettlementInProgress | MatchingEngineQueueFull
+      | SystemMaintenance | InternalServerError | TemporarilyUnavailable | Unavailable | NotOnThisServer
+    )
+  }
+
+  /// Missing, invalid or expired credentials, or a token without the required scope.
+  pub fn is_auth_error(&self) -> bool {
+    matches!(self,
+      AuthorizationRequired | Unauthorized | InvalidToken | InvalidLogin | AccountBlocked
+      | TfaRequired | Forbidden | ScopeExceeded | PermissionDenied
+    )
+  }
+
+  /// The request was rejected by Deribit's rate limits.
+  pub fn is_rate_limit(&self) -> bool {
+    matches!(self, TooManyRequests | TooManyConcurrentRequests)
+  }
+}
+
+impl std::fmt::Display for DeribitErrorCode {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} ({})", self.as_str(), self.code())
+  }
+}
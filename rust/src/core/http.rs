@@ -0,0 +1,229 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use serde::de::DeserializeOwned;
+
+use crate::core::client::JsonRpcRequest;
+use crate::core::{now_us, parse_json, Error, PrivateClient, RequestMetrics, Response, RpcTransport, Scope, DEFAULT_TIMEOUT};
+
+pub const TESTNET_HTTP: &str = "https://test.deribit.com/api/v2";
+pub const MAINNET_HTTP: &str = "https://www.deribit.com/api/v2";
+
+/// JSON-RPC client over HTTPS. Every request is a separate `POST {base_url}/{method}`, so there is no connection to keep open,
+/// which suits short-lived jobs; subscriptions, heartbeats and other connection-bound features need a `SocketClient`.
+/// Cheap to clone: clones share the underlying connection pool.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+  http: reqwest::Client,
+  base_url: String,
+  timeout: Option<Duration>,
+  basic_auth: Option<(String, String)>,
+  id_counter: Arc<AtomicU64>,
+}
+
+impl HttpClient {
+  /// Create a client for the given API root.
+  /// - `base_url` - e.g. `deribit::TESTNET_HTTP`, `deribit::MAINNET_HTTP` or a local stand-in such as `"http://127.0.0.1:8080/api/v2"`.
+  pub fn new(base_url: &str) -> Self {
+    HttpClient {
+      http: reqwest::Client::new(),
+      base_url: base_url.trim_end_matches('/').to_string(),
+      timeout: Some(DEFAULT_TIMEOUT),
+      basic_auth: None,
+      id_counter: Arc::new(AtomicU64::new(0)),
+    }
+  }
+
+  /// Use a preconfigured `reqwest::Client`, e.g. with a proxy or custom TLS roots.
+  pub fn with_http(mut self, http: reqwest::Client) -> Self {
+    self.http = http;
+    self
+  }
+
+  /// Set the timeout for every request. `None` waits forever.
+  /// - `timeout` - e.g. `Some(deribit::DEFAULT_TIMEOUT)`, the default.
+  pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Send the API key with every request using HTTP Basic auth, so raw private requests work without `public/auth`.
+  /// The typed private API needs a `PrivateClient`, which authenticates with a token instead; see `authenticated`.
+  /// - `client_id` - The client ID provided by Deribit.
+  /// - `client_secret` - The client secret provided by Deribit.
+  pub fn basic_auth(mut self, client_id: &str, client_secret: &str) -> Self {
+    self.basic_auth = Some((client_id.to_string(), client_secret.to_string()));
+    self
+  }
+
+  pub fn base_url(&self) -> &str {
+    &self.base_url
+  }
+
+  /// Send a request and wait for its reply, failing with `Error::Timeout` if it takes too long.
+  /// - `method` - The API method to call, e.g. `"public/get_instruments"`
+  /// - `params` - The parameters for the request, as a JSON object.
+  pub async fn request(&self, method: &str, params: serde_json::Value) -> Result<Response, Error> {
+    self.post(method, params, None).await
+  }
+
+  /// Send a request and parse its result into `T`.
+  /// - `method` - The API method to call, e.g. `"public/get_instruments"`
+  /// - `params` - The parameters for the request, as a JSON object.
+  pub async fn call<T: DeserializeOwned>(&self, method: &str, params: serde_json::Value) -> Result<T, Error> {
+    let resp = self.request(method, params).await?.value()?;
+    parse_json::<T>(resp)
+  }
+
+  /// Authenticate with client credentials. The returned client can be used to make authenticated requests,
+  /// which are sent with the access token as a Bearer token.
+  /// - `client_id` - The client ID provided by Deribit.
+  /// - `client_secret` - The client secret provided by Deribit.
+  /// - `scope` - The scope (i.e. permissions) of the new session, e.g. `deribit::Scope::default()`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-auth)
+  pub async fn authenticated(self, client_id: &str, client_secret: &str, scope: Scope) -> Result<PrivateClient<HttpClient>, Error> {
+    PrivateClient::login(self, client_id, client_secret, scope).await
+  }
+
+  async fn post(&self, method: &str, params: serde_json::Value, bearer: Option<&str>) -> Result<Response, Error> {
+    let id = self.id_counter.fetch_add(1, Ordering::Relaxed) + 1;
+    let body = serde_json::to_string(&JsonRpcRequest { jsonrpc: "2.0", id, method, params })?;
+    let mut req = self.http.post(format!("{}/{}", self.base_url, method))
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .body(body);
+    if let Some(timeout) = self.timeout {
+      req = req.timeout(timeout);
+    }
+    req = match (bearer, &self.basic_auth) {
+      (Some(token), _) => req.bearer_auth(token),
+      (None, Some((client_id, client_secret))) => req.basic_auth(client_id, Some(client_secret)),
+      (None, None) => req,
+    };
+
+    let sent_us = now_us();
+    let reply = req.send().await?;
+    // API errors come back as JSON-RPC errors with a 4xx status; anything else that isn't JSON-RPC is an HTTP error
+    let status = reply.error_for_status_ref().err();
+    let text = reply.text().await?;
+    let received_us = now_us();
+    let mut value = match (serde_json::from_str::<serde_json::Value>(&text), status) {
+      (Ok(value), _) if value.get("result").is_some() || value.get("error").is_some() => value,
+      (_, Some(err)) => return Err(err.into()),
+      (Ok(_), None) => return Err(Error::Logic("Response must contain either result or error")),
+      (Err(e), None) => return Err(e.into()),
+    };
+    if value.get("id").is_none_or(|id| id.is_null()) {
+      value["id"] = serde_json::Value::from(id);
+    }
+    let mut resp = parse_json::<Response>(value)?;
+    resp.metrics = Some(RequestMetrics { sent_us, server_in_us: resp.us_in, server_out_us: resp.us_out, received_us });
+    Ok(resp)
+  }
+}
+
+impl RpcTransport for HttpClient {
+  fn request(&self, method: &str, params: serde_json::Value) -> impl Future<Output = Result<Response, Error>> + Send {
+    self.post(method, params, None)
+  }
+
+  /// Sends the token in the `Authorization: Bearer` header rather than as a parameter.
+  fn authed_request(&self, method: &str, params: serde_json::Value, access_token: &str) -> impl Future<Output = Result<Response, Error>> + Send {
+    self.post(method, params, Some(access_token))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::io::{AsyncReadExt, AsyncWriteExt};
+  use tokio::net::TcpListener;
+
+  use super::*;
+
+  /// A request received by `serve`: its request line, headers and body.
+  struct Received {
+    line: String,
+    headers: Vec<String>,
+    body: serde_json::Value,
+  }
+
+  /// Answer one HTTP request on a local port with `status` and `body`, or never answer if `status` is 0.
+  async fn serve(status: u16, body: &'static str) -> (String, tokio::task::JoinHandle<Received>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}/api/v2", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+      let (mut socket, _) = listener.accept().await.unwrap();
+      let mut data = Vec::new();
+      let mut buffer = [0u8; 4096];
+      let (head, length) = loop {
+        let read = socket.read(&mut buffer).await.unwrap();
+        data.extend_from_slice(&buffer[..read]);
+        let text = String::from_utf8_lossy(&data).to_string();
+        if let Some(end) = text.find("\r\n\r\n") {
+          let head = text[..end].to_string();
+          let length = head.lines()
+            .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|length| length.trim().parse::<usize>().unwrap()))
+            .unwrap_or(0);
+          if data.len() >= end + 4 + length {
+            break (head, length);
+          }
+        }
+      };
+      let mut lines = head.lines().map(str::to_string);
+      let received = Received {
+        line: lines.next().unwrap(),
+        headers: lines.collect(),
+        body: serde_json::from_slice(&data[data.len() - length..]).unwrap(),
+      };
+      if status == 0 {
+        std::future::pending::<()>().await;
+      }
+      let reply = format!("HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body);
+      socket.write_all(reply.as_bytes()).await.unwrap();
+      received
+    });
+    (base_url, server)
+  }
+
+  #[tokio::test]
+  async fn requests_are_posted_to_the_method_path() {
+    let (base_url, server) = serve(200, r#"{"jsonrpc":"2.0","id":1,"result":1700000000000,"usIn":1,"usOut":3,"usDiff":2,"testnet":true}"#).await;
+    let client = HttpClient::new(&format!("{}/", base_url)).basic_auth("id", "secret");
+    let resp = client.request("public/get_time", serde_json::json!({ "a": 1 })).await.unwrap();
+    assert_eq!(resp.result, Some(serde_json::json!(1700000000000i64)));
+    let metrics = resp.metrics.unwrap();
+    assert_eq!(metrics.server_us(), Some(2));
+    assert!(metrics.received_us >= metrics.sent_us);
+
+    let received = server.await.unwrap();
+    assert_eq!(received.line, "POST /api/v2/public/get_time HTTP/1.1");
+    assert!(received.headers.iter().any(|header| header.eq_ignore_ascii_case("authorization: Basic aWQ6c2VjcmV0")));
+    assert_eq!(received.body["method"], "public/get_time");
+    assert_eq!(received.body["params"], serde_json::json!({ "a": 1 }));
+  }
+
+  #[tokio::test]
+  async fn api_errors_are_returned_with_their_code() {
+    let (base_url, _server) = serve(400, r#"{"jsonrpc":"2.0","error":{"code":10009,"message":"not_enough_funds"},"testnet":true}"#).await;
+    let client = HttpClient::new(&base_url);
+    let err = client.call::<serde_json::Value>("private/buy", serde_json::json!({})).await.unwrap_err();
+    assert_eq!(err.api_code(), Some(crate::core::DeribitErrorCode::NotEnoughFunds));
+  }
+
+  #[tokio::test]
+  async fn other_failures_are_http_errors() {
+    let (base_url, _server) = serve(502, "bad gateway").await;
+    let err = HttpClient::new(&base_url).request("public/test", serde_json::json!({})).await.unwrap_err();
+    assert!(matches!(err, Error::Http(_)), "{:?}", err);
+    assert!(err.is_retryable());
+  }
+
+  #[tokio::test]
+  async fn slow_replies_time_out() {
+    let (base_url, _server) = serve(0, "").await;
+    let client = HttpClient::new(&base_url).timeout(Some(Duration::from_millis(100)));
+    let err = client.request("public/test", serde_json::json!({})).await.unwrap_err();
+    assert!(matches!(err, Error::Timeout), "{:?}", err);
+  }
+}
@@ -1,13 +1,28 @@
 mod client;
 mod auth;
 mod error;
+mod error_code;
 mod util;
 mod response;
 mod scope;
+mod reconnect;
+mod routing;
+mod delivery;
+mod rate_limit;
+mod transport;
+mod http;
 
-pub use response::{Response, Message, Notification, ResponseHandler};
+pub use response::{Response, Message, Notification, Heartbeat, HeartbeatKind, ResponseHandler, ReplySender, RequestMetrics};
 pub use client::{SocketClient, TESTNET, MAINNET};
-pub use auth::PrivateClient;
+pub use transport::{RpcTransport, Transport};
+pub use http::{HttpClient, TESTNET_HTTP, MAINNET_HTTP};
+pub use auth::{Auth, AuthResponse, PrivateClient, SharedAuth};
 pub use error::{ApiError, Error};
-pub use util::parse_json;
-pub use scope::{Scope, Access};
\ No newline at end of file
+pub use error_code::DeribitErrorCode;
+pub use util::{parse_json, now_us};
+pub use scope::{Scope, Access};
+pub use delivery::{Backpressure, Delivery, NotificationSender, NotificationReceiver, notification_channel};
+pub use rate_limit::{RateLimit, Limits, CostClass, RateLimitPolicy, RateLimiter, DEFAULT_MATCHING_ENGINE, DEFAULT_NON_MATCHING};
+pub use routing::{Pattern, ListenerId, Router};
+pub use reconnect::{Reconnect, ConnectionState, DEFAULT_TIMEOUT};
+pub(crate) use reconnect::{Connector, Session};
\ No newline at end of file
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+use crate::core::Error;
+
+/// Sustained rate and burst size of a credit pool, in requests.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+  /// Requests per second
+  pub rate: f64,
+  /// Requests that can be sent at once after being idle
+  pub burst: f64,
+}
+
+impl RateLimit {
+  /// Whether the pool refills at all and can hold at least one request.
+  pub fn is_valid(&self) -> bool {
+    self.rate > 0.0 && self.rate.is_finite() && self.burst >= 1.0
+  }
+}
+
+/// Rate limits of the account, as returned in `AccountSummary::limits`.
+///
+/// Source: [Deribit docs](https://docs.deribit.com/#rate-limits)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Limits {
+  #[serde(default)]
+  pub limits_per_currency: Option<bool>,
+  #[serde(default)]
+  pub non_matching_engine: Option<RateLimit>,
+  /// Either a single limit, or limits per group (e.g. `trading.total`, `futures`, `options`).
+  #[serde(default)]
+  pub matching_engine: Option<serde_json::Value>,
+}
+
+impl Limits {
+  /// The limit applying to every matching engine request: the flat limit, `trading.total` if present,
+  /// or else the strictest of the per-group limits.
+  pub fn matching_engine_limit(&self) -> Option<RateLimit> {
+    let value = self.matching_engine.as_ref()?;
+    if let Ok(limit) = serde_json::from_value::<RateLimit>(value.clone()) {
+      return Some(limit);
+    }
+    if let Some(total) = value.pointer("/trading/total") {
+      if let Ok(limit) = serde_json::from_value::<RateLimit>(total.clone()) {
+        return Some(limit);
+      }
+    }
+    let mut limits = Vec::new();
+    collect_limits(value, &mut limits);
+    limits.into_iter().min_by(|a, b| a.rate.total_cmp(&b.rate))
+  }
+}
+
+fn collect_limits(value: &serde_json::Value, limits: &mut Vec<RateLimit>) {
+  if let Ok(limit) = serde_json::from_value::<RateLimit>(value.clone()) {
+    limits.push(limit);
+  } else if let Some(object) = value.as_object() {
+    for value in object.values() {
+      collect_limits(value, limits);
+    }
+  }
+}
+
+/// Which credit pool a method draws from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CostClass {
+  /// Order entry and cancellation
+  MatchingEngine,
+  /// Everything else
+  NonMatching,
+}
+
+impl CostClass {
+  pub fn of(method: &str) -> Self {
+    match method {
+      "private/buy" | "private/sell" | "private/edit" | "private/edit_by_label" | "private/cancel"
+      | "private/cancel_by_label" | "private/cancel_all" | "private/cancel_all_by_currency"
+      | "private/cancel_all_by_currency_pair" | "private/cancel_all_by_instrument" | "private/cancel_all_by_kind_or_type"
+      | "private/close_position" | "private/mass_quote" | "private/cancel_quotes"
+      | "private/verify_block_trade" | "private/execute_block_trade" => CostClass::MatchingEngine,
+      _ => CostClass::NonMatching,
+    }
+  }
+}
+
+/// What to do with a request when its pool has no credits left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitPolicy {
+  /// Wait until the pool refills. The wait counts towards the request timeout.
+  Queue,
+  /// Fail immediately with `Error::RateLimited`.
+  FailFast,
+}
+
+#[derive(Debug, Clone)]
+struct Bucket {
+  limit: RateLimit,
+  tokens: f64,
+  updated: Instant,
+}
+
+impl Bucket {
+  fn new(limit: RateLimit) -> Self {
+    Bucket { limit, tokens: limit.burst, updated: Instant::now() }
+  }
+
+  /// Take `cost` credits, or return how long until they are available. A cost above the burst takes a full bucket.
+  fn take(&mut self, now: Instant, cost: f64) -> Result<(), Duration> {
+    let cost = cost.min(self.limit.burst);
+    let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+    self.tokens = (self.tokens + elapsed * self.limit.rate).min(self.limit.burst);
+    self.updated = now;
+    if self.tokens >= cost {
+      self.tokens -= cost;
+      Ok(())
+    } else {
+      Err(Duration::try_from_secs_f64((cost - self.tokens) / self.limit.rate).unwrap_or(Duration::MAX))
+    }
+  }
+
+  fn set_limit(&mut self, limit: RateLimit) {
+    self.limit = limit;
+    self.tokens = self.tokens.min(limit.burst);
+  }
+}
+
+/// Client-side token buckets mirroring Deribit's credit pools, so requests are delayed (or rejected locally)
+/// instead of failing with `too_many_requests`.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+  matching_engine: Bucket,
+  non_matching: Bucket,
+  /// Credits charged per method, in requests; methods not listed cost one
+  costs: HashMap<String, f64>,
+  pub policy: RateLimitPolicy,
+}
+
+/// Deribit's default matching engine limit for new accounts.
+pub const DEFAULT_MATCHING_ENGINE: RateLimit = RateLimit { rate: 5.0, burst: 20.0 };
+/// Deribit's default non-matching engine limit.
+pub const DEFAULT_NON_MATCHING: RateLimit = RateLimit { rate: 20.0, burst: 100.0 };
+
+impl Default for RateLimiter {
+  fn default() -> Self {
+    RateLimiter {
+      matching_engine: Bucket::new(DEFAULT_MATCHING_ENGINE),
+      non_matching: Bucket::new(DEFAULT_NON_MATCHING),
+      costs: HashMap::new(),
+      policy: RateLimitPolicy::Queue,
+    }
+  }
+}
+
+impl RateLimiter {
+  /// Fails if either limit has no positive rate or a burst below one request, see `RateLimit::is_valid`.
+  pub fn new(matching_engine: RateLimit, non_matching: RateLimit, policy: RateLimitPolicy) -> Result<Self, Error> {
+    if !matching_engine.is_valid() || !non_matching.is_valid() {
+      return Err(Error::Logic("Rate limits need a positive rate and a burst of at least one request"));
+    }
+    Ok(RateLimiter {
+      matching_engine: Bucket::new(matching_engine),
+      non_matching: Bucket::new(non_matching),
+      costs: HashMap::new(),
+      policy,
+    })
+  }
+
+  /// Charge `method` more (or less) than one request's worth of credits, e.g. for the endpoints Deribit limits more strictly.
+  /// Ignored unless `cost` is positive and finite.
+  /// - `method` - e.g. `"private/get_transaction_log"`
+  /// - `cost` - Credits per call, in requests of its pool, e.g. `5.0`.
+  pub fn with_cost(mut self, method: &str, cost: f64) -> Self {
+    if cost > 0.0 && cost.is_finite() {
+      self.costs.insert(method.to_string(), cost);
+    }
+    self
+  }
+
+  /// Credits charged for one call to `method`, in requests of its pool.
+  pub fn cost(&self, method: &str) -> f64 {
+    self.costs.get(method).copied().unwrap_or(1.0)
+  }
+
+  /// Take the credits for `method`, or return how long until they are available.
+  pub fn try_acquire(&mut self, method: &str) -> Result<(), Duration> {
+    let now = Instant::now();
+    let cost = self.cost(method);
+    match CostClass::of(method) {
+      CostClass::MatchingEngine => self.matching_engine.take(now, cost),
+      CostClass::NonMatching => self.non_matching.take(now, cost),
+    }
+  }
+
+  /// Adopt the account's actual limits. Pools missing from `limits`, or with an invalid limit, keep their current limit.
+  pub fn tune(&mut self, limits: &Limits) {
+    if let Some(limit) = limits.matching_engine_limit().filter(RateLimit::is_valid) {
+      self.matching_engine.set_limit(limit);
+    }
+    if let Some(limit) = limits.non_matching_engine.filter(RateLimit::is_valid) {
+      self.non_matching.set_limit(limit);
+    }
+  }
+
+  pub fn matching_engine(&self) -> RateLimit {
+    self.matching_engine.limit
+  }
+
+  pub fn non_matching(&self) -> RateLimit {
+    self.non_matching.limit
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn bucket_waits_for_the_next_credit() {
+    let mut bucket = Bucket::new(RateLimit { rate: 4.0, burst: 2.0 });
+    let start = bucket.updated;
+    assert_eq!(bucket.take(start, 1.0), Ok(()));
+    assert_eq!(bucket.take(start, 1.0), Ok(()));
+    assert_eq!(bucket.take(start, 1.0), Err(Duration::from_millis(250)));
+    // Half a credit refilled, so half the wait is left
+    let later = start + Duration::from_millis(125);
+    assert_eq!(bucket.take(later, 1.0), Err(Duration::from_millis(125)));
+    assert_eq!(bucket.take(start + Duration::from_millis(250), 1.0), Ok(()));
+  }
+
+  #[test]
+  fn limits_without_a_rate_are_rejected() {
+    let stalled = RateLimit { rate: 0.0, burst: 10.0 };
+    assert!(RateLimiter::new(stalled, DEFAULT_NON_MATCHING, RateLimitPolicy::Queue).is_err());
+    assert!(RateLimiter::new(DEFAULT_MATCHING_ENGINE, RateLimit { rate: f64::NAN, burst: 10.0 }, RateLimitPolicy::Queue).is_err());
+
+    let mut limiter = RateLimiter::default();
+    let limits = Limits { limits_per_currency: None, non_matching_engine: Some(stalled), matching_engine: None };
+    limiter.tune(&limits);
+    assert_eq!(limiter.non_matching(), DEFAULT_NON_MATCHING);
+  }
+
+  #[test]
+  fn tiny_rates_wait_without_panicking() {
+    let mut bucket = Bucket::new(RateLimit { rate: 1e-320, burst: 1.0 });
+    let now = bucket.updated;
+    assert_eq!(bucket.take(now, 1.0), Ok(()));
+    assert_eq!(bucket.take(now, 1.0), Err(Duration::MAX));
+  }
+
+  #[test]
+  fn methods_draw_their_own_cost() {
+    let limit = RateLimit { rate: 1.0, burst: 10.0 };
+    let mut limiter = RateLimiter::new(limit, limit, RateLimitPolicy::FailFast).unwrap()
+      .with_cost("private/get_transaction_log", 4.0)
+      .with_cost("public/get_time", f64::NAN);
+    assert_eq!(limiter.cost("public/get_time"), 1.0);
+    assert_eq!(limiter.try_acquire("private/get_transaction_log"), Ok(()));
+    assert_eq!(limiter.try_acquire("private/get_transaction_log"), Ok(()));
+    // Two credits left, so a third call waits for two more
+    let wait = limiter.try_acquire("private/get_transaction_log").unwrap_err();
+    assert!(wait > Duration::from_millis(1900) && wait <= Duration::from_secs(2), "{:?}", wait);
+    assert_eq!(limiter.try_acquire("private/get_positions"), Ok(()));
+    // Order entry draws from the other pool
+    assert_eq!(limiter.try_acquire("private/buy"), Ok(()));
+  }
+
+  #[tokio::test]
+  async fn fail_fast_requests_are_rejected_locally() {
+    let mock = crate::mock::MockDeribit::new();
+    let client = mock.client();
+    let limit = RateLimit { rate: 1.0, burst: 1.0 };
+    client.set_rate_limiter(Some(RateLimiter::new(limit, limit, RateLimitPolicy::FailFast).unwrap()));
+    client.request("public/test", serde_json::json!({})).await.unwrap();
+    let err = client.request("public/test", serde_json::json!({})).await.unwrap_err();
+    assert!(matches!(err, Error::RateLimited(wait) if wait > Duration::ZERO && wait <= Duration::from_secs(1)), "{:?}", err);
+    assert_eq!(mock.requests_to("public/test").len(), 1);
+  }
+
+  #[tokio::test]
+  async fn queued_requests_wait_for_credits() {
+    let mock = crate::mock::MockDeribit::new();
+    let client = mock.client();
+    let limit = RateLimit { rate: 20.0, burst: 1.0 };
+    client.set_rate_limiter(Some(RateLimiter::new(limit, limit, RateLimitPolicy::Queue).unwrap()));
+    let start = Instant::now();
+    for _ in 0..3 {
+      client.request("public/test", serde_json::json!({})).await.unwrap();
+    }
+    // The burst covers the first request, the other two wait 50ms each
+    assert!(start.elapsed() >= Duration::from_millis(95), "{:?}", start.elapsed());
+    assert_eq!(mock.requests_to("public/test").len(), 3);
+  }
+}
@@ -0,0 +1,314 @@
+use std::time::Duration;
+use futures_util::{future::BoxFuture, stream::SplitStream, StreamExt};
+use tokio::sync::oneshot;
+
+use crate::core::{parse_json, AuthResponse, Error, HeartbeatKind, RateLimiter, SharedAuth, SocketClient, Transport};
+use crate::session::CodScope;
+
+/// Backoff policy used to re-establish a dropped connection.
+#[derive(Debug, Clone)]
+pub struct Reconnect {
+  /// Delay before the first attempt.
+  pub initial_delay: Duration,
+  /// Upper bound for the delay between attempts.
+  pub max_delay: Duration,
+  /// Factor applied to the delay after every failed attempt.
+  pub multiplier: f64,
+  /// Give up after this many consecutive failures. `None` retries forever; `Some(0)` never reconnects.
+  pub max_attempts: Option<u32>,
+}
+
+impl Default for Reconnect {
+  fn default() -> Self {
+    Reconnect {
+      initial_delay: Duration::from_millis(500),
+      max_delay: Duration::from_secs(30),
+      multiplier: 2.0,
+      max_attempts: None,
+    }
+  }
+}
+
+impl Reconnect {
+  /// Never reconnect: the client is closed as soon as the connection drops.
+  pub fn never() -> Self {
+    Reconnect { max_attempts: Some(0), ..Reconnect::default() }
+  }
+
+  /// Delay before the given (zero-based) attempt.
+  pub fn delay(&self, attempt: u32) -> Duration {
+    let factor = self.multiplier.max(1.0).powi(attempt.min(i32::MAX as u32) as i32);
+    self.initial_delay.mul_f64(factor).min(self.max_delay)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+  Connected,
+  /// The connection dropped; pending requests were failed with `Error::Disconnected` and a new one is being established
+  /// and its session restored. New requests fail with `Error::Disconnected` until then.
+  Reconnecting,
+  /// The connection dropped for good. Listeners have been removed, so subscription streams have ended.
+  Closed,
+}
+
+/// Opens a new connection, e.g. to the URL the client was started with.
+pub(crate) type Connector = Box<dyn Fn() -> BoxFuture<'static, Result<Box<dyn Transport>, Error>> + Send + Sync>;
+
+/// What the client needs to know to rebuild its session on a fresh connection.
+pub(crate) struct Session {
+  pub connector: Option<Connector>,
+  pub reconnect: Reconnect,
+  pub auth: Option<SharedAuth>,
+  pub heartbeat: Option<Duration>,
+  pub timeout: Option<Duration>,
+  /// Client name and version sent with `public/hello`
+  pub hello: Option<(String, String)>,
+  pub cancel_on_disconnect: Option<CodScope>,
+  pub rate_limiter: Option<RateLimiter>,
+}
+
+impl Default for Session {
+  fn default() -> Self {
+    Session {
+      connector: None,
+      reconnect: Reconnect::never(),
+      auth: None,
+      heartbeat: None,
+      timeout: Some(DEFAULT_TIMEOUT),
+      hello: None,
+      cancel_on_disconnect: None,
+      rate_limiter: Some(RateLimiter::default()),
+    }
+  }
+}
+
+/// Default time to wait for a reply before failing with `Error::Timeout`.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Number of heartbeat intervals without any message after which the connection is considered dead.
+const MISSED_HEARTBEATS: u32 = 2;
+
+type Reader = SplitStream<Box<dyn Transport>>;
+
+impl SocketClient {
+  /// Read loop: dispatches incoming messages and reconnects whenever the connection drops.
+  /// After a reconnect the session is restored in the background while replies keep being dispatched;
+  /// the state only goes back to `Connected` once that succeeds, and a failed restore drops the connection again.
+  pub(crate) async fn run(self, mut read: Reader) {
+    let mut restoring: Option<oneshot::Receiver<Result<(), Error>>> = None;
+    // Consecutive connections lost or failed to restore, for the reconnection backoff
+    let mut failures = 0;
+    loop {
+      loop {
+        let heartbeat = self.session.lock().unwrap().heartbeat;
+        let next = async {
+          match heartbeat {
+            // `None` marks a missed heartbeat
+            Some(interval) => tokio::time::timeout(interval * MISSED_HEARTBEATS, read.next()).await.ok(),
+            None => Some(read.next().await),
+          }
+        };
+        let restored = async {
+          match restoring.as_mut() {
+            Some(restoring) => restoring.await.unwrap_or(Err(Error::Disconnected)),
+            None => std::future::pending().await,
+          }
+        };
+        let next = tokio::select! {
+          next = next => next,
+          restored = restored => {
+            restoring = None;
+            match restored {
+              Ok(()) => {
+                failures = 0;
+                self.state.send_replace(ConnectionState::Connected);
+                continue;
+              }
+              Err(_) => break,
+            }
+          }
+        };
+        match next {
+          Some(Some(Ok(tungstenite::Message::Text(msg)))) => {
+            if let Some(heartbeat) = self.handler.handle(&msg) {
+              if heartbeat.params.kind == HeartbeatKind::TestRequest {
+                // A failed answer means the connection is gone, which the next read reports
+                let _ = self.send("public/test", serde_json::json!({}), 0).await;
+              }
+            }
+          }
+          Some(Some(Ok(tungstenite::Message::Close(_)))) | Some(Some(Err(_))) | Some(None) | None => break,
+          Some(Some(Ok(_))) => {}
+        }
+      }
+
+      self.handler.disconnect();
+      self.state.send_replace(ConnectionState::Reconnecting);
+      match self.reconnect(failures).await {
+        Some(socket) => {
+          failures += 1;
+          let (write, new_read) = socket.split();
+          let _ = self.sinks.send(write);
+          read = new_read;
+          let (restored, restoring_rx) = oneshot::channel();
+          restoring = Some(restoring_rx);
+          let client = self.clone();
+          tokio::spawn(async move {
+            let _ = restored.send(client.restore().await);
+          });
+        }
+        None => {
+          self.handler.close();
+          self.state.send_replace(ConnectionState::Closed);
+          return;
+        }
+      }
+    }
+  }
+
+  /// Try to open a new connection according to the reconnection policy.
+  /// - `failures` - Reconnections that already failed to stick, which count towards the backoff and `max_attempts`.
+  async fn reconnect(&self, failures: u32) -> Option<Box<dyn Transport>> {
+    let mut attempt = failures;
+    loop {
+      let policy = self.session.lock().unwrap().reconnect.clone();
+      if policy.max_attempts.is_some_and(|max| attempt >= max) {
+        return None;
+      }
+      tokio::time::sleep(policy.delay(attempt)).await;
+      let connecting = {
+        let session = self.session.lock().unwrap();
+        (session.connector.as_ref()?)()
+      };
+      if let Ok(socket) = connecting.await {
+        return Some(socket);
+      }
+      attempt += 1;
+    }
+  }
+
+  /// Re-identify the client, re-enable heartbeats, re-authenticate with the stored refresh token, re-enable
+  /// cancel-on-disconnect and re-subscribe every channel that has a listener.
+  /// The renewed tokens are written back to the shared credentials, since Deribit rotates the refresh token.
+  async fn restore(&self) -> Result<(), Error> {
+    let (auth, heartbeat, hello, cod, timeout) = {
+      let session = self.session.lock().unwrap();
+      (session.auth.clone(), session.heartbeat, session.hello.clone(), session.cancel_on_disconnect, session.timeout)
+    };
+    let request = |method: &'static str, params: serde_json::Value| self.dispatch(method, params, timeout);
+    if let Some((client_name, client_version)) = hello {
+      let params = serde_json::json!({ "client_name": client_name, "client_version": client_version });
+      request("public/hello", params).await?.value()?;
+    }
+    if let Some(interval) = heartbeat {
+      request("public/set_heartbeat", serde_json::json!({ "interval": interval.as_secs() })).await?.value()?;
+    }
+    let subscribe_method = match auth {
+      Some(shared) => {
+        let refresh_token = shared.lock().unwrap().response.refresh_token.clone();
+        let params = serde_json::json!({
+          "grant_type": "refresh_token",
+          "refresh_token": refresh_token,
+        });
+        let resp = request("public/auth", params).await?.value()?;
+        let auth = parse_json::<AuthResponse>(resp)?.parse();
+        *shared.lock().unwrap() = auth.clone();
+        if let Some(scope) = cod {
+          let params = serde_json::json!({ "scope": scope, "access_token": auth.response.access_token });
+          request("private/enable_cancel_on_disconnect", params).await?.value()?;
+        }
+        "private/subscribe"
+      }
+      None => "public/subscribe",
+    };
+
+    let channels: Vec<String> = self.handler.subscriptions.lock().unwrap().channels();
+    if !channels.is_empty() {
+      let params = serde_json::json!({ "channels": channels });
+      request(subscribe_method, params).await?.value()?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::core::Scope;
+  use crate::mock::{MockDeribit, MOCK_ACCESS_TOKEN, MOCK_REFRESH_TOKEN, MOCK_SCOPE};
+
+  fn fast() -> Reconnect {
+    Reconnect { initial_delay: Duration::from_millis(10), max_delay: Duration::from_millis(50), ..Reconnect::default() }
+  }
+
+  #[tokio::test]
+  async fn reconnect_restores_the_session() {
+    let mock = MockDeribit::new();
+    // Like Deribit, hand out new tokens on every refresh
+    mock.on("public/auth", |req| {
+      let refreshed = req.params["grant_type"] == "refresh_token";
+      Ok(serde_json::json!({
+        "access_token": if refreshed { "refreshed_access_token" } else { MOCK_ACCESS_TOKEN },
+        "refresh_token": if refreshed { "refreshed_refresh_token" } else { MOCK_REFRESH_TOKEN },
+        "expires_in": 31536000,
+        "scope": MOCK_SCOPE,
+        "token_type": "bearer",
+      }))
+    });
+    mock.respond("private/enable_cancel_on_disconnect", serde_json::json!("ok"));
+    mock.respond("private/get_positions", serde_json::json!([]));
+    let socket = mock.reconnecting_client(fast()).await;
+    let mut client = socket.clone().authenticated("mock_client_id", "mock_client_secret", Scope::default()).await.unwrap();
+    let mut other = client.clone();
+    client.enable_heartbeat(Duration::from_secs(10)).await.unwrap();
+    client.enable_cancel_on_disconnect(CodScope::Connection).await.unwrap();
+    let mut orders = client.subscribe::<serde_json::Value>(&["user.orders.any.any.raw"]).await.unwrap();
+
+    mock.disconnect();
+    let resubscribed = mock.wait_for("private/subscribe", 2, Duration::from_secs(1)).await.expect("the channels were not re-subscribed");
+    let mut state = socket.state();
+    tokio::time::timeout(Duration::from_secs(1), state.wait_for(|state| *state == ConnectionState::Connected)).await.unwrap().unwrap();
+
+    let connection = resubscribed.connection;
+    assert_eq!(resubscribed.params["channels"], serde_json::json!(["user.orders.any.any.raw"]));
+    let refresh = mock.assert_requested("public/auth");
+    assert_eq!((refresh.connection, refresh.params["refresh_token"].as_str()), (connection, Some(MOCK_REFRESH_TOKEN)));
+    let heartbeat = mock.assert_requested("public/set_heartbeat");
+    assert_eq!((heartbeat.connection, heartbeat.params["interval"].as_u64()), (connection, Some(10)));
+    let cod = mock.assert_requested("private/enable_cancel_on_disconnect");
+    assert_eq!((cod.connection, cod.params["scope"].as_str()), (connection, Some("connection")));
+    assert_eq!(cod.access_token(), Some("refreshed_access_token"));
+
+    // Every clone of the client picks up the rotated tokens
+    assert_eq!(other.access_token(), "refreshed_access_token");
+    assert_eq!(other.auth().response.refresh_token, "refreshed_refresh_token");
+    other.authed_request("private/get_positions", serde_json::json!({ "currency": "BTC" })).await.unwrap();
+    assert_eq!(mock.assert_requested("private/get_positions").access_token(), Some("refreshed_access_token"));
+
+    assert!(mock.notify_connection(connection, "user.orders.any.any.raw", serde_json::json!({ "order_id": "1" })));
+    let update = tokio::time::timeout(Duration::from_secs(1), orders.recv()).await.unwrap().unwrap().unwrap();
+    assert_eq!(update.data["order_id"], "1");
+    mock.test_request();
+    let answered = mock.wait_for("public/test", 1, Duration::from_secs(1)).await.expect("the test request was not answered");
+    assert_eq!(answered.connection, connection);
+  }
+
+  #[tokio::test]
+  async fn missed_heartbeats_drop_the_connection() {
+    let mock = MockDeribit::new();
+    let client = mock.reconnecting_client(fast()).await;
+    client.enable_heartbeat(Duration::from_secs(10)).await.unwrap();
+    let first = mock.assert_requested("public/set_heartbeat").connection;
+    mock.test_request();
+    let answered = mock.wait_for("public/test", 1, Duration::from_secs(1)).await.expect("the test request was not answered");
+    assert_eq!(answered.connection, first);
+
+    // Shorten the interval so the test doesn't wait 20 seconds for the mock's missing heartbeats.
+    // The read loop picks it up with the next message.
+    client.session.lock().unwrap().heartbeat = Some(Duration::from_millis(50));
+    mock.test_request();
+    let restored = mock.wait_for("public/set_heartbeat", 2, Duration::from_secs(1)).await.expect("the silent connection was not dropped");
+    assert_ne!(restored.connection, first);
+  }
+}
@@ -1,9 +1,10 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use tokio::sync::{mpsc, oneshot};
 use serde::{Deserialize};
 
-use crate::core::{ApiError, Error};
+use crate::core::{now_us, ApiError, Error, ListenerId, NotificationSender, Pattern, Router};
 
 /// JSON-RPC response, with either a result or an error.
 #[derive(Debug, Clone, Deserialize)]
@@ -14,10 +15,50 @@ pub struct Response {
   pub result: Option<serde_json::Value>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub error: Option<ApiError>,
-  // pub testnet: bool,
-  // pub usDiff: u64,
-  // pub usIn: u64,
-  // pub usOut: u64,
+  #[serde(default)]
+  pub testnet: Option<bool>,
+  /// Time spent by the server processing the request, in microseconds
+  #[serde(default, rename = "usDiff")]
+  pub us_diff: Option<i64>,
+  /// Server time when the request was received, in microseconds since epoch
+  #[serde(default, rename = "usIn")]
+  pub us_in: Option<i64>,
+  /// Server time when the response was sent, in microseconds since epoch
+  #[serde(default, rename = "usOut")]
+  pub us_out: Option<i64>,
+  /// Timing of the request, set on every reply returned by `SocketClient::request`
+  #[serde(skip)]
+  pub metrics: Option<RequestMetrics>,
+}
+
+/// Client and server timestamps of a request, in microseconds since epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestMetrics {
+  /// Local time just before the request was queued for sending
+  pub sent_us: i64,
+  /// Server time when the request was received
+  pub server_in_us: Option<i64>,
+  /// Server time when the response was sent
+  pub server_out_us: Option<i64>,
+  /// Local time when the response was read from the socket
+  pub received_us: i64,
+}
+
+impl RequestMetrics {
+  /// Total time from sending the request to reading the reply.
+  pub fn round_trip_us(&self) -> i64 {
+    self.received_us - self.sent_us
+  }
+
+  /// Time spent inside the server, including the matching engine.
+  pub fn server_us(&self) -> Option<i64> {
+    Some(self.server_out_us? - self.server_in_us?)
+  }
+
+  /// Time spent on the wire and in local queues, i.e. the round trip minus the server time.
+  pub fn network_us(&self) -> Option<i64> {
+    Some(self.round_trip_us() - self.server_us()?)
+  }
 }
 
 /// JSON-RPC notification
@@ -33,11 +74,35 @@ pub struct NotificationParams {
   pub data: serde_json::Value,
 }
 
+/// Heartbeat sent by the server once `public/set_heartbeat` is enabled
+#[derive(Debug, Clone, Deserialize)]
+pub struct Heartbeat {
+  pub jsonrpc: String,
+  pub method: String,
+  pub params: HeartbeatParams,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HeartbeatParams {
+  #[serde(rename = "type")]
+  pub kind: HeartbeatKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeartbeatKind {
+  /// Informational only.
+  Heartbeat,
+  /// Must be answered with `public/test`, or the server closes the connection.
+  TestRequest,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(untagged)]
 pub enum Message {
   Response(Response),
   Notification(Notification),
+  Heartbeat(Heartbeat),
 }
 
 impl Response {
@@ -52,58 +117,187 @@ impl Response {
   }
 }
 
+/// Resolves a pending request with its reply, or with the reason it will never get one.
+pub type ReplySender = oneshot::Sender<Result<Response, Error>>;
+
 #[derive(Debug, Clone)]
 pub struct ResponseHandler {
-  pub id_counter: u64,
-  pub requests: Arc<Mutex<HashMap<u64, oneshot::Sender<Response>>>>,
-  pub subscriptions: Arc<Mutex<HashMap<String, mpsc::Sender<Notification>>>>,
+  pub id_counter: Arc<AtomicU64>,
+  pub requests: Arc<Mutex<HashMap<u64, ReplySender>>>,
+  pub subscriptions: Arc<Mutex<Router>>,
+  /// Notifications waiting to be delivered by `notification_task`
+  notifications: mpsc::UnboundedSender<Notification>,
+  pending: Arc<Mutex<Option<mpsc::UnboundedReceiver<Notification>>>>,
+  /// Messages that could not be parsed nor matched to a request
+  malformed: Arc<AtomicU64>,
+}
+
+impl Default for ResponseHandler {
+  fn default() -> Self {
+    Self::new()
+  }
 }
 
 impl ResponseHandler {
   pub fn new() -> Self {
+    let (notifications, pending) = mpsc::unbounded_channel();
     ResponseHandler {
-      id_counter: 0,
+      id_counter: Arc::new(AtomicU64::new(0)),
       requests: Arc::new(Mutex::new(HashMap::new())),
-      subscriptions: Arc::new(Mutex::new(HashMap::new())),
+      subscriptions: Arc::new(Mutex::new(Router::new())),
+      notifications,
+      pending: Arc::new(Mutex::new(Some(pending))),
+      malformed: Arc::new(AtomicU64::new(0)),
     }
   }
 
-  pub fn handle(&self, message: &str) {
-    println!("Received message: {}", serde_json::to_string_pretty(&serde_json::from_str::<serde_json::Value>(message).unwrap()).unwrap());
+  /// Task delivering the notifications queued by `handle` to their listeners, in order, until every handle is dropped.
+  /// Waits while a listener with `Backpressure::Block` has no room, which holds back later notifications
+  /// but not replies or heartbeats. Only the first call returns a task.
+  pub fn notification_task(&self) -> Option<impl std::future::Future<Output = ()> + Send + 'static> {
+    let mut pending = self.pending.lock().unwrap().take()?;
+    let subscriptions = self.subscriptions.clone();
+    Some(async move {
+      while let Some(notif) = pending.recv().await {
+        let blocked = subscriptions.lock().unwrap().route(notif);
+        for (sender, notif) in blocked {
+          sender.deliver(notif).await;
+        }
+      }
+    })
+  }
+
+  /// Dispatch a message to the pending request it answers, or queue it for its listeners. Heartbeats are returned
+  /// to the caller, which owns the connection and must answer them. Never waits for slow listeners.
+  /// A reply that can't be parsed fails its request with `Error::Json`; other unreadable messages are counted by `malformed`.
+  pub fn handle(&self, message: &str) -> Option<Heartbeat> {
+    let received_us = now_us();
     match serde_json::from_str::<Message>(message) {
-      Ok(Message::Response(resp)) => {
+      Ok(Message::Response(mut resp)) => {
+        // The send time is filled in by the requester
+        resp.metrics = Some(RequestMetrics { sent_us: 0, server_in_us: resp.us_in, server_out_us: resp.us_out, received_us });
         let mut requests = self.requests.lock().unwrap();
         if let Some(sender) = requests.remove(&resp.id) {
-          let _ = sender.send(resp);
+          let _ = sender.send(Ok(resp));
         }
       }
       Ok(Message::Notification(notif)) => {
-        let mut subscriptions = self.subscriptions.lock().unwrap();
-        if let Some(sender) = subscriptions.get_mut(&notif.params.channel) {
-          let _ = sender.send(notif);
-        }
+        let _ = self.notifications.send(notif);
+      }
+      Ok(Message::Heartbeat(heartbeat)) => {
+        return Some(heartbeat);
       }
       Err(e) => {
-        eprintln!("Failed to parse message: {}", e);
+        let id = serde_json::from_str::<serde_json::Value>(message).ok()
+          .and_then(|value| value.get("id").and_then(|id| id.as_u64()));
+        let pending = id.and_then(|id| self.requests.lock().unwrap().remove(&id));
+        match pending {
+          Some(sender) => { let _ = sender.send(Err(Error::Json(e))); }
+          None => { self.malformed.fetch_add(1, Ordering::Relaxed); }
+        }
       }
     }
+    None
+  }
+
+  /// Number of messages received so far that could be neither parsed nor matched to a pending request.
+  pub fn malformed(&self) -> u64 {
+    self.malformed.load(Ordering::Relaxed)
   }
 
-  pub fn subscribe(&self, channel: String, sender: mpsc::Sender<Notification>) {
+  /// Register a listener for every channel matching `pattern`. Earlier listeners on the same channels keep receiving too.
+  pub fn subscribe(&self, pattern: impl Into<Pattern>, sender: NotificationSender) -> ListenerId {
     let mut subscriptions = self.subscriptions.lock().unwrap();
-    subscriptions.insert(channel, sender);
+    subscriptions.add(pattern.into(), sender)
   }
 
-  pub fn unsubscribe(&self, channel: &str) {
+  pub fn unsubscribe(&self, id: ListenerId) {
     let mut subscriptions = self.subscriptions.lock().unwrap();
-    subscriptions.remove(channel);
+    subscriptions.remove(id);
+  }
+
+  pub fn request(&self, sender: ReplySender) -> u64 {
+    let id = self.id_counter.fetch_add(1, Ordering::Relaxed) + 1;
+    let mut requests = self.requests.lock().unwrap();
+    requests.insert(id, sender);
+    id
+  }
+
+  /// Forget a pending request, e.g. because it could not be sent.
+  pub fn cancel(&self, id: u64) {
+    let mut requests = self.requests.lock().unwrap();
+    requests.remove(&id);
   }
 
-  pub fn request(&mut self, sender: oneshot::Sender<Response>) -> u64 {
-    self.id_counter += 1;
+  /// Fail a pending request, e.g. because it could not be written to the socket.
+  pub fn fail(&self, id: u64, err: Error) {
     let mut requests = self.requests.lock().unwrap();
-    requests.insert(self.id_counter, sender);
-    self.id_counter
+    if let Some(sender) = requests.remove(&id) {
+      let _ = sender.send(Err(err));
+    }
+  }
+
+  /// Fail every pending request with `Error::Disconnected`. Listeners are kept, so they can be restored on reconnect.
+  pub fn disconnect(&self) {
+    let mut requests = self.requests.lock().unwrap();
+    for (_, sender) in requests.drain() {
+      let _ = sender.send(Err(Error::Disconnected));
+    }
+  }
+
+  /// Total number of notifications dropped or conflated by the listeners' backpressure policies.
+  pub fn dropped(&self) -> u64 {
+    self.subscriptions.lock().unwrap().dropped()
+  }
+
+  /// Fail every pending request and drop every listener, which ends their notification streams.
+  pub fn close(&self) {
+    self.disconnect();
+    let mut subscriptions = self.subscriptions.lock().unwrap();
+    subscriptions.clear();
   }
 }
 
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn unreadable_replies_fail_their_request() {
+    let handler = ResponseHandler::new();
+    let (tx, rx) = oneshot::channel();
+    let id = handler.request(tx);
+    let reply = format!(r#"{{"jsonrpc":"2.0","id":{},"result":{{}},"usIn":"soon"}}"#, id);
+    assert!(handler.handle(&reply).is_none());
+    assert!(matches!(rx.await.unwrap(), Err(Error::Json(_))));
+    assert_eq!(handler.malformed(), 0);
+
+    handler.handle(r#"{"jsonrpc":"2.0","method":"subscription","params":{"channel":42}}"#);
+    handler.handle("not json");
+    assert_eq!(handler.malformed(), 2);
+  }
+
+  #[tokio::test]
+  async fn replies_carry_their_server_timing() {
+    let handler = ResponseHandler::new();
+    let (tx, rx) = oneshot::channel();
+    let id = handler.request(tx);
+    let before = now_us();
+    // Shaped like the `public/get_time` example in the Deribit docs
+    let reply = format!(r#"{{"jsonrpc":"2.0","id":{},"result":1550147385946,"usIn":1550147385946088,"usOut":1550147385946179,"usDiff":91,"testnet":false}}"#, id);
+    handler.handle(&reply);
+    let resp = rx.await.unwrap().unwrap();
+    assert_eq!((resp.testnet, resp.us_in, resp.us_out, resp.us_diff), (Some(false), Some(1550147385946088), Some(1550147385946179), Some(91)));
+    let metrics = resp.metrics.unwrap();
+    assert_eq!((metrics.server_in_us, metrics.server_out_us), (Some(1550147385946088), Some(1550147385946179)));
+    assert!((before..=now_us()).contains(&metrics.received_us));
+  }
+
+  #[test]
+  fn metrics_split_the_round_trip() {
+    let metrics = RequestMetrics { sent_us: 1_000, server_in_us: Some(5_000_400), server_out_us: Some(5_000_500), received_us: 1_900 };
+    assert_eq!((metrics.round_trip_us(), metrics.server_us(), metrics.network_us()), (900, Some(100), Some(800)));
+    let untimed = RequestMetrics { server_in_us: None, ..metrics };
+    assert_eq!((untimed.round_trip_us(), untimed.server_us(), untimed.network_us()), (900, None, None));
+  }
+}
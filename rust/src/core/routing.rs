@@ -0,0 +1,250 @@
+use std::collections::{BTreeSet, HashMap};
+use crate::core::{Delivery, Notification, NotificationSender};
+
+/// Which notification channels a listener receives.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Pattern {
+  /// Exactly this channel, e.g. `"ticker.BTC-PERPETUAL.100ms"`
+  Exact(String),
+  /// Every channel starting with this prefix, e.g. `"user."`
+  Prefix(String),
+  /// Channels matching a glob where `*` stands for any run of characters within one dot-separated segment,
+  /// e.g. `"user.orders.*.raw"` matches `"user.orders.BTC-PERPETUAL.raw"` but not `"user.orders.any.any.raw"`
+  Glob(String),
+}
+
+impl Pattern {
+  pub fn exact(channel: &str) -> Self {
+    Pattern::Exact(channel.to_string())
+  }
+
+  pub fn prefix(prefix: &str) -> Self {
+    Pattern::Prefix(prefix.to_string())
+  }
+
+  pub fn glob(glob: &str) -> Self {
+    Pattern::Glob(glob.to_string())
+  }
+
+  pub fn matches(&self, channel: &str) -> bool {
+    match self {
+      Pattern::Exact(exact) => exact == channel,
+      Pattern::Prefix(prefix) => channel.starts_with(prefix.as_str()),
+      Pattern::Glob(glob) => glob_matches(glob.as_bytes(), channel.as_bytes()),
+    }
+  }
+}
+
+/// Strings containing `*` become globs; anything else matches exactly.
+impl From<&str> for Pattern {
+  fn from(pattern: &str) -> Self {
+    if pattern.contains('*') { Pattern::glob(pattern) } else { Pattern::exact(pattern) }
+  }
+}
+
+impl From<String> for Pattern {
+  fn from(pattern: String) -> Self {
+    if pattern.contains('*') { Pattern::Glob(pattern) } else { Pattern::Exact(pattern) }
+  }
+}
+
+impl From<&String> for Pattern {
+  fn from(pattern: &String) -> Self {
+    Pattern::from(pattern.as_str())
+  }
+}
+
+fn glob_matches(glob: &[u8], channel: &[u8]) -> bool {
+  match glob.split_first() {
+    None => channel.is_empty(),
+    Some((b'*', rest)) => {
+      // Try every split point up to the end of the current segment
+      let segment = channel.iter().position(|&c| c == b'.').unwrap_or(channel.len());
+      (0..=segment).any(|i| glob_matches(rest, &channel[i..]))
+    }
+    Some((&c, rest)) => channel.first() == Some(&c) && glob_matches(rest, &channel[1..]),
+  }
+}
+
+/// Identifies a registered listener, to remove it later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ListenerId(u64);
+
+/// Routes notifications to every listener whose pattern matches their channel, and keeps track of the channels
+/// subscribed on the server so they are only unsubscribed once nobody listens to them.
+#[derive(Debug, Default)]
+pub struct Router {
+  next_id: u64,
+  exact: HashMap<String, Vec<(ListenerId, NotificationSender)>>,
+  patterns: Vec<(ListenerId, Pattern, NotificationSender)>,
+  channels: HashMap<String, usize>,
+  dropped: u64,
+}
+
+impl Router {
+  pub fn new() -> Self {
+    Router::default()
+  }
+
+  /// Register a listener. Several listeners may match the same channel; each gets its own copy.
+  pub fn add(&mut self, pattern: Pattern, sender: NotificationSender) -> ListenerId {
+    self.next_id += 1;
+    let id = ListenerId(self.next_id);
+    match pattern {
+      Pattern::Exact(channel) => self.exact.entry(channel).or_default().push((id, sender)),
+      pattern => self.patterns.push((id, pattern, sender)),
+    }
+    id
+  }
+
+  /// Remove a listener. Returns whether it was registered.
+  pub fn remove(&mut self, id: ListenerId) -> bool {
+    let mut removed = false;
+    self.exact.retain(|_, listeners| {
+      let before = listeners.len();
+      listeners.retain(|(listener, _)| *listener != id);
+      removed |= listeners.len() != before;
+      !listeners.is_empty()
+    });
+    let before = self.patterns.len();
+    self.patterns.retain(|(listener, _, _)| *listener != id);
+    removed || self.patterns.len() != before
+  }
+
+  /// Deliver a notification to every matching listener, according to each listener's backpressure policy.
+  /// Listeners whose receiver is gone are removed. Returns the deliveries that must wait for room (`Backpressure::Block`);
+  /// the caller should complete them with `NotificationSender::deliver` once the router is unlocked.
+  pub fn route(&mut self, notif: Notification) -> Vec<(NotificationSender, Notification)> {
+    let channel = notif.params.channel.clone();
+    let mut targets: Vec<(ListenerId, NotificationSender)> = self.exact.get(&channel)
+      .map(|listeners| listeners.to_vec())
+      .unwrap_or_default();
+    targets.extend(self.patterns.iter()
+      .filter(|(_, pattern, _)| pattern.matches(&channel))
+      .map(|(id, _, sender)| (*id, sender.clone())));
+
+    let mut blocked = Vec::new();
+    let mut closed = Vec::new();
+    for (id, sender) in targets {
+      match sender.try_deliver(notif.clone()) {
+        Delivery::Queued => {}
+        Delivery::Dropped => self.dropped += 1,
+        Delivery::Full(notif) => blocked.push((sender, *notif)),
+        Delivery::Closed => closed.push(id),
+      }
+    }
+    for id in closed {
+      self.remove(id);
+    }
+    blocked
+  }
+
+  /// Total number of notifications dropped or conflated across all listeners.
+  pub fn dropped(&self) -> u64 {
+    self.dropped
+  }
+
+  /// Count one more user of a server-side channel subscription.
+  pub fn retain_channel(&mut self, channel: &str) {
+    *self.channels.entry(channel.to_string()).or_default() += 1;
+  }
+
+  /// Count one less user of a server-side channel subscription. Returns `true` if it was the last one,
+  /// i.e. the channel should be unsubscribed on the server.
+  pub fn release_channel(&mut self, channel: &str) -> bool {
+    match self.channels.get_mut(channel) {
+      Some(count) if *count > 1 => {
+        *count -= 1;
+        false
+      }
+      Some(_) => {
+        self.channels.remove(channel);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Channels to subscribe to on the server: those in use by a subscription, plus every exact listener.
+  pub fn channels(&self) -> Vec<String> {
+    let channels: BTreeSet<&String> = self.channels.keys().chain(self.exact.keys()).collect();
+    channels.into_iter().cloned().collect()
+  }
+
+  /// Remove every listener, which ends their notification streams.
+  pub fn clear(&mut self) {
+    self.exact.clear();
+    self.patterns.clear();
+    self.channels.clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use futures_util::FutureExt;
+
+  use super::*;
+  use crate::core::{notification_channel, Backpressure, NotificationReceiver};
+  use crate::core::response::NotificationParams;
+
+  fn notification(channel: &str) -> Notification {
+    Notification {
+      jsonrpc: "2.0".to_string(),
+      params: NotificationParams { channel: channel.to_string(), data: serde_json::json!({}) },
+    }
+  }
+
+  fn listener(router: &mut Router, pattern: &str) -> NotificationReceiver {
+    let (sender, receiver) = notification_channel(16, Backpressure::DropNewest);
+    router.add(Pattern::from(pattern), sender);
+    receiver
+  }
+
+  /// The channels of the notifications queued for a listener.
+  fn received(receiver: &mut NotificationReceiver) -> Vec<String> {
+    let mut channels = Vec::new();
+    while let Some(Some(notif)) = receiver.recv().now_or_never() {
+      channels.push(notif.params.channel);
+    }
+    channels
+  }
+
+  #[test]
+  fn globs_match_within_one_segment() {
+    let glob = Pattern::from("user.orders.*.raw");
+    assert!(matches!(glob, Pattern::Glob(_)));
+    assert!(glob.matches("user.orders.BTC-PERPETUAL.raw"));
+    assert!(!glob.matches("user.orders.any.any.raw"));
+    assert!(!glob.matches("user.orders.BTC-PERPETUAL.100ms"));
+    assert!(Pattern::from("ticker.BTC-*.100ms").matches("ticker.BTC-27DEC24.100ms"));
+    assert!(!Pattern::from("ticker.BTC-*.100ms").matches("ticker.ETH-27DEC24.100ms"));
+    assert!(Pattern::from("book.*.*").matches("book.BTC-PERPETUAL.raw"));
+    assert!(Pattern::prefix("user.").matches("user.portfolio.btc"));
+    assert!(!Pattern::from("user.orders.BTC-PERPETUAL.raw").matches("user.orders.BTC-PERPETUAL.raw.x"));
+  }
+
+  #[test]
+  fn notifications_reach_every_matching_listener() {
+    let mut router = Router::new();
+    let mut exact = listener(&mut router, "user.orders.BTC-PERPETUAL.raw");
+    let mut glob = listener(&mut router, "user.orders.*.raw");
+    let mut other = listener(&mut router, "ticker.*.100ms");
+    for channel in ["user.orders.BTC-PERPETUAL.raw", "user.orders.ETH-PERPETUAL.raw", "user.trades.BTC-PERPETUAL.raw"] {
+      assert!(router.route(notification(channel)).is_empty());
+    }
+    assert_eq!(received(&mut exact), ["user.orders.BTC-PERPETUAL.raw"]);
+    assert_eq!(received(&mut glob), ["user.orders.BTC-PERPETUAL.raw", "user.orders.ETH-PERPETUAL.raw"]);
+    assert!(received(&mut other).is_empty());
+  }
+
+  #[test]
+  fn channels_are_released_by_their_last_user() {
+    let mut router = Router::new();
+    router.retain_channel("ticker.BTC-PERPETUAL.100ms");
+    router.retain_channel("ticker.BTC-PERPETUAL.100ms");
+    assert!(!router.release_channel("ticker.BTC-PERPETUAL.100ms"));
+    assert_eq!(router.channels(), ["ticker.BTC-PERPETUAL.100ms"]);
+    assert!(router.release_channel("ticker.BTC-PERPETUAL.100ms"));
+    assert!(router.channels().is_empty());
+  }
+}
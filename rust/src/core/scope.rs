@@ -1,13 +1,37 @@
 use serde::{Deserialize, Serialize};
 
+use crate::core::Error;
 
-#[derive(Debug, Clone)]
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Access {
   ReadOnly,
   ReadWrite,
   None,
 }
 
+impl Access {
+  pub fn can_read(&self) -> bool {
+    matches!(self, Access::ReadOnly | Access::ReadWrite)
+  }
+
+  pub fn can_write(&self) -> bool {
+    matches!(self, Access::ReadWrite)
+  }
+
+  /// Fails with `Error::Scope(scope)` unless this access allows reading.
+  /// - `scope` - The missing scope to report, e.g. `"block_trade:read"`.
+  pub fn require_read(&self, scope: &'static str) -> Result<(), Error> {
+    if self.can_read() { Ok(()) } else { Err(Error::Scope(scope)) }
+  }
+
+  /// Fails with `Error::Scope(scope)` unless this access allows writing.
+  /// - `scope` - The missing scope to report, e.g. `"wallet:read_write"`.
+  pub fn require_write(&self, scope: &'static str) -> Result<(), Error> {
+    if self.can_write() { Ok(()) } else { Err(Error::Scope(scope)) }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub enum IP {
   Any,
@@ -41,17 +65,17 @@ impl Scope {
     parts.push(format!("session:{}", self.session));
     match self.account {
       Access::ReadOnly => parts.push("account:read".to_string()),
-      Access::ReadWrite => parts.push("account:write".to_string()),
+      Access::ReadWrite => parts.push("account:read_write".to_string()),
       Access::None => {}
     }
     match self.trade {
       Access::ReadOnly => parts.push("trade:read".to_string()),
-      Access::ReadWrite => parts.push("trade:write".to_string()),
+      Access::ReadWrite => parts.push("trade:read_write".to_string()),
       Access::None => {}
     }
     match self.wallet {
       Access::ReadOnly => parts.push("wallet:read".to_string()),
-      Access::ReadWrite => parts.push("wallet:write".to_string()),
+      Access::ReadWrite => parts.push("wallet:read_write".to_string()),
       Access::None => {}
     }
     if let Some(ref expires_in) = self.expires_in {
@@ -64,32 +88,34 @@ impl Scope {
     }
     match self.block_trade {
       Access::ReadOnly => parts.push("block_trade:read".to_string()),
-      Access::ReadWrite => parts.push("block_trade:write".to_string()),
+      Access::ReadWrite => parts.push("block_trade:read_write".to_string()),
       Access::None => {}
     }
     match self.block_rfq {
       Access::ReadOnly => parts.push("block_rfq:read".to_string()),
-      Access::ReadWrite => parts.push("block_rfq:write".to_string()),
+      Access::ReadWrite => parts.push("block_rfq:read_write".to_string()),
       Access::None => {}
     }
 
-    parts.join(",")
+    parts.join(" ")
   }
 
+  /// Parse a scope as returned by `public/auth`, e.g. `"connection mainaccount trade:read_write wallet:read"`.
+  /// Deribit separates the parts with spaces; commas are accepted too.
   pub fn parse(scope_str: &str) -> Self {
     let mut scope = Scope::default();
-    
-    for part in scope_str.split(',') {
+
+    for part in scope_str.split(|c: char| c.is_whitespace() || c == ',').filter(|part| !part.is_empty()) {
       match part {
         "mainaccount" => scope.mainaccount = true,
         "connection" => scope.connection = true,
         s if s.starts_with("session:") => scope.session = s[8..].to_string(),
         "account:read" => scope.account = Access::ReadOnly,
-        "account:write" => scope.account = Access::ReadWrite,
+        "account:read_write" | "account:write" => scope.account = Access::ReadWrite,
         "trade:read" => scope.trade = Access::ReadOnly,
-        "trade:write" => scope.trade = Access::ReadWrite,
+        "trade:read_write" | "trade:write" => scope.trade = Access::ReadWrite,
         "wallet:read" => scope.wallet = Access::ReadOnly,
-        "wallet:write" => scope.wallet = Access::ReadWrite,
+        "wallet:read_write" | "wallet:write" => scope.wallet = Access::ReadWrite,
         s if s.starts_with("expires_in:") => {
           if let Ok(secs) = s.split(":").nth(1).unwrap().parse::<u64>() {
             scope.expires_in = Some(std::time::Duration::from_secs(secs));
@@ -98,9 +124,9 @@ impl Scope {
         "ip:*" => scope.ip = IP::Any,
         s if s.starts_with("ip:") => scope.ip = IP::This(s.split(":").nth(1).unwrap().to_string()),
         "block_trade:read" => scope.block_trade = Access::ReadOnly,
-        "block_trade:write" => scope.block_trade = Access::ReadWrite,
+        "block_trade:read_write" | "block_trade:write" => scope.block_trade = Access::ReadWrite,
         "block_rfq:read" => scope.block_rfq = Access::ReadOnly,
-        "block_rfq:write" => scope.block_rfq = Access::ReadWrite,
+        "block_rfq:read_write" | "block_rfq:write" => scope.block_rfq = Access::ReadWrite,
         _ => {}
       }
     }
@@ -108,7 +134,37 @@ impl Scope {
     scope
   }
 
-  pub fn default() -> Self {
+  pub fn session(&self) -> &str {
+    &self.session
+  }
+
+  pub fn account(&self) -> Access {
+    self.account
+  }
+
+  pub fn trade(&self) -> Access {
+    self.trade
+  }
+
+  pub fn wallet(&self) -> Access {
+    self.wallet
+  }
+
+  pub fn block_trade(&self) -> Access {
+    self.block_trade
+  }
+
+  pub fn block_rfq(&self) -> Access {
+    self.block_rfq
+  }
+
+  pub fn named(name: &str) -> Self {
+    Scope { session: name.to_string(), ..Scope::default() }
+  }
+}
+
+impl Default for Scope {
+  fn default() -> Self {
     Scope {
       mainaccount: false,
       connection: false,
@@ -122,12 +178,6 @@ impl Scope {
       block_rfq: Access::None,
     }
   }
-
-  pub fn named(name: &str) -> Self {
-    let mut scope = Scope::default();
-    scope.session = name.to_string();
-    scope
-  }
 }
 
 impl<'a> Deserialize<'a> for Scope {
@@ -138,8 +188,39 @@ impl<'a> Deserialize<'a> for Scope {
   }
 }
 
-impl<'a> Serialize for Scope {
+impl Serialize for Scope {
   fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
     serializer.serialize_str(&self.dump())
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_the_scope_returned_by_auth() {
+    // Verbatim `result` of the `public/auth` example in the Deribit docs
+    let auth = r#"{"access_token":"1582628593469.1MbQ-J_4.CBP-OqOwm_FBdMYj4cRK2dMXyHPfBtXGpzLxhWg31nHu3H_Q60FpE5_vqUBEQGSiMrIGzw3nC37NMb9d1tpBNqBOM_Ql9pXOmgtV9Yj3Pq1c6BqC6dU6eTxHMFO67x8GpJxqw_QcKP5IepwGBD-gfKSHfAv9AEnLJkNu3JkMJBdLToY1lrBnuedF3dU_uARm","expires_in":31536000,"refresh_token":"1582628593469.1GP4rQd0.A9Wa78o5kFRIUP49mScaD1CqHgiK50HOl2VA6kCtWa8BQZU5Dr03BhcbXPNvEh3I_MVixKZXnyoBeKJwLl8LXnfo180ckAiPj3zOclcUu4zkXuF3NNP3sTPcDf1B3C1CwMKkJ1NOcf1yPmRbsrd7hbgQ-hLa40tfx6Oa-85ymm_3Z65LZcnCeLrqlj_A9jM","scope":"connection mainaccount","token_type":"bearer"}"#;
+    let auth: crate::core::AuthResponse = serde_json::from_str(auth).unwrap();
+    assert!(auth.scope.mainaccount && auth.scope.connection);
+    assert_eq!(auth.scope.session(), "default");
+  }
+
+  #[test]
+  fn parses_space_separated_access() {
+    let scope = Scope::parse("connection mainaccount session:default trade:read_write wallet:read block_trade:read account:read_write expires:31536000");
+    assert_eq!(scope.trade(), Access::ReadWrite);
+    assert_eq!(scope.wallet(), Access::ReadOnly);
+    assert_eq!(scope.account(), Access::ReadWrite);
+    assert_eq!(scope.block_trade(), Access::ReadOnly);
+    assert_eq!(scope.block_rfq(), Access::None);
+  }
+
+  #[test]
+  fn dumps_space_separated_scopes() {
+    let scope = Scope::parse("session:bot,trade:read_write, wallet:read");
+    assert_eq!(scope.dump(), "session:bot trade:read_write wallet:read");
+    assert_eq!(Scope::parse(&scope.dump()).wallet(), Access::ReadOnly);
+  }
+}
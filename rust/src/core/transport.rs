@@ -0,0 +1,61 @@
+use std::future::Future;
+use futures_util::{Sink, Stream};
+use serde::de::DeserializeOwned;
+
+use crate::core::{parse_json, Error, Response, SharedAuth, SocketClient};
+
+/// Carries JSON-RPC requests to Deribit, e.g. over a WebSocket (`SocketClient`) or HTTPS (`HttpClient`).
+/// The typed API and `PrivateClient` are built on top of it, so they work over either one.
+pub trait RpcTransport: Clone + Send + Sync + 'static {
+  /// Send a request and wait for its reply.
+  /// - `method` - The API method to call, e.g. `"public/get_instruments"`
+  /// - `params` - The parameters for the request, as a JSON object.
+  fn request(&self, method: &str, params: serde_json::Value) -> impl Future<Output = Result<Response, Error>> + Send;
+
+  /// Send a request on behalf of the session owning `access_token`. By default the token is sent as the `access_token` parameter.
+  /// - `method` - The API method to call, e.g. `"private/get_positions"`
+  /// - `params` - The parameters for the request, as a JSON object.
+  /// - `access_token` - The session's current access token.
+  fn authed_request(&self, method: &str, params: serde_json::Value, access_token: &str) -> impl Future<Output = Result<Response, Error>> + Send {
+    let mut params = params;
+    params["access_token"] = serde_json::Value::String(access_token.to_string());
+    self.request(method, params)
+  }
+
+  /// Send a request and parse its result into `T`.
+  fn call<T: DeserializeOwned>(&self, method: &str, params: serde_json::Value) -> impl Future<Output = Result<T, Error>> + Send {
+    async move {
+      let resp = self.request(method, params).await?.value()?;
+      parse_json::<T>(resp)
+    }
+  }
+
+  /// Called with the session's credentials whenever it (re-)authenticates, e.g. to restore them after a reconnect.
+  /// Credentials renewed by the transport should be written back through `auth`, so every clone of the client uses them.
+  fn remember_auth(&self, _auth: &SharedAuth) {}
+}
+
+impl RpcTransport for SocketClient {
+  fn request(&self, method: &str, params: serde_json::Value) -> impl Future<Output = Result<Response, Error>> + Send {
+    SocketClient::request(self, method, params)
+  }
+
+  fn remember_auth(&self, auth: &SharedAuth) {
+    SocketClient::remember_auth(self, auth)
+  }
+}
+
+/// Message-level connection underneath a `SocketClient`: anything that reads and writes WebSocket messages,
+/// e.g. a `tokio_tungstenite::WebSocketStream` or an in-memory `crate::mock::MockTransport`.
+pub trait Transport:
+  Stream<Item = Result<tungstenite::Message, tungstenite::Error>>
+  + Sink<tungstenite::Message, Error = tungstenite::Error>
+  + Send + Unpin + 'static
+{}
+
+impl<T> Transport for T
+where
+  T: Stream<Item = Result<tungstenite::Message, tungstenite::Error>>
+    + Sink<tungstenite::Message, Error = tungstenite::Error>
+    + Send + Unpin + 'static
+{}
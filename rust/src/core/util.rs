@@ -1,6 +1,15 @@
+use std::time::{SystemTime, UNIX_EPOCH};
 use crate::core::Error;
 
 pub fn parse_json<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> Result<T, Error> {
   serde_json::from_value(value)
-    .map_err(|e| Error::Json(e))
-}
\ No newline at end of file
+    .map_err(Error::Json)
+}
+
+/// Microseconds since epoch, according to the local clock.
+pub fn now_us() -> i64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_micros() as i64)
+    .unwrap_or_default()
+}
@@ -1,8 +1,41 @@
 pub mod core;
 pub mod subscriptions;
+pub mod channels;
+pub mod types;
+pub mod market_data;
+pub mod order_book;
+pub mod trading;
+pub mod orders;
+pub mod account;
+pub mod wallet;
+pub mod combo;
+pub mod block_trade;
+pub mod block_rfq;
+pub mod session;
+pub mod supporting;
+pub mod mock;
+pub mod paper;
 
 pub use core::SocketClient;
+pub use core::{HttpClient, RpcTransport};
 pub use core::PrivateClient;
 pub use core::TESTNET;
 pub use core::MAINNET;
+pub use core::{TESTNET_HTTP, MAINNET_HTTP};
 pub use core::Scope;
+pub use core::Reconnect;
+pub use core::ConnectionState;
+pub use core::DEFAULT_TIMEOUT;
+pub use core::Pattern;
+pub use core::Backpressure;
+pub use core::DeribitErrorCode;
+pub use core::{RateLimiter, RateLimitPolicy};
+pub use core::RequestMetrics;
+pub use subscriptions::{Subscription, Update};
+pub use channels::{Channel, ChannelData, Interval, Target};
+pub use market_data::MarketDataApi;
+pub use supporting::SupportingApi;
+pub use types::{Currency, Direction, Kind, OptionType, SubaccountId};
+pub use order_book::{OrderBook, LocalOrderBook, BookSync, OrderBookApi};
+pub use trading::{OrderRequest, EditRequest, Order, Trade, OrderResponse};
+pub use combo::{ComboBuilder, ComboApi};
\ No newline at end of file
@@ -5,15 +5,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
   let client_id = std::env::var("DERIBIT_CLIENT_ID").expect("DERIBIT_CLIENT_ID must be set");
   let client_secret = std::env::var("DERIBIT_CLIENT_SECRET").expect("DERIBIT_CLIENT_SECRET must be set");
 
-  let mut client = deribit::PrivateClient::start_debug(
+  let client = deribit::PrivateClient::start(
     deribit::TESTNET,
     &client_id, &client_secret,
-    deribit::LogLevel::Debug,
+    deribit::Scope::default(),
   ).await?;
   let start = std::time::Instant::now();
 
   // let params = serde_json::json!({
-  //   "refresh_token": client.auth.response.refresh_token,
+  //   "refresh_token": client.auth().response.refresh_token,
   //   "subject_id": ,
   // });
   // for _ in 0..1 {
@@ -21,7 +21,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
   //   println!("Response: {:?}", r);
   // }
 
-  let r = client.exchange_token(69914).await?;
+  let _auth = client.exchange_token(69914, None).await?;
 
   let duration = start.elapsed();
   println!("Total time: {:?}", duration);
@@ -0,0 +1,441 @@
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{Error, RpcTransport};
+use crate::types::{Currency, Kind, OptionType};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Instrument {
+  pub instrument_name: String,
+  pub instrument_id: i64,
+  pub kind: Kind,
+  pub base_currency: Currency,
+  pub quote_currency: Currency,
+  #[serde(default)]
+  pub counter_currency: Option<Currency>,
+  #[serde(default)]
+  pub settlement_currency: Option<Currency>,
+  #[serde(default)]
+  pub settlement_period: Option<String>,
+  pub contract_size: f64,
+  pub tick_size: f64,
+  pub min_trade_amount: f64,
+  pub is_active: bool,
+  pub creation_timestamp: i64,
+  pub expiration_timestamp: i64,
+  #[serde(default)]
+  pub option_type: Option<OptionType>,
+  #[serde(default)]
+  pub strike: Option<f64>,
+  pub maker_commission: f64,
+  pub taker_commission: f64,
+  #[serde(default)]
+  pub block_trade_commission: Option<f64>,
+  #[serde(default)]
+  pub max_leverage: Option<f64>,
+  #[serde(default)]
+  pub price_index: Option<String>,
+  #[serde(default)]
+  pub rfq: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WithdrawalPriority {
+  pub name: String,
+  pub value: f64,
+}
+
+/// Currency details, as returned by `get_currencies`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrencyInfo {
+  pub currency: Currency,
+  pub currency_long: String,
+  pub coin_type: String,
+  #[serde(default)]
+  pub fee_precision: Option<i64>,
+  #[serde(default)]
+  pub min_confirmations: Option<i64>,
+  #[serde(default)]
+  pub min_withdrawal_fee: Option<f64>,
+  pub withdrawal_fee: f64,
+  #[serde(default)]
+  pub withdrawal_priorities: Vec<WithdrawalPriority>,
+  #[serde(default)]
+  pub apr: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexPrice {
+  pub index_price: f64,
+  pub estimated_delivery_price: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookSummary {
+  pub instrument_name: String,
+  pub base_currency: Currency,
+  pub quote_currency: Currency,
+  pub creation_timestamp: i64,
+  pub volume: f64,
+  #[serde(default)]
+  pub volume_usd: Option<f64>,
+  #[serde(default)]
+  pub volume_notional: Option<f64>,
+  pub open_interest: f64,
+  #[serde(default)]
+  pub mark_price: Option<f64>,
+  #[serde(default)]
+  pub mid_price: Option<f64>,
+  #[serde(default)]
+  pub bid_price: Option<f64>,
+  #[serde(default)]
+  pub ask_price: Option<f64>,
+  #[serde(default)]
+  pub last: Option<f64>,
+  #[serde(default)]
+  pub low: Option<f64>,
+  #[serde(default)]
+  pub high: Option<f64>,
+  #[serde(default)]
+  pub price_change: Option<f64>,
+  #[serde(default)]
+  pub estimated_delivery_price: Option<f64>,
+  #[serde(default)]
+  pub underlying_price: Option<f64>,
+  #[serde(default)]
+  pub underlying_index: Option<String>,
+  #[serde(default)]
+  pub interest_rate: Option<f64>,
+  #[serde(default)]
+  pub funding_8h: Option<f64>,
+  #[serde(default)]
+  pub current_funding: Option<f64>,
+  #[serde(default)]
+  pub mark_iv: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TickerStats {
+  #[serde(default)]
+  pub volume: Option<f64>,
+  #[serde(default)]
+  pub volume_usd: Option<f64>,
+  #[serde(default)]
+  pub price_change: Option<f64>,
+  #[serde(default)]
+  pub low: Option<f64>,
+  #[serde(default)]
+  pub high: Option<f64>,
+}
+
+/// Option greeks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Greeks {
+  pub delta: f64,
+  pub gamma: f64,
+  pub rho: f64,
+  pub theta: f64,
+  pub vega: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ticker {
+  pub instrument_name: String,
+  pub timestamp: i64,
+  pub state: String,
+  #[serde(default)]
+  pub last_price: Option<f64>,
+  pub mark_price: f64,
+  pub index_price: f64,
+  #[serde(default)]
+  pub best_bid_price: Option<f64>,
+  pub best_bid_amount: f64,
+  #[serde(default)]
+  pub best_ask_price: Option<f64>,
+  pub best_ask_amount: f64,
+  #[serde(default)]
+  pub min_price: Option<f64>,
+  #[serde(default)]
+  pub max_price: Option<f64>,
+  #[serde(default)]
+  pub open_interest: Option<f64>,
+  #[serde(default)]
+  pub settlement_price: Option<f64>,
+  #[serde(default)]
+  pub delivery_price: Option<f64>,
+  #[serde(default)]
+  pub estimated_delivery_price: Option<f64>,
+  #[serde(default)]
+  pub interest_value: Option<f64>,
+  #[serde(default)]
+  pub funding_8h: Option<f64>,
+  #[serde(default)]
+  pub current_funding: Option<f64>,
+  pub stats: TickerStats,
+  #[serde(default)]
+  pub greeks: Option<Greeks>,
+  #[serde(default)]
+  pub mark_iv: Option<f64>,
+  #[serde(default)]
+  pub bid_iv: Option<f64>,
+  #[serde(default)]
+  pub ask_iv: Option<f64>,
+  #[serde(default)]
+  pub underlying_price: Option<f64>,
+  #[serde(default)]
+  pub underlying_index: Option<String>,
+  #[serde(default)]
+  pub interest_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ContractSize {
+  contract_size: f64,
+}
+
+/// Market data endpoints, available on any transport, e.g. `SocketClient` or `HttpClient`.
+pub trait MarketDataApi: RpcTransport {
+  /// Retrieves available trading instruments.
+  /// - `currency` - The currency of the instruments, or `Currency::Any`.
+  /// - `kind` - Only return instruments of this kind, if given.
+  /// - `expired` - Return recently expired instruments instead of active ones.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_instruments)
+  fn get_instruments(&self, currency: Currency, kind: Option<Kind>, expired: bool) -> impl Future<Output = Result<Vec<Instrument>, Error>> + Send {
+    async move {
+      let mut params = serde_json::json!({
+        "currency": currency,
+        "expired": expired,
+      });
+      if let Some(kind) = kind {
+        params["kind"] = serde_json::to_value(kind)?;
+      }
+      self.call("public/get_instruments", params).await
+    }
+  }
+
+  /// Retrieves a single instrument by name.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_instrument)
+  fn get_instrument(&self, instrument_name: &str) -> impl Future<Output = Result<Instrument, Error>> + Send {
+    let params = serde_json::json!({ "instrument_name": instrument_name });
+    self.call("public/get_instrument", params)
+  }
+
+  /// Retrieves all cryptocurrencies supported by the API.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_currencies)
+  fn get_currencies(&self) -> impl Future<Output = Result<Vec<CurrencyInfo>, Error>> + Send {
+    self.call("public/get_currencies", serde_json::json!({}))
+  }
+
+  /// Retrieves the current index price and estimated delivery price.
+  /// - `index_name` - e.g. `"btc_usd"`; see `get_index_price_names`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_index_price)
+  fn get_index_price(&self, index_name: &str) -> impl Future<Output = Result<IndexPrice, Error>> + Send {
+    let params = serde_json::json!({ "index_name": index_name });
+    self.call("public/get_index_price", params)
+  }
+
+  /// Retrieves the identifiers of all supported price indexes.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_index_price_names)
+  fn get_index_price_names(&self) -> impl Future<Output = Result<Vec<String>, Error>> + Send {
+    self.call("public/get_index_price_names", serde_json::json!({}))
+  }
+
+  /// Retrieves the summary information of every instrument for a currency.
+  /// - `currency` - The currency of the instruments.
+  /// - `kind` - Only return instruments of this kind, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_book_summary_by_currency)
+  fn get_book_summary_by_currency(&self, currency: Currency, kind: Option<Kind>) -> impl Future<Output = Result<Vec<BookSummary>, Error>> + Send {
+    async move {
+      let mut params = serde_json::json!({ "currency": currency });
+      if let Some(kind) = kind {
+        params["kind"] = serde_json::to_value(kind)?;
+      }
+      self.call("public/get_book_summary_by_currency", params).await
+    }
+  }
+
+  /// Retrieves the summary information of a single instrument.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_book_summary_by_instrument)
+  fn get_book_summary_by_instrument(&self, instrument_name: &str) -> impl Future<Output = Result<Vec<BookSummary>, Error>> + Send {
+    let params = serde_json::json!({ "instrument_name": instrument_name });
+    self.call("public/get_book_summary_by_instrument", params)
+  }
+
+  /// Retrieves the contract size of an instrument.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_contract_size)
+  fn get_contract_size(&self, instrument_name: &str) -> impl Future<Output = Result<f64, Error>> + Send {
+    async move {
+      let params = serde_json::json!({ "instrument_name": instrument_name });
+      let size = self.call::<ContractSize>("public/get_contract_size", params).await?;
+      Ok(size.contract_size)
+    }
+  }
+
+  /// Retrieves the ticker (best prices, mark price, stats) of an instrument.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-ticker)
+  fn ticker(&self, instrument_name: &str) -> impl Future<Output = Result<Ticker, Error>> + Send {
+    let params = serde_json::json!({ "instrument_name": instrument_name });
+    self.call("public/ticker", params)
+  }
+}
+
+impl<C: RpcTransport> MarketDataApi for C {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::MockDeribit;
+
+  fn instrument(instrument_name: &str) -> serde_json::Value {
+    serde_json::json!({
+      "tick_size": 2.5,
+      "taker_commission": 0.0005,
+      "settlement_period": "month",
+      "settlement_currency": "BTC",
+      "quote_currency": "USD",
+      "min_trade_amount": 10,
+      "maker_commission": 0.0001,
+      "kind": "future",
+      "is_active": true,
+      "instrument_name": instrument_name,
+      "instrument_id": 138583,
+      "expiration_timestamp": 1656057600000i64,
+      "creation_timestamp": 1646380800000i64,
+      "counter_currency": "USD",
+      "contract_size": 10,
+      "block_trade_commission": 0.0001,
+      "base_currency": "BTC",
+    })
+  }
+
+  fn book_summary(instrument_name: &str) -> serde_json::Value {
+    serde_json::json!({
+      "volume": 0.55,
+      "underlying_price": 121.38,
+      "underlying_index": "index_price",
+      "quote_currency": "USD",
+      "price_change": -26.7793594,
+      "open_interest": 0.55,
+      "mid_price": 0.2444,
+      "mark_price": 80,
+      "low": 0.34,
+      "last": 0.34,
+      "interest_rate": 0.207,
+      "instrument_name": instrument_name,
+      "high": 0.34,
+      "creation_timestamp": 1550227952163i64,
+      "bid_price": 0.2,
+      "base_currency": "BTC",
+      "ask_price": 0.2888,
+    })
+  }
+
+  #[tokio::test]
+  async fn instruments_are_requested_by_currency_and_kind() {
+    let mock = MockDeribit::new();
+    mock.respond("public/get_instruments", serde_json::json!([instrument("BTC-24JUN22")]));
+    mock.respond("public/get_instrument", instrument("BTC-24JUN22"));
+    let client = mock.client();
+    // Spawned to check that the futures are `Send`
+    let instruments = tokio::spawn({
+      let client = client.clone();
+      async move { client.get_instruments(Currency::BTC, Some(Kind::Future), false).await }
+    }).await.unwrap().unwrap();
+    assert_eq!((instruments[0].instrument_name.as_str(), instruments[0].contract_size), ("BTC-24JUN22", 10.0));
+    assert_eq!(mock.assert_requested("public/get_instruments").params, serde_json::json!({ "currency": "BTC", "kind": "future", "expired": false }));
+
+    let instrument = client.get_instrument("BTC-24JUN22").await.unwrap();
+    assert_eq!((instrument.kind, instrument.settlement_currency), (Kind::Future, Some(Currency::BTC)));
+    assert_eq!(mock.assert_requested("public/get_instrument").params, serde_json::json!({ "instrument_name": "BTC-24JUN22" }));
+  }
+
+  #[tokio::test]
+  async fn currencies_and_indexes_are_parsed() {
+    let mock = MockDeribit::new();
+    mock.respond("public/get_currencies", serde_json::json!([{
+      "withdrawal_priorities": [{ "value": 0.15, "name": "very_low" }, { "value": 1.5, "name": "very_high" }],
+      "withdrawal_fee": 0.0001,
+      "min_withdrawal_fee": 0.0001,
+      "min_confirmations": 1,
+      "fee_precision": 4,
+      "currency_long": "Bitcoin",
+      "currency": "BTC",
+      "coin_type": "BITCOIN",
+    }]));
+    mock.respond("public/get_index_price", serde_json::json!({ "estimated_delivery_price": 11628.81, "index_price": 11628.81 }));
+    mock.respond("public/get_index_price_names", serde_json::json!(["btc_usd", "eth_usd", "btc_usdc"]));
+    let client = mock.client();
+
+    let currencies = client.get_currencies().await.unwrap();
+    assert_eq!(currencies[0].currency, Currency::BTC);
+    assert_eq!(currencies[0].withdrawal_priorities.len(), 2);
+    let index = client.get_index_price("btc_usd").await.unwrap();
+    assert_eq!(index.index_price, 11628.81);
+    assert_eq!(mock.assert_requested("public/get_index_price").params["index_name"], "btc_usd");
+    assert_eq!(client.get_index_price_names().await.unwrap(), ["btc_usd", "eth_usd", "btc_usdc"]);
+  }
+
+  #[tokio::test]
+  async fn book_summaries_are_requested_by_currency_or_instrument() {
+    let mock = MockDeribit::new();
+    mock.respond("public/get_book_summary_by_currency", serde_json::json!([book_summary("BTC-22FEB19-3500-P")]));
+    mock.respond("public/get_book_summary_by_instrument", serde_json::json!([book_summary("ETH-22FEB19-140-P")]));
+    let client = mock.client();
+
+    let summaries = client.get_book_summary_by_currency(Currency::BTC, Some(Kind::Option)).await.unwrap();
+    assert_eq!((summaries[0].instrument_name.as_str(), summaries[0].mark_price), ("BTC-22FEB19-3500-P", Some(80.0)));
+    assert_eq!(mock.assert_requested("public/get_book_summary_by_currency").params, serde_json::json!({ "currency": "BTC", "kind": "option" }));
+    let summaries = client.get_book_summary_by_instrument("ETH-22FEB19-140-P").await.unwrap();
+    assert_eq!(summaries[0].open_interest, 0.55);
+    assert_eq!(mock.assert_requested("public/get_book_summary_by_instrument").params["instrument_name"], "ETH-22FEB19-140-P");
+  }
+
+  #[tokio::test]
+  async fn contract_size_and_ticker_are_parsed() {
+    let mock = MockDeribit::new();
+    mock.respond("public/get_contract_size", serde_json::json!({ "contract_size": 10 }));
+    mock.respond("public/ticker", serde_json::json!({
+      "timestamp": 1623060194301i64,
+      "stats": { "volume_usd": 284061480.0, "volume": 7871.02139035, "price_change": 0.7229, "low": 35213.5, "high": 36824.5 },
+      "state": "open",
+      "settlement_price": 36169.49,
+      "open_interest": 502097590,
+      "min_price": 35898.37,
+      "max_price": 36991.72,
+      "mark_price": 36446.51,
+      "last_price": 36457.5,
+      "interest_value": 1.7362511643080387,
+      "instrument_name": "BTC-PERPETUAL",
+      "index_price": 36441.64,
+      "funding_8h": 0.0000211,
+      "estimated_delivery_price": 36441.64,
+      "current_funding": 0,
+      "best_bid_price": 36442.5,
+      "best_bid_amount": 5000,
+      "best_ask_price": 36443,
+      "best_ask_amount": 100,
+    }));
+    let client = mock.client();
+
+    assert_eq!(client.get_contract_size("BTC-PERPETUAL").await.unwrap(), 10.0);
+    assert_eq!(mock.assert_requested("public/get_contract_size").params["instrument_name"], "BTC-PERPETUAL");
+    let ticker = client.ticker("BTC-PERPETUAL").await.unwrap();
+    assert_eq!((ticker.best_bid_price, ticker.best_ask_price), (Some(36442.5), Some(36443.0)));
+    assert_eq!((ticker.stats.high, ticker.greeks.is_none()), (Some(36824.5), true));
+    assert_eq!(mock.assert_requested("public/ticker").params["instrument_name"], "BTC-PERPETUAL");
+  }
+}
@@ -0,0 +1,428 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use futures_util::{Sink, Stream};
+use serde::Deserialize;
+use tokio::sync::{mpsc, Notify};
+use tungstenite::Message;
+
+use crate::core::{now_us, ApiError, DeribitErrorCode, Error, PrivateClient, Reconnect, Scope, SocketClient};
+
+/// A request received by `MockDeribit`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockRequest {
+  pub id: u64,
+  pub method: String,
+  #[serde(default)]
+  pub params: serde_json::Value,
+  /// The connection the request arrived on, as passed to `notify_connection`
+  #[serde(skip)]
+  pub connection: u64,
+}
+
+impl MockRequest {
+  /// The access token the request was sent with, for private methods.
+  pub fn access_token(&self) -> Option<&str> {
+    self.params.get("access_token").and_then(|token| token.as_str())
+  }
+}
+
+/// Computes the reply to a request: its `result`, or a JSON-RPC error.
+pub type MockHandler = Arc<dyn Fn(&MockRequest) -> Result<serde_json::Value, ApiError> + Send + Sync>;
+
+/// Access token handed out by the default `public/auth` handler.
+pub const MOCK_ACCESS_TOKEN: &str = "mock_access_token";
+/// Refresh token handed out by the default `public/auth` handler.
+pub const MOCK_REFRESH_TOKEN: &str = "mock_refresh_token";
+/// Scope granted by the default `public/auth` handler: read and write access to everything.
+pub const MOCK_SCOPE: &str = "connection mainaccount session:default account:read_write trade:read_write wallet:read_write block_trade:read_write block_rfq:read_write";
+
+#[derive(Default)]
+struct State {
+  handlers: HashMap<String, MockHandler>,
+  scripted: HashMap<String, VecDeque<Result<serde_json::Value, ApiError>>>,
+  ignored: HashSet<String>,
+  server_times: Option<(i64, i64)>,
+  requests: Vec<MockRequest>,
+  next_connection: u64,
+  connections: Vec<(u64, mpsc::UnboundedSender<Message>)>,
+}
+
+/// In-process stand-in for the Deribit WebSocket API, to test code built on `SocketClient` or `PrivateClient`
+/// without a network connection.
+///
+/// Replies come from, in order: one-shot scripted replies (`respond_once`, `fail_once`), handlers (`on`, `respond`, `fail`),
+/// and built-in handlers for authentication, subscriptions, heartbeats and `public/test`. Any other method fails
+/// with JSON-RPC error -32601, and methods passed to `ignore` get no reply at all. Every request is recorded for later assertions.
+///
+/// ```ignore
+/// let mock = MockDeribit::new();
+/// mock.respond("public/get_time", serde_json::json!(1700000000000i64));
+/// let client = SocketClient::start(mock.connect());
+/// assert_eq!(client.get_time().await?, 1700000000000);
+/// mock.notify("ticker.BTC-PERPETUAL.100ms", serde_json::json!({ ... }));
+/// mock.assert_requested("public/get_time");
+/// ```
+#[derive(Clone, Default)]
+pub struct MockDeribit {
+  state: Arc<Mutex<State>>,
+  received: Arc<Notify>,
+}
+
+impl MockDeribit {
+  pub fn new() -> Self {
+    MockDeribit::default()
+  }
+
+  /// Open a new connection to the mock, to pass to `SocketClient::start`. Must be called within a Tokio runtime.
+  pub fn connect(&self) -> MockTransport {
+    let (to_server, from_client) = mpsc::unbounded_channel();
+    let (to_client, from_server) = mpsc::unbounded_channel();
+    let id = {
+      let mut state = self.state.lock().unwrap();
+      state.next_connection += 1;
+      let id = state.next_connection;
+      state.connections.push((id, to_client));
+      id
+    };
+    tokio::spawn(self.clone().serve(id, from_client));
+    MockTransport { outgoing: Some(to_server), incoming: from_server }
+  }
+
+  /// A public client connected to the mock, without client-side rate limiting.
+  pub fn client(&self) -> SocketClient {
+    let client = SocketClient::start(self.connect());
+    client.set_rate_limiter(None);
+    client
+  }
+
+  /// A public client connected to the mock that opens a new connection to it whenever the current one drops,
+  /// e.g. after `disconnect`, without client-side rate limiting.
+  /// - `reconnect` - The reconnection policy, e.g. with a short `initial_delay` to keep tests fast.
+  pub async fn reconnecting_client(&self, reconnect: Reconnect) -> SocketClient {
+    let mock = self.clone();
+    let client = SocketClient::connect_via(move || std::future::ready(Ok(mock.connect())), reconnect).await
+      .expect("connecting to the mock can't fail");
+    client.set_rate_limiter(None);
+    client
+  }
+
+  /// A private client connected to the mock, authenticated through `public/auth`, without client-side rate limiting.
+  pub async fn private_client(&self) -> Result<PrivateClient, Error> {
+    self.client().authenticated("mock_client_id", "mock_client_secret", Scope::default()).await
+  }
+
+  /// Handle every request to `method` with `handler`, replacing any previous handler.
+  /// - `method` - e.g. `"public/get_instruments"`
+  /// - `handler` - Computes the reply from the request.
+  pub fn on<F>(&self, method: &str, handler: F)
+  where
+    F: Fn(&MockRequest) -> Result<serde_json::Value, ApiError> + Send + Sync + 'static,
+  {
+    self.state.lock().unwrap().handlers.insert(method.to_string(), Arc::new(handler));
+  }
+
+  /// Reply to every request to `method` with `result`.
+  pub fn respond(&self, method: &str, result: serde_json::Value) {
+    self.on(method, move |_| Ok(result.clone()));
+  }
+
+  /// Fail every request to `method` with the given error code.
+  pub fn fail(&self, method: &str, code: DeribitErrorCode) {
+    let error = api_error(code);
+    self.on(method, move |_| Err(error.clone()));
+  }
+
+  /// Reply to the next request to `method` with `result`, before falling back to its handler.
+  /// Several scripted replies are used in the order they were added.
+  pub fn respond_once(&self, method: &str, result: serde_json::Value) {
+    self.script(method, Ok(result));
+  }
+
+  /// Fail the next request to `method` with the given error code, before falling back to its handler.
+  pub fn fail_once(&self, method: &str, code: DeribitErrorCode) {
+    self.script(method, Err(api_error(code)));
+  }
+
+  /// Record requests to `method` but never reply to them, e.g. to test timeouts.
+  pub fn ignore(&self, method: &str) {
+    self.state.lock().unwrap().ignored.insert(method.to_string());
+  }
+
+  /// Stamp every reply with these `usIn` and `usOut` instead of the mock's clock, e.g. to test latency or clock offset calculations.
+  /// - `us_in` - Server time the requests were received at, in microseconds since epoch.
+  /// - `us_out` - Server time the replies were sent at, in microseconds since epoch.
+  pub fn server_times(&self, us_in: i64, us_out: i64) {
+    self.state.lock().unwrap().server_times = Some((us_in, us_out));
+  }
+
+  fn script(&self, method: &str, reply: Result<serde_json::Value, ApiError>) {
+    self.state.lock().unwrap().scripted.entry(method.to_string()).or_default().push_back(reply);
+  }
+
+  /// Send a notification to every open connection.
+  /// - `channel` - e.g. `"user.orders.BTC-PERPETUAL.raw"`
+  /// - `data` - The notification payload.
+  pub fn notify(&self, channel: &str, data: serde_json::Value) {
+    self.broadcast(notification(channel, data));
+  }
+
+  /// Send a notification to one connection, e.g. the one a subscription request arrived on.
+  /// Returns false if the connection is closed.
+  /// - `connection` - The `MockRequest::connection` of a request received on it.
+  /// - `channel` - e.g. `"user.orders.BTC-PERPETUAL.raw"`
+  /// - `data` - The notification payload.
+  pub fn notify_connection(&self, connection: u64, channel: &str, data: serde_json::Value) -> bool {
+    self.send_to(connection, notification(channel, data))
+  }
+
+  /// Send a heartbeat test request to every open connection, which clients must answer with `public/test`.
+  pub fn test_request(&self) {
+    let heartbeat = serde_json::json!({
+      "jsonrpc": "2.0",
+      "method": "heartbeat",
+      "params": { "type": "test_request" },
+    });
+    self.broadcast(Message::Text(heartbeat.to_string()));
+  }
+
+  /// Send a message to one connection. Returns false if it is gone.
+  fn send_to(&self, connection: u64, message: Message) -> bool {
+    let state = self.state.lock().unwrap();
+    state.connections.iter().any(|(id, conn)| *id == connection && conn.send(message.clone()).is_ok())
+  }
+
+  fn broadcast(&self, message: Message) {
+    let mut state = self.state.lock().unwrap();
+    state.connections.retain(|(_, conn)| conn.send(message.clone()).is_ok());
+  }
+
+  /// Close every open connection, as if the network dropped.
+  pub fn disconnect(&self) {
+    self.state.lock().unwrap().connections.clear();
+  }
+
+  /// Every request received so far, in order.
+  pub fn requests(&self) -> Vec<MockRequest> {
+    self.state.lock().unwrap().requests.clone()
+  }
+
+  /// Every request to `method` received so far, in order.
+  pub fn requests_to(&self, method: &str) -> Vec<MockRequest> {
+    self.state.lock().unwrap().requests.iter().filter(|req| req.method == method).cloned().collect()
+  }
+
+  /// Forget the requests received so far.
+  pub fn clear_requests(&self) {
+    self.state.lock().unwrap().requests.clear();
+  }
+
+  /// The last request to `method`. Panics if there was none.
+  pub fn assert_requested(&self, method: &str) -> MockRequest {
+    let state = self.state.lock().unwrap();
+    match state.requests.iter().rev().find(|req| req.method == method) {
+      Some(req) => req.clone(),
+      None => {
+        let received: Vec<&str> = state.requests.iter().map(|req| req.method.as_str()).collect();
+        panic!("Expected a request to {}, received {:?}", method, received);
+      }
+    }
+  }
+
+  /// Panics if a request to `method` was received.
+  pub fn assert_not_requested(&self, method: &str) {
+    let count = self.requests_to(method).len();
+    assert!(count == 0, "Expected no request to {}, received {}", method, count);
+  }
+
+  /// Wait until `count` requests to `method` have been received in total, e.g. for requests sent from another task.
+  /// Returns the `count`th one, or `None` if it doesn't arrive within `timeout`.
+  pub async fn wait_for(&self, method: &str, count: usize, timeout: Duration) -> Option<MockRequest> {
+    let wait = async {
+      loop {
+        let received = self.received.notified();
+        if let Some(req) = self.requests_to(method).into_iter().nth(count.max(1) - 1) {
+          return req;
+        }
+        received.await;
+      }
+    };
+    tokio::time::timeout(timeout, wait).await.ok()
+  }
+
+  /// Answer one connection's requests until it closes.
+  async fn serve(self, connection: u64, mut from_client: mpsc::UnboundedReceiver<Message>) {
+    while let Some(message) = from_client.recv().await {
+      let text = match message {
+        Message::Text(text) => text,
+        Message::Close(_) => break,
+        _ => continue,
+      };
+      let mut req = match serde_json::from_str::<MockRequest>(&text) {
+        Ok(req) => req,
+        Err(_) => {
+          // Like the real server, answer requests whose ID can be read with a JSON-RPC error, and ignore the rest
+          let id = serde_json::from_str::<serde_json::Value>(&text).ok().and_then(|value| value.get("id").and_then(|id| id.as_u64()));
+          let Some(id) = id else { continue };
+          let error = ApiError { code: -32600, message: "Invalid Request".to_string(), data: None };
+          let resp = serde_json::json!({ "jsonrpc": "2.0", "id": id, "error": error, "testnet": true });
+          if !self.send_to(connection, Message::Text(resp.to_string())) {
+            break;
+          }
+          continue;
+        }
+      };
+      req.connection = connection;
+      let us_in = now_us();
+      let (to_client, ignored, server_times) = {
+        let mut state = self.state.lock().unwrap();
+        // The connection is gone once `disconnect` drops its sender
+        let Some((_, to_client)) = state.connections.iter().find(|(id, _)| *id == connection) else { break };
+        let to_client = to_client.clone();
+        state.requests.push(req.clone());
+        (to_client, state.ignored.contains(&req.method), state.server_times)
+      };
+      self.received.notify_waiters();
+      if ignored {
+        continue;
+      }
+      let reply = self.reply(&req);
+      let (us_in, us_out) = server_times.unwrap_or((us_in, now_us()));
+      let mut resp = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": req.id,
+        "testnet": true,
+        "usIn": us_in,
+        "usOut": us_out,
+        "usDiff": us_out - us_in,
+      });
+      match reply {
+        Ok(result) => resp["result"] = result,
+        Err(error) => resp["error"] = serde_json::to_value(error).unwrap_or_default(),
+      }
+      if to_client.send(Message::Text(resp.to_string())).is_err() {
+        break;
+      }
+    }
+  }
+
+  fn reply(&self, req: &MockRequest) -> Result<serde_json::Value, ApiError> {
+    // Release the lock before calling the handler, which may use the mock itself, e.g. to send notifications
+    let handler = {
+      let mut state = self.state.lock().unwrap();
+      if let Some(reply) = state.scripted.get_mut(&req.method).and_then(|replies| replies.pop_front()) {
+        return reply;
+      }
+      state.handlers.get(&req.method).cloned()
+    };
+    match handler {
+      Some(handler) => handler(req),
+      None => builtin(req),
+    }
+  }
+}
+
+fn notification(channel: &str, data: serde_json::Value) -> Message {
+  let notification = serde_json::json!({
+    "jsonrpc": "2.0",
+    "method": "subscription",
+    "params": { "channel": channel, "data": data },
+  });
+  Message::Text(notification.to_string())
+}
+
+pub(crate) fn api_error(code: DeribitErrorCode) -> ApiError {
+  ApiError { code: code.code(), message: code.as_str().to_string(), data: None }
+}
+
+/// Default replies for session-level methods, so clients can authenticate and subscribe out of the box.
+fn builtin(req: &MockRequest) -> Result<serde_json::Value, ApiError> {
+  match req.method.as_str() {
+    "public/auth" | "public/exchange_token" | "public/fork_token" => Ok(serde_json::json!({
+      "access_token": MOCK_ACCESS_TOKEN,
+      "refresh_token": MOCK_REFRESH_TOKEN,
+      "expires_in": 31536000,
+      "scope": MOCK_SCOPE,
+      "token_type": "bearer",
+    })),
+    "public/subscribe" | "private/subscribe" | "public/unsubscribe" | "private/unsubscribe" => {
+      Ok(req.params.get("channels").cloned().unwrap_or_else(|| serde_json::json!([])))
+    }
+    "public/set_heartbeat" | "public/disable_heartbeat" => Ok(serde_json::json!("ok")),
+    "public/test" | "public/hello" => Ok(serde_json::json!({ "version": "mock" })),
+    "public/get_time" => Ok(serde_json::json!(now_us() / 1000)),
+    _ => Err(ApiError { code: -32601, message: "Method not found".to_string(), data: None }),
+  }
+}
+
+/// Client end of a connection to `MockDeribit`.
+pub struct MockTransport {
+  outgoing: Option<mpsc::UnboundedSender<Message>>,
+  incoming: mpsc::UnboundedReceiver<Message>,
+}
+
+impl Stream for MockTransport {
+  type Item = Result<Message, tungstenite::Error>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    self.incoming.poll_recv(cx).map(|message| message.map(Ok))
+  }
+}
+
+impl Sink<Message> for MockTransport {
+  type Error = tungstenite::Error;
+
+  fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn start_send(self: Pin<&mut Self>, message: Message) -> Result<(), Self::Error> {
+    match self.outgoing.as_ref() {
+      Some(outgoing) => outgoing.send(message).map_err(|_| tungstenite::Error::ConnectionClosed),
+      None => Err(tungstenite::Error::AlreadyClosed),
+    }
+  }
+
+  fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_close(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    self.outgoing = None;
+    Poll::Ready(Ok(()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use futures_util::{SinkExt, StreamExt};
+
+  use super::*;
+
+  #[tokio::test]
+  async fn invalid_requests_get_a_json_rpc_error() {
+    let mock = MockDeribit::new();
+    let mut socket = mock.connect();
+    socket.send(Message::Text(r#"{"jsonrpc":"2.0","id":7,"method":42}"#.to_string())).await.unwrap();
+    let Some(Ok(Message::Text(reply))) = socket.next().await else { panic!("Expected a reply") };
+    let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+    assert_eq!(reply["id"], 7);
+    assert_eq!(reply["error"]["code"], -32600);
+    assert!(mock.requests().is_empty());
+  }
+
+  #[tokio::test]
+  async fn handlers_answer_requests() {
+    let mock = MockDeribit::new();
+    mock.respond("public/get_time", serde_json::json!(1700000000000i64));
+    mock.fail_once("public/get_time", DeribitErrorCode::TooManyRequests);
+    let client = mock.client();
+    let first = client.request("public/get_time", serde_json::json!({})).await.unwrap();
+    assert_eq!(first.error.map(|err| err.code), Some(DeribitErrorCode::TooManyRequests.code()));
+    let second = client.request("public/get_time", serde_json::json!({})).await.unwrap();
+    assert_eq!(second.result, Some(serde_json::json!(1700000000000i64)));
+    assert_eq!(mock.requests_to("public/get_time").len(), 2);
+  }
+}
@@ -0,0 +1,398 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+
+use crate::channels::{Channel, Interval};
+use crate::core::{Error, RpcTransport, SocketClient};
+use crate::subscriptions::Subscription;
+use crate::types::Direction;
+
+/// Depth requested when taking a snapshot to (re)build a `LocalOrderBook`.
+pub const SNAPSHOT_DEPTH: u32 = 10000;
+
+/// Price level, sent by Deribit as a `[price, amount]` pair
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(from = "(f64, f64)", into = "(f64, f64)")]
+pub struct Level {
+  pub price: f64,
+  pub amount: f64,
+}
+
+impl From<(f64, f64)> for Level {
+  fn from((price, amount): (f64, f64)) -> Self {
+    Level { price, amount }
+  }
+}
+
+impl From<Level> for (f64, f64) {
+  fn from(level: Level) -> Self {
+    (level.price, level.amount)
+  }
+}
+
+/// Order book snapshot, as returned by `get_order_book`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderBook {
+  pub instrument_name: String,
+  pub timestamp: i64,
+  pub change_id: i64,
+  pub state: String,
+  /// Best bid first
+  pub bids: Vec<Level>,
+  /// Best ask first
+  pub asks: Vec<Level>,
+  #[serde(default)]
+  pub best_bid_price: Option<f64>,
+  #[serde(default)]
+  pub best_bid_amount: Option<f64>,
+  #[serde(default)]
+  pub best_ask_price: Option<f64>,
+  #[serde(default)]
+  pub best_ask_amount: Option<f64>,
+  #[serde(default)]
+  pub mark_price: Option<f64>,
+  #[serde(default)]
+  pub index_price: Option<f64>,
+  #[serde(default)]
+  pub last_price: Option<f64>,
+  #[serde(default)]
+  pub open_interest: Option<f64>,
+  #[serde(default)]
+  pub settlement_price: Option<f64>,
+  #[serde(default)]
+  pub min_price: Option<f64>,
+  #[serde(default)]
+  pub max_price: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LevelAction {
+  New,
+  Change,
+  Delete,
+}
+
+/// Change to a price level, sent by Deribit as an `[action, price, amount]` triple
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(from = "(LevelAction, f64, f64)", into = "(LevelAction, f64, f64)")]
+pub struct LevelChange {
+  pub action: LevelAction,
+  pub price: f64,
+  pub amount: f64,
+}
+
+impl From<(LevelAction, f64, f64)> for LevelChange {
+  fn from((action, price, amount): (LevelAction, f64, f64)) -> Self {
+    LevelChange { action, price, amount }
+  }
+}
+
+impl From<LevelChange> for (LevelAction, f64, f64) {
+  fn from(change: LevelChange) -> Self {
+    (change.action, change.price, change.amount)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookChangeKind {
+  Snapshot,
+  Change,
+}
+
+/// Payload of `book.{instrument_name}.{interval}` notifications
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookChange {
+  #[serde(rename = "type")]
+  pub kind: BookChangeKind,
+  pub instrument_name: String,
+  pub timestamp: i64,
+  pub change_id: i64,
+  #[serde(default)]
+  pub prev_change_id: Option<i64>,
+  pub bids: Vec<LevelChange>,
+  pub asks: Vec<LevelChange>,
+}
+
+/// Result of applying a `BookChange` to a `LocalOrderBook`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applied {
+  Applied,
+  /// The change is older than the book and was ignored.
+  Stale,
+  /// One or more changes were missed; the book must be rebuilt from a snapshot.
+  Gap,
+}
+
+/// Total order over prices, so they can be used as map keys.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Price(f64);
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Price {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.0.total_cmp(&other.0)
+  }
+}
+
+/// L2 order book kept up to date from `book.{instrument_name}.raw` notifications.
+#[derive(Debug, Clone)]
+pub struct LocalOrderBook {
+  pub instrument_name: String,
+  pub timestamp: i64,
+  pub change_id: i64,
+  bids: BTreeMap<Price, f64>,
+  asks: BTreeMap<Price, f64>,
+}
+
+impl LocalOrderBook {
+  /// Empty book, to be filled by a snapshot notification.
+  pub fn new(instrument_name: &str) -> Self {
+    LocalOrderBook { instrument_name: instrument_name.to_string(), timestamp: 0, change_id: 0, bids: BTreeMap::new(), asks: BTreeMap::new() }
+  }
+
+  pub fn from_snapshot(snapshot: &OrderBook) -> Self {
+    LocalOrderBook {
+      instrument_name: snapshot.instrument_name.clone(),
+      timestamp: snapshot.timestamp,
+      change_id: snapshot.change_id,
+      bids: snapshot.bids.iter().map(|l| (Price(l.price), l.amount)).collect(),
+      asks: snapshot.asks.iter().map(|l| (Price(l.price), l.amount)).collect(),
+    }
+  }
+
+  /// Apply a notification. Snapshots replace the book; changes must continue from the current `change_id`.
+  pub fn apply(&mut self, change: &BookChange) -> Applied {
+    match change.kind {
+      BookChangeKind::Snapshot => {
+        self.bids.clear();
+        self.asks.clear();
+      }
+      BookChangeKind::Change => {
+        if change.change_id <= self.change_id {
+          return Applied::Stale;
+        }
+        if change.prev_change_id != Some(self.change_id) {
+          return Applied::Gap;
+        }
+      }
+    }
+    apply_levels(&mut self.bids, &change.bids);
+    apply_levels(&mut self.asks, &change.asks);
+    self.change_id = change.change_id;
+    self.timestamp = change.timestamp;
+    Applied::Applied
+  }
+
+  /// Bids, best (highest) first
+  pub fn bids(&self) -> impl Iterator<Item = Level> + '_ {
+    self.bids.iter().rev().map(|(price, amount)| Level { price: price.0, amount: *amount })
+  }
+
+  /// Asks, best (lowest) first
+  pub fn asks(&self) -> impl Iterator<Item = Level> + '_ {
+    self.asks.iter().map(|(price, amount)| Level { price: price.0, amount: *amount })
+  }
+
+  pub fn best_bid(&self) -> Option<Level> {
+    self.bids().next()
+  }
+
+  pub fn best_ask(&self) -> Option<Level> {
+    self.asks().next()
+  }
+
+  pub fn mid_price(&self) -> Option<f64> {
+    Some((self.best_bid()?.price + self.best_ask()?.price) / 2.0)
+  }
+
+  /// Remove up to `amount` from the side a `direction` order trades against, best price first, as a taker would.
+  /// Returns the levels taken, i.e. the fills.
+  /// - `direction` - Side of the taker: buys take asks, sells take bids.
+  /// - `limit` - Worst price to take, or `None` for a market order.
+  /// - `amount` - Amount to take.
+  pub fn take(&mut self, direction: Direction, limit: Option<f64>, amount: f64) -> Vec<Level> {
+    let mut fills = Vec::new();
+    let mut remaining = amount;
+    while remaining > 0.0 {
+      let best = match direction {
+        Direction::Buy => self.asks.first_key_value(),
+        Direction::Sell => self.bids.last_key_value(),
+      };
+      let Some((&price, &available)) = best else { break };
+      let within = match direction {
+        Direction::Buy => limit.is_none_or(|limit| price.0 <= limit),
+        Direction::Sell => limit.is_none_or(|limit| price.0 >= limit),
+      };
+      if !within {
+        break;
+      }
+      let taken = available.min(remaining);
+      let side = if direction == Direction::Buy { &mut self.asks } else { &mut self.bids };
+      if taken < available {
+        side.insert(price, available - taken);
+      } else {
+        side.remove(&price);
+      }
+      fills.push(Level { price: price.0, amount: taken });
+      remaining -= taken;
+    }
+    fills
+  }
+}
+
+fn apply_levels(side: &mut BTreeMap<Price, f64>, changes: &[LevelChange]) {
+  for change in changes {
+    match change.action {
+      LevelAction::New | LevelAction::Change => { side.insert(Price(change.price), change.amount); }
+      LevelAction::Delete => { side.remove(&Price(change.price)); }
+    }
+  }
+}
+
+/// A `LocalOrderBook` driven by its subscription, which re-snapshots automatically whenever a change is missed.
+pub struct BookSync {
+  client: SocketClient,
+  subscription: Subscription<BookChange>,
+  book: LocalOrderBook,
+}
+
+impl BookSync {
+  /// Subscribe to `book.{instrument_name}.raw` and build the initial book from a snapshot.
+  /// Raw book channels require an authenticated connection.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  pub async fn start(client: &SocketClient, instrument_name: &str) -> Result<Self, Error> {
+    let channel = Channel::Book { instrument_name: instrument_name.to_string(), interval: Interval::Raw }.to_string();
+    let subscription = client.subscribe::<BookChange>(&[&channel]).await?;
+    let snapshot = client.get_order_book(instrument_name, Some(SNAPSHOT_DEPTH)).await?;
+    let book = LocalOrderBook::from_snapshot(&snapshot);
+    Ok(BookSync { client: client.clone(), subscription, book })
+  }
+
+  pub fn book(&self) -> &LocalOrderBook {
+    &self.book
+  }
+
+  /// Wait for the next change and apply it, re-snapshotting on a gap. The change that revealed the gap is applied
+  /// on top of the new snapshot if it continues from it. Fails with `Error::Disconnected` once the subscription ends.
+  pub async fn next(&mut self) -> Result<&LocalOrderBook, Error> {
+    loop {
+      let update = match self.subscription.recv().await {
+        Some(update) => update?,
+        None => return Err(Error::Disconnected),
+      };
+      match self.book.apply(&update.data) {
+        Applied::Applied => return Ok(&self.book),
+        Applied::Stale => {}
+        Applied::Gap => {
+          let snapshot = self.client.get_order_book(&self.book.instrument_name, Some(SNAPSHOT_DEPTH)).await?;
+          self.book = LocalOrderBook::from_snapshot(&snapshot);
+          // Older changes are already in the snapshot; a newer one that doesn't continue from it is left to the next gap
+          if self.book.apply(&update.data) == Applied::Applied {
+            return Ok(&self.book);
+          }
+        }
+      }
+    }
+  }
+}
+
+/// Order book snapshots, available on any transport.
+pub trait OrderBookApi: RpcTransport {
+  /// Retrieves the order book of an instrument.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  /// - `depth` - Number of levels per side, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_order_book)
+  fn get_order_book(&self, instrument_name: &str, depth: Option<u32>) -> impl Future<Output = Result<OrderBook, Error>> + Send {
+    let mut params = serde_json::json!({ "instrument_name": instrument_name });
+    if let Some(depth) = depth {
+      params["depth"] = serde_json::Value::from(depth);
+    }
+    self.call("public/get_order_book", params)
+  }
+}
+
+impl<C: RpcTransport> OrderBookApi for C {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::MockDeribit;
+
+  fn snapshot(change_id: i64, bid: f64, ask: f64) -> serde_json::Value {
+    serde_json::json!({
+      "instrument_name": "BTC-PERPETUAL",
+      "timestamp": change_id,
+      "change_id": change_id,
+      "state": "open",
+      "bids": [[bid, 10.0]],
+      "asks": [[ask, 10.0]],
+    })
+  }
+
+  fn change(change_id: i64, prev_change_id: i64, bid: f64) -> serde_json::Value {
+    serde_json::json!({
+      "type": "change",
+      "instrument_name": "BTC-PERPETUAL",
+      "timestamp": change_id,
+      "change_id": change_id,
+      "prev_change_id": prev_change_id,
+      "bids": [["new", bid, 1.0]],
+      "asks": [],
+    })
+  }
+
+  #[tokio::test]
+  async fn book_sync_resnapshots_on_a_gap() {
+    let mock = MockDeribit::new();
+    mock.respond_once("public/get_order_book", snapshot(10, 99.0, 101.0));
+    mock.respond_once("public/get_order_book", snapshot(12, 98.0, 102.0));
+    mock.respond("public/get_order_book", snapshot(20, 97.0, 103.0));
+    let client = mock.client();
+    let mut sync = BookSync::start(&client, "BTC-PERPETUAL").await.unwrap();
+    assert_eq!(sync.book().change_id, 10);
+
+    let channel = "book.BTC-PERPETUAL.raw";
+    mock.notify(channel, change(11, 10, 99.5));
+    // 12 was missed, and the snapshot stops right before 13, which is applied on top of it
+    mock.notify(channel, change(13, 12, 99.6));
+    // 14 to 18 were missed, then the snapshot is already past 19
+    mock.notify(channel, change(19, 18, 99.7));
+    mock.notify(channel, change(21, 20, 98.5));
+
+    let book = sync.next().await.unwrap();
+    assert_eq!((book.change_id, book.best_bid().map(|level| level.price)), (11, Some(99.5)));
+    let book = sync.next().await.unwrap();
+    assert_eq!(book.change_id, 13);
+    assert_eq!(book.bids().map(|level| level.price).collect::<Vec<_>>(), [99.6, 98.0]);
+    assert_eq!(book.best_ask().map(|level| level.price), Some(102.0));
+    let book = sync.next().await.unwrap();
+    assert_eq!(book.change_id, 21);
+    assert_eq!(book.bids().map(|level| level.price).collect::<Vec<_>>(), [98.5, 97.0]);
+    assert_eq!(book.best_ask().map(|level| level.price), Some(103.0));
+    assert_eq!(mock.requests_to("public/get_order_book").len(), 3);
+  }
+
+  #[test]
+  fn changes_continue_from_the_current_change_id() {
+    let mut book = LocalOrderBook::new("BTC-PERPETUAL");
+    let mut first: BookChange = serde_json::from_value(change(5, 4, 100.0)).unwrap();
+    first.kind = BookChangeKind::Snapshot;
+    assert_eq!(book.apply(&first), Applied::Applied);
+    assert_eq!(book.apply(&serde_json::from_value(change(5, 4, 100.0)).unwrap()), Applied::Stale);
+    assert_eq!(book.apply(&serde_json::from_value(change(7, 6, 100.0)).unwrap()), Applied::Gap);
+    assert_eq!(book.apply(&serde_json::from_value(change(6, 5, 101.0)).unwrap()), Applied::Applied);
+    assert_eq!(book.best_bid().map(|level| level.price), Some(101.0));
+  }
+}
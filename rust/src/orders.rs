@@ -0,0 +1,534 @@
+use std::collections::HashSet;
+
+use futures_util::{stream, Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::core::{Error, PrivateClient, RpcTransport};
+use crate::trading::{Order, Trade};
+use crate::types::{Currency, Kind};
+
+/// Number of items requested per page when walking paginated endpoints.
+pub const PAGE_SIZE: u32 = 100;
+/// The largest `count` Deribit accepts for the user trade endpoints.
+pub const MAX_TRADES_COUNT: u32 = 1000;
+
+/// Filter for the open order endpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenOrdersFilter {
+  All,
+  Limit,
+  TriggerAll,
+  StopAll,
+  StopLimit,
+  StopMarket,
+  TakeAll,
+  TakeLimit,
+  TakeMarket,
+  TrailingAll,
+  TrailingStop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sorting {
+  Asc,
+  Desc,
+  Default,
+}
+
+/// Time range and page size for the user trade endpoints. Leave fields unset for the server defaults.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TradeQuery {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub start_timestamp: Option<i64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub end_timestamp: Option<i64>,
+  /// First `trade_seq` to return. Only for `get_user_trades_by_instrument`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub start_seq: Option<i64>,
+  /// Last `trade_seq` to return. Only for `get_user_trades_by_instrument`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub end_seq: Option<i64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub count: Option<u32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub sorting: Option<Sorting>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradesPage {
+  pub trades: Vec<Trade>,
+  pub has_more: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettlementType {
+  Settlement,
+  Delivery,
+  Bankruptcy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settlement {
+  #[serde(rename = "type")]
+  pub settlement_type: SettlementType,
+  pub timestamp: i64,
+  #[serde(default)]
+  pub instrument_name: Option<String>,
+  #[serde(default)]
+  pub position: Option<f64>,
+  #[serde(default)]
+  pub mark_price: Option<f64>,
+  #[serde(default)]
+  pub index_price: Option<f64>,
+  #[serde(default)]
+  pub profit_loss: Option<f64>,
+  #[serde(default)]
+  pub session_profit_loss: Option<f64>,
+  #[serde(default)]
+  pub funding: Option<f64>,
+  #[serde(default)]
+  pub funded: Option<f64>,
+  #[serde(default)]
+  pub session_bankrupcy: Option<f64>,
+  #[serde(default)]
+  pub socialized: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettlementsPage {
+  pub settlements: Vec<Settlement>,
+  /// Token for the next page; `None` on the last one.
+  #[serde(default)]
+  pub continuation: Option<String>,
+}
+
+impl SettlementsPage {
+  fn next(&self) -> Option<String> {
+    self.continuation.clone().filter(|c| !c.is_empty() && c != "none")
+  }
+}
+
+/// Flattens a stream of pages into a stream of items.
+fn flatten<T, S: Stream<Item = Result<Vec<T>, Error>>>(pages: S) -> impl Stream<Item = Result<T, Error>> {
+  pages.map_ok(|page| stream::iter(page.into_iter().map(Ok))).try_flatten()
+}
+
+impl<C: RpcTransport> PrivateClient<C> {
+  /// Retrieves all open orders.
+  /// - `kind` - Only return orders on instruments of this kind, if given.
+  /// - `filter` - Only return orders of this type, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_open_orders)
+  pub async fn get_open_orders(&mut self, kind: Option<Kind>, filter: Option<OpenOrdersFilter>) -> Result<Vec<Order>, Error> {
+    let mut params = serde_json::json!({});
+    if let Some(kind) = kind {
+      params["kind"] = serde_json::to_value(kind)?;
+    }
+    if let Some(filter) = filter {
+      params["type"] = serde_json::to_value(filter)?;
+    }
+    self.authed_call("private/get_open_orders", params).await
+  }
+
+  /// Retrieves open orders in a currency.
+  /// - `currency` - The currency of the instruments.
+  /// - `kind` - Only return orders on instruments of this kind, if given.
+  /// - `filter` - Only return orders of this type, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_open_orders_by_currency)
+  pub async fn get_open_orders_by_currency(&mut self, currency: Currency, kind: Option<Kind>, filter: Option<OpenOrdersFilter>) -> Result<Vec<Order>, Error> {
+    let mut params = serde_json::json!({ "currency": currency });
+    if let Some(kind) = kind {
+      params["kind"] = serde_json::to_value(kind)?;
+    }
+    if let Some(filter) = filter {
+      params["type"] = serde_json::to_value(filter)?;
+    }
+    self.authed_call("private/get_open_orders_by_currency", params).await
+  }
+
+  /// Retrieves open orders on an instrument.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  /// - `filter` - Only return orders of this type, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_open_orders_by_instrument)
+  pub async fn get_open_orders_by_instrument(&mut self, instrument_name: &str, filter: Option<OpenOrdersFilter>) -> Result<Vec<Order>, Error> {
+    let mut params = serde_json::json!({ "instrument_name": instrument_name });
+    if let Some(filter) = filter {
+      params["type"] = serde_json::to_value(filter)?;
+    }
+    self.authed_call("private/get_open_orders_by_instrument", params).await
+  }
+
+  /// Retrieves open orders with the given label.
+  /// - `currency` - The currency of the instruments.
+  /// - `label` - The user defined label of the orders.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_open_orders_by_label)
+  pub async fn get_open_orders_by_label(&mut self, currency: Currency, label: &str) -> Result<Vec<Order>, Error> {
+    let params = serde_json::json!({ "currency": currency, "label": label });
+    self.authed_call("private/get_open_orders_by_label", params).await
+  }
+
+  /// Retrieves the current state of an order.
+  /// - `order_id` - The ID of the order.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_order_state)
+  pub async fn get_order_state(&mut self, order_id: &str) -> Result<Order, Error> {
+    let params = serde_json::json!({ "order_id": order_id });
+    self.authed_call("private/get_order_state", params).await
+  }
+
+  /// Retrieves the state of recent orders with the given label.
+  /// - `currency` - The currency of the instruments.
+  /// - `label` - The user defined label of the orders.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_order_state_by_label)
+  pub async fn get_order_state_by_label(&mut self, currency: Currency, label: &str) -> Result<Vec<Order>, Error> {
+    let params = serde_json::json!({ "currency": currency, "label": label });
+    self.authed_call("private/get_order_state_by_label", params).await
+  }
+
+  /// Retrieves one page of closed and filled orders in a currency, most recent first.
+  /// - `currency` - The currency of the instruments.
+  /// - `kind` - Only return orders on instruments of this kind, if given.
+  /// - `count` - Page size.
+  /// - `offset` - Number of orders to skip.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_order_history_by_currency)
+  pub async fn get_order_history_by_currency(&mut self, currency: Currency, kind: Option<Kind>, count: u32, offset: u32) -> Result<Vec<Order>, Error> {
+    let mut params = serde_json::json!({ "currency": currency, "count": count, "offset": offset });
+    if let Some(kind) = kind {
+      params["kind"] = serde_json::to_value(kind)?;
+    }
+    self.authed_call("private/get_order_history_by_currency", params).await
+  }
+
+  /// Retrieves one page of closed and filled orders on an instrument, most recent first.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  /// - `count` - Page size.
+  /// - `offset` - Number of orders to skip.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_order_history_by_instrument)
+  pub async fn get_order_history_by_instrument(&mut self, instrument_name: &str, count: u32, offset: u32) -> Result<Vec<Order>, Error> {
+    let params = serde_json::json!({ "instrument_name": instrument_name, "count": count, "offset": offset });
+    self.authed_call("private/get_order_history_by_instrument", params).await
+  }
+
+  /// Walks every page of `get_order_history_by_currency`.
+  pub fn order_history_by_currency(&self, currency: Currency, kind: Option<Kind>) -> impl Stream<Item = Result<Order, Error>> {
+    let pages = stream::try_unfold(Some((self.clone(), 0)), move |state| {
+      let currency = currency.clone();
+      async move {
+        let Some((mut client, offset)) = state else { return Ok(None) };
+        let page = client.get_order_history_by_currency(currency, kind, PAGE_SIZE, offset).await?;
+        let next = (page.len() as u32 == PAGE_SIZE).then(|| (client, offset + PAGE_SIZE));
+        Ok(Some((page, next)))
+      }
+    });
+    flatten(pages)
+  }
+
+  /// Walks every page of `get_order_history_by_instrument`.
+  pub fn order_history_by_instrument(&self, instrument_name: &str) -> impl Stream<Item = Result<Order, Error>> {
+    let instrument_name = instrument_name.to_string();
+    let pages = stream::try_unfold(Some((self.clone(), 0)), move |state| {
+      let instrument_name = instrument_name.clone();
+      async move {
+        let Some((mut client, offset)) = state else { return Ok(None) };
+        let page = client.get_order_history_by_instrument(&instrument_name, PAGE_SIZE, offset).await?;
+        let next = (page.len() as u32 == PAGE_SIZE).then(|| (client, offset + PAGE_SIZE));
+        Ok(Some((page, next)))
+      }
+    });
+    flatten(pages)
+  }
+
+  /// Retrieves one page of the user's trades in a currency.
+  /// - `currency` - The currency of the instruments.
+  /// - `kind` - Only return trades on instruments of this kind, if given.
+  /// - `query` - Time range, page size and sorting.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_user_trades_by_currency)
+  pub async fn get_user_trades_by_currency(&mut self, currency: Currency, kind: Option<Kind>, query: TradeQuery) -> Result<TradesPage, Error> {
+    let mut params = serde_json::to_value(query)?;
+    params["currency"] = serde_json::to_value(currency)?;
+    if let Some(kind) = kind {
+      params["kind"] = serde_json::to_value(kind)?;
+    }
+    self.authed_call("private/get_user_trades_by_currency", params).await
+  }
+
+  /// Retrieves one page of the user's trades on an instrument.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  /// - `query` - Time range, page size and sorting.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_user_trades_by_instrument)
+  pub async fn get_user_trades_by_instrument(&mut self, instrument_name: &str, query: TradeQuery) -> Result<TradesPage, Error> {
+    let mut params = serde_json::to_value(query)?;
+    params["instrument_name"] = serde_json::Value::from(instrument_name);
+    self.authed_call("private/get_user_trades_by_instrument", params).await
+  }
+
+  /// Retrieves the trades of an order.
+  /// - `order_id` - The ID of the order.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_user_trades_by_order)
+  pub async fn get_user_trades_by_order(&mut self, order_id: &str) -> Result<Vec<Trade>, Error> {
+    let params = serde_json::json!({ "order_id": order_id });
+    self.authed_call("private/get_user_trades_by_order", params).await
+  }
+
+  /// Walks every page of `get_user_trades_by_currency`, oldest first, starting at `query.start_timestamp`.
+  pub fn user_trades_by_currency(&self, currency: Currency, kind: Option<Kind>, query: TradeQuery) -> impl Stream<Item = Result<Trade, Error>> {
+    let query = TradeQuery { sorting: Some(Sorting::Asc), count: query.count.or(Some(PAGE_SIZE)), ..query };
+    let pages = stream::try_unfold(Some((self.clone(), query, HashSet::new())), move |state| {
+      let currency = currency.clone();
+      async move {
+        let Some((mut client, query, seen)) = state else { return Ok(None) };
+        let page = client.get_user_trades_by_currency(currency, kind, query.clone()).await?;
+        next_trades_page(client, query, seen, page).map(Some)
+      }
+    });
+    flatten(pages)
+  }
+
+  /// Walks every page of `get_user_trades_by_instrument`, oldest first, starting at `query.start_timestamp` or `query.start_seq`.
+  pub fn user_trades_by_instrument(&self, instrument_name: &str, query: TradeQuery) -> impl Stream<Item = Result<Trade, Error>> {
+    let instrument_name = instrument_name.to_string();
+    let query = TradeQuery { sorting: Some(Sorting::Asc), count: query.count.or(Some(PAGE_SIZE)), ..query };
+    let pages = stream::try_unfold(Some((self.clone(), query)), move |state| {
+      let instrument_name = instrument_name.clone();
+      async move {
+        let Some((mut client, query)) = state else { return Ok(None) };
+        let page = client.get_user_trades_by_instrument(&instrument_name, query.clone()).await?;
+        Ok(Some(next_seq_page(client, query, page)))
+      }
+    });
+    flatten(pages)
+  }
+
+  /// Retrieves one page of settlement, delivery and bankruptcy events in a currency.
+  /// - `currency` - The currency of the instruments.
+  /// - `settlement_type` - Only return events of this type, if given.
+  /// - `count` - Page size.
+  /// - `continuation` - Token from the previous page, if any.
+  /// - `search_start_timestamp` - Only return events before this timestamp, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_settlement_history_by_currency)
+  pub async fn get_settlement_history_by_currency(
+    &mut self,
+    currency: Currency,
+    settlement_type: Option<SettlementType>,
+    count: u32,
+    continuation: Option<&str>,
+    search_start_timestamp: Option<i64>,
+  ) -> Result<SettlementsPage, Error> {
+    let mut params = serde_json::json!({ "currency": currency, "count": count });
+    add_settlement_params(&mut params, settlement_type, continuation, search_start_timestamp)?;
+    self.authed_call("private/get_settlement_history_by_currency", params).await
+  }
+
+  /// Retrieves one page of settlement, delivery and bankruptcy events on an instrument.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  /// - `settlement_type` - Only return events of this type, if given.
+  /// - `count` - Page size.
+  /// - `continuation` - Token from the previous page, if any.
+  /// - `search_start_timestamp` - Only return events before this timestamp, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_settlement_history_by_instrument)
+  pub async fn get_settlement_history_by_instrument(
+    &mut self,
+    instrument_name: &str,
+    settlement_type: Option<SettlementType>,
+    count: u32,
+    continuation: Option<&str>,
+    search_start_timestamp: Option<i64>,
+  ) -> Result<SettlementsPage, Error> {
+    let mut params = serde_json::json!({ "instrument_name": instrument_name, "count": count });
+    add_settlement_params(&mut params, settlement_type, continuation, search_start_timestamp)?;
+    self.authed_call("private/get_settlement_history_by_instrument", params).await
+  }
+
+  /// Walks every page of `get_settlement_history_by_currency`.
+  pub fn settlement_history_by_currency(&self, currency: Currency, settlement_type: Option<SettlementType>) -> impl Stream<Item = Result<Settlement, Error>> {
+    let pages = stream::try_unfold(Some((self.clone(), None::<String>)), move |state| {
+      let currency = currency.clone();
+      async move {
+        let Some((mut client, continuation)) = state else { return Ok(None) };
+        let page = client.get_settlement_history_by_currency(currency, settlement_type, PAGE_SIZE, continuation.as_deref(), None).await?;
+        let next = page.next().map(|c| (client, Some(c)));
+        Ok(Some((page.settlements, next)))
+      }
+    });
+    flatten(pages)
+  }
+
+  /// Walks every page of `get_settlement_history_by_instrument`.
+  pub fn settlement_history_by_instrument(&self, instrument_name: &str, settlement_type: Option<SettlementType>) -> impl Stream<Item = Result<Settlement, Error>> {
+    let instrument_name = instrument_name.to_string();
+    let pages = stream::try_unfold(Some((self.clone(), None::<String>)), move |state| {
+      let instrument_name = instrument_name.clone();
+      async move {
+        let Some((mut client, continuation)) = state else { return Ok(None) };
+        let page = client.get_settlement_history_by_instrument(&instrument_name, settlement_type, PAGE_SIZE, continuation.as_deref(), None).await?;
+        let next = page.next().map(|c| (client, Some(c)));
+        Ok(Some((page.settlements, next)))
+      }
+    });
+    flatten(pages)
+  }
+}
+
+fn add_settlement_params(
+  params: &mut serde_json::Value,
+  settlement_type: Option<SettlementType>,
+  continuation: Option<&str>,
+  search_start_timestamp: Option<i64>,
+) -> Result<(), Error> {
+  if let Some(settlement_type) = settlement_type {
+    params["type"] = serde_json::to_value(settlement_type)?;
+  }
+  if let Some(continuation) = continuation {
+    params["continuation"] = serde_json::Value::from(continuation);
+  }
+  if let Some(timestamp) = search_start_timestamp {
+    params["search_start_timestamp"] = serde_json::Value::from(timestamp);
+  }
+  Ok(())
+}
+
+type TradesState<C> = Option<(PrivateClient<C>, TradeQuery, HashSet<String>)>;
+
+/// Moves the start of an ascending trade query to the last timestamp seen. Trades sharing that millisecond
+/// are returned again by the next page, so their IDs are remembered and skipped. If a whole page shares it,
+/// the rest of that millisecond can't be reached by timestamp, so the page is refetched with twice the count,
+/// up to `MAX_TRADES_COUNT`; past that the walk fails rather than skip trades.
+fn next_trades_page<C: RpcTransport>(client: PrivateClient<C>, query: TradeQuery, seen: HashSet<String>, page: TradesPage) -> Result<(Vec<Trade>, TradesState<C>), Error> {
+  let page_first = page.trades.first().map(|t| t.timestamp);
+  let page_last = page.trades.last().map(|t| t.timestamp);
+  let trades: Vec<Trade> = page.trades.into_iter().filter(|t| !seen.contains(&t.trade_id)).collect();
+  let (true, Some(page_last)) = (page.has_more, page_last) else {
+    return Ok((trades, None));
+  };
+  if page_first == Some(page_last) {
+    let count = query.count.unwrap_or(PAGE_SIZE);
+    if count >= MAX_TRADES_COUNT {
+      return Err(Error::Logic("more trades share one millisecond than a page can hold"));
+    }
+    let mut seen = seen;
+    seen.extend(trades.iter().map(|t| t.trade_id.clone()));
+    let query = TradeQuery { start_timestamp: Some(page_last), count: Some((count * 2).min(MAX_TRADES_COUNT)), ..query };
+    return Ok((trades, Some((client, query, seen))));
+  }
+  let last = trades.last().map_or(page_last, |t| t.timestamp);
+  let mut seen: HashSet<String> = seen.into_iter().filter(|_| query.start_timestamp == Some(last)).collect();
+  seen.extend(trades.iter().filter(|t| t.timestamp == last).map(|t| t.trade_id.clone()));
+  let query = TradeQuery { start_timestamp: Some(last), ..query };
+  Ok((trades, Some((client, query, seen))))
+}
+
+/// Continues an ascending instrument trade query after the last `trade_seq` seen, which is unique per instrument.
+fn next_seq_page<C: RpcTransport>(client: PrivateClient<C>, query: TradeQuery, page: TradesPage) -> (Vec<Trade>, Option<(PrivateClient<C>, TradeQuery)>) {
+  let next = match page.trades.last() {
+    Some(last) if page.has_more => {
+      let query = TradeQuery { start_seq: Some(last.trade_seq + 1), start_timestamp: None, ..query };
+      Some((client, query))
+    }
+    _ => None,
+  };
+  (page.trades, next)
+}
+
+#[cfg(test)]
+mod tests {
+  use futures_util::TryStreamExt;
+
+  use super::*;
+  use crate::mock::MockDeribit;
+
+  fn trade(trade_seq: i64, timestamp: i64) -> serde_json::Value {
+    serde_json::json!({
+      "trade_id": format!("BTC-{}", trade_seq),
+      "trade_seq": trade_seq,
+      "order_id": "order",
+      "instrument_name": "BTC-PERPETUAL",
+      "direction": "buy",
+      "price": 50000.0,
+      "amount": 10.0,
+      "fee": 0.0,
+      "fee_currency": "BTC",
+      "timestamp": timestamp,
+      "index_price": 50000.0,
+      "mark_price": 50000.0,
+      "state": "filled",
+      "order_type": "limit",
+      "tick_direction": 0,
+    })
+  }
+
+  /// Serves `trades` (sorted by `trade_seq`) page by page, filtering like Deribit on the query parameters.
+  fn serve_trades(mock: &MockDeribit, method: &str, trades: Vec<(i64, i64)>) {
+    mock.on(method, move |req| {
+      let param = |name: &str| req.params.get(name).and_then(|value| value.as_i64());
+      let count = param("count").unwrap_or(10) as usize;
+      let matching: Vec<serde_json::Value> = trades.iter()
+        .filter(|(seq, timestamp)| param("start_seq").is_none_or(|start| *seq >= start) && param("start_timestamp").is_none_or(|start| *timestamp >= start))
+        .map(|(seq, timestamp)| trade(*seq, *timestamp))
+        .collect();
+      let has_more = matching.len() > count;
+      Ok(serde_json::json!({ "trades": matching.into_iter().take(count).collect::<Vec<_>>(), "has_more": has_more }))
+    });
+  }
+
+  #[tokio::test]
+  async fn instrument_trades_page_by_sequence() {
+    let mock = MockDeribit::new();
+    // Five trades in the same millisecond, more than a page
+    serve_trades(&mock, "private/get_user_trades_by_instrument", (1..=5).map(|seq| (seq, 1000)).collect());
+    let client = mock.private_client().await.unwrap();
+    let query = TradeQuery { count: Some(2), ..TradeQuery::default() };
+    let trades: Vec<Trade> = client.user_trades_by_instrument("BTC-PERPETUAL", query).try_collect().await.unwrap();
+    assert_eq!(trades.iter().map(|t| t.trade_seq).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+    let requests = mock.requests_to("private/get_user_trades_by_instrument");
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[1].params["start_seq"], 3);
+  }
+
+  #[tokio::test]
+  async fn currency_trades_skip_repeated_timestamps() {
+    let mock = MockDeribit::new();
+    serve_trades(&mock, "private/get_user_trades_by_currency", vec![(1, 1000), (2, 1000), (3, 2000), (4, 2000), (5, 3000)]);
+    let client = mock.private_client().await.unwrap();
+    let query = TradeQuery { count: Some(2), ..TradeQuery::default() };
+    let trades: Vec<Trade> = client.user_trades_by_currency(Currency::BTC, None, query).try_collect().await.unwrap();
+    assert_eq!(trades.iter().map(|t| t.trade_seq).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+  }
+
+  #[tokio::test]
+  async fn currency_trades_move_past_a_full_millisecond() {
+    let mock = MockDeribit::new();
+    // Three trades share a millisecond but pages hold two, so the page grows to reach the third
+    serve_trades(&mock, "private/get_user_trades_by_currency", vec![(1, 1000), (2, 1000), (3, 1000), (4, 2000)]);
+    let client = mock.private_client().await.unwrap();
+    let query = TradeQuery { count: Some(2), ..TradeQuery::default() };
+    let trades: Vec<Trade> = client.user_trades_by_currency(Currency::BTC, None, query).try_collect().await.unwrap();
+    assert_eq!(trades.iter().map(|t| t.trade_seq).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    let counts: Vec<i64> = mock.requests_to("private/get_user_trades_by_currency").iter().map(|r| r.params["count"].as_i64().unwrap()).collect();
+    assert_eq!(counts[..2], [2, 4]);
+  }
+
+  #[tokio::test]
+  async fn currency_trades_fail_rather_than_skip_a_millisecond() {
+    let mock = MockDeribit::new();
+    let trades = (1..=MAX_TRADES_COUNT as i64 + 1).map(|seq| (seq, 1000)).collect();
+    serve_trades(&mock, "private/get_user_trades_by_currency", trades);
+    let client = mock.private_client().await.unwrap();
+    let query = TradeQuery { count: Some(MAX_TRADES_COUNT), ..TradeQuery::default() };
+    let result: Result<Vec<Trade>, Error> = client.user_trades_by_currency(Currency::BTC, None, query).try_collect().await;
+    assert!(matches!(result, Err(Error::Logic(_))));
+  }
+}
@@ -0,0 +1,1230 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::channels::{Channel, Interval, Target};
+use crate::core::{now_us, parse_json, ApiError, DeribitErrorCode, Error, PrivateClient, SocketClient};
+use crate::mock::{api_error, MockDeribit, MockRequest, MockTransport};
+use crate::order_book::{Applied, BookChange, BookChangeKind, Level, LocalOrderBook, OrderBook};
+use crate::trading::{Liquidity, Order, OrderState, OrderType, TimeInForce, Trade};
+use crate::types::{Currency, Direction, Kind};
+
+/// Simulated exchange for dry runs: a `MockDeribit` whose trading methods are served by a local matching engine.
+///
+/// Orders match against order books fed from recorded market data (`feed`, `replay`): takers walk the book,
+/// and resting limit orders fill once the market trades through their price. Fills update positions and balances,
+/// and are notified on the `user.orders` and `user.trades` channels clients subscribed to.
+/// Market data notifications are forwarded to subscribers as they are fed. Like on Deribit, subscriptions belong
+/// to the connection they were made on, and only that connection receives their notifications.
+///
+/// Served methods: `private/buy`, `sell`, `edit`, `cancel`, `cancel_all`, `cancel_all_by_currency`,
+/// `cancel_all_by_instrument`, `get_order_state`, `get_open_orders`, `get_open_orders_by_currency`,
+/// `get_open_orders_by_instrument`, `get_position`, `get_positions`, `get_account_summary`, as well as
+/// `public/get_order_book`, `public/get_time` and subscriptions. Any other method can be scripted on `mock()`.
+///
+/// The model is deliberately simple: futures without a quote currency (e.g. `BTC-PERPETUAL`) are inverse,
+/// sized in USD and settled in the base currency; everything else is linear, settled in the quote currency
+/// (e.g. `BTC_USDC-PERPETUAL`) or the base currency (e.g. `BTC-27DEC24-60000-C`). Margins are a flat rate of the
+/// notional; there are no trigger orders, funding, settlement or liquidations.
+///
+/// ```ignore
+/// let paper = PaperExchange::new();
+/// paper.deposit(Currency::BTC, 1.0);
+/// paper.replay("btc-perpetual-book.jsonl")?;
+/// let mut client = paper.private_client().await?;
+/// client.buy("BTC-PERPETUAL", OrderRequest::market(100.0)).await?;
+/// let positions = client.get_positions(Some(Currency::BTC), None).await?;
+/// ```
+#[derive(Clone)]
+pub struct PaperExchange {
+  mock: MockDeribit,
+  engine: Arc<Mutex<Engine>>,
+}
+
+impl Default for PaperExchange {
+  fn default() -> Self {
+    PaperExchange::new()
+  }
+}
+
+impl PaperExchange {
+  pub fn new() -> Self {
+    let exchange = PaperExchange { mock: MockDeribit::new(), engine: Arc::new(Mutex::new(Engine::default())) };
+    exchange.handle("private/buy", |engine, req| engine.place(Direction::Buy, req));
+    exchange.handle("private/sell", |engine, req| engine.place(Direction::Sell, req));
+    exchange.handle("private/edit", Engine::edit);
+    exchange.handle("private/cancel", Engine::cancel);
+    exchange.handle("private/cancel_all", |engine, _| Ok(Value::from(engine.cancel_where(|_| true))));
+    exchange.handle("private/cancel_all_by_currency", Engine::cancel_all_by_currency);
+    exchange.handle("private/cancel_all_by_instrument", Engine::cancel_all_by_instrument);
+    exchange.handle("private/get_order_state", Engine::order_state);
+    exchange.handle("private/get_open_orders", Engine::open_orders);
+    exchange.handle("private/get_open_orders_by_currency", Engine::open_orders);
+    exchange.handle("private/get_open_orders_by_instrument", Engine::open_orders);
+    exchange.handle("private/get_position", Engine::position);
+    exchange.handle("private/get_positions", Engine::positions);
+    exchange.handle("private/get_account_summary", Engine::account_summary);
+    exchange.handle("public/get_order_book", Engine::order_book);
+    exchange.handle("public/get_time", |engine, _| Ok(Value::from(engine.now())));
+    for method in ["public/subscribe", "private/subscribe"] {
+      exchange.handle(method, Engine::subscribe);
+    }
+    for method in ["public/unsubscribe", "private/unsubscribe"] {
+      exchange.handle(method, Engine::unsubscribe);
+    }
+    for method in ["public/unsubscribe_all", "private/unsubscribe_all"] {
+      exchange.handle(method, |engine, req| {
+        engine.subscribers.retain(|_, connections| {
+          connections.remove(&req.connection);
+          !connections.is_empty()
+        });
+        Ok(Value::from("ok"))
+      });
+    }
+    exchange
+  }
+
+  /// Set the fee rates, as fractions of the traded notional. Defaults to 0 for makers and 0.0005 for takers.
+  pub fn fees(self, maker: f64, taker: f64) -> Self {
+    {
+      let mut engine = self.engine.lock().unwrap();
+      engine.maker_fee = maker;
+      engine.taker_fee = taker;
+    }
+    self
+  }
+
+  /// Set the margin rates, as fractions of the position notional. Defaults to 0.02 initial and 0.01 maintenance.
+  pub fn margin(self, initial: f64, maintenance: f64) -> Self {
+    {
+      let mut engine = self.engine.lock().unwrap();
+      engine.initial_margin = initial;
+      engine.maintenance_margin = maintenance;
+    }
+    self
+  }
+
+  /// Credit `amount` to the balance in `currency`. Accounts start empty.
+  pub fn deposit(&self, currency: Currency, amount: f64) {
+    *self.engine.lock().unwrap().balances.entry(currency).or_default() += amount;
+  }
+
+  /// The underlying mock, e.g. to script methods the exchange doesn't serve or to assert on requests.
+  pub fn mock(&self) -> &MockDeribit {
+    &self.mock
+  }
+
+  /// Open a new connection to the exchange, to pass to `SocketClient::start`. Must be called within a Tokio runtime.
+  pub fn connect(&self) -> MockTransport {
+    self.mock.connect()
+  }
+
+  /// A public client connected to the exchange, without client-side rate limiting.
+  pub fn client(&self) -> SocketClient {
+    self.mock.client()
+  }
+
+  /// A private client connected to the exchange, authenticated through `public/auth`, without client-side rate limiting.
+  pub async fn private_client(&self) -> Result<PrivateClient, Error> {
+    self.mock.private_client().await
+  }
+
+  /// The current state of an instrument's book, including the liquidity taken by fills.
+  pub fn book(&self, instrument_name: &str) -> Option<LocalOrderBook> {
+    self.engine.lock().unwrap().books.get(instrument_name).cloned()
+  }
+
+  /// Replace an instrument's book with a snapshot, e.g. from `get_order_book`, and fill the orders it crosses.
+  pub fn load_snapshot(&self, snapshot: &OrderBook) {
+    let mut engine = self.engine.lock().unwrap();
+    let instrument_name = snapshot.instrument_name.clone();
+    engine.books.insert(instrument_name.clone(), LocalOrderBook::from_snapshot(snapshot));
+    if let Some(mark_price) = snapshot.mark_price {
+      engine.marks.insert(instrument_name.clone(), mark_price);
+    }
+    engine.time = Some(snapshot.timestamp);
+    engine.match_resting(&instrument_name);
+    engine.publish(&self.mock);
+  }
+
+  /// Apply a book notification and fill the orders it crosses. Also forwarded to clients subscribed to the instrument's book.
+  /// Books start with a snapshot notification; changes to unknown books are reported as `Applied::Gap` and ignored.
+  pub fn feed_book(&self, change: &BookChange) -> Applied {
+    let mut engine = self.engine.lock().unwrap();
+    let applied = engine.apply_book(change);
+    if applied == Applied::Applied {
+      let channels: Vec<String> = engine.subscribers.keys()
+        .filter(|channel| matches!(channel.parse::<Channel>(), Ok(Channel::Book { instrument_name, .. }) if instrument_name == change.instrument_name))
+        .cloned()
+        .collect();
+      for channel in channels {
+        engine.notify(&self.mock, &channel, serde_json::to_value(change).unwrap_or_default());
+      }
+    }
+    engine.publish(&self.mock);
+    applied
+  }
+
+  /// Feed a recorded notification. Book changes update the book, tickers update the mark price,
+  /// and every notification is forwarded to clients subscribed to its channel.
+  /// - `channel` - e.g. `"book.BTC-PERPETUAL.raw"`
+  /// - `data` - The notification payload.
+  pub fn feed(&self, channel: &str, data: Value) -> Result<(), Error> {
+    let parsed = channel.parse::<Channel>();
+    if let Ok(Channel::Book { .. }) = parsed {
+      self.feed_book(&parse_json::<BookChange>(data)?);
+      return Ok(());
+    }
+    let mut engine = self.engine.lock().unwrap();
+    if let Ok(Channel::Ticker { instrument_name, .. }) = parsed {
+      if let Some(mark_price) = data.get("mark_price").and_then(Value::as_f64) {
+        engine.marks.insert(instrument_name, mark_price);
+      }
+      if let Some(timestamp) = data.get("timestamp").and_then(Value::as_i64) {
+        engine.time = Some(timestamp);
+      }
+    }
+    engine.notify(&self.mock, channel, data);
+    Ok(())
+  }
+
+  /// Feed every notification of a recording, in order. Returns the number of notifications fed.
+  /// - `path` - JSON lines file with one notification per line, either the full JSON-RPC message
+  ///   as received from Deribit or just its `{"channel": ..., "data": ...}` params.
+  pub fn replay(&self, path: impl AsRef<Path>) -> Result<usize, Error> {
+    let recording = std::fs::read_to_string(path)?;
+    let mut count = 0;
+    for line in recording.lines().filter(|line| !line.trim().is_empty()) {
+      let message: Value = serde_json::from_str(line)?;
+      let notification = message.get("params").unwrap_or(&message);
+      let (Some(channel), Some(data)) = (notification.get("channel").and_then(Value::as_str), notification.get("data")) else {
+        return Err(Error::Logic("Recorded line is not a notification"));
+      };
+      self.feed(channel, data.clone())?;
+      count += 1;
+    }
+    Ok(count)
+  }
+
+  /// Serve `method` from the engine, then send the notifications for whatever it changed.
+  fn handle(&self, method: &str, handler: fn(&mut Engine, &MockRequest) -> Result<Value, ApiError>) {
+    let (engine, mock) = (self.engine.clone(), self.mock.clone());
+    self.mock.on(method, move |req| {
+      let mut engine = engine.lock().unwrap();
+      let reply = handler(&mut engine, req);
+      engine.publish(&mock);
+      reply
+    });
+  }
+}
+
+/// How an instrument is sized and settled, inferred from its name.
+#[derive(Debug, Clone, PartialEq)]
+struct Contract {
+  kind: Kind,
+  /// Currency profits, losses, fees and margins are paid in
+  currency: Currency,
+  /// Sized in USD and settled in the base currency, e.g. `BTC-PERPETUAL`. Otherwise sized in the base currency.
+  inverse: bool,
+}
+
+impl Contract {
+  /// e.g. `BTC-PERPETUAL` (inverse future), `BTC_USDC-PERPETUAL` (linear future), `BTC-27DEC24-60000-C` (option), `BTC_USDC` (spot)
+  fn of(instrument_name: &str) -> Contract {
+    let parts: Vec<&str> = instrument_name.split('-').collect();
+    let (base, quote) = match parts[0].split_once('_') {
+      Some((base, quote)) => (base, Some(quote)),
+      None => (parts[0], None),
+    };
+    let kind = match parts.as_slice() {
+      [_] => Kind::Spot,
+      [_, _, _, "C" | "P"] => Kind::Option,
+      _ => Kind::Future,
+    };
+    Contract { kind, currency: Currency::parse(quote.unwrap_or(base)), inverse: kind == Kind::Future && quote.is_none() }
+  }
+
+  /// Value of `amount` at `price`, in the settlement currency.
+  fn notional(&self, amount: f64, price: f64) -> f64 {
+    if self.inverse { amount / price } else { amount * price }
+  }
+
+  /// Size in the base currency.
+  fn delta(&self, size: f64, price: f64) -> f64 {
+    if self.inverse { size / price } else { size }
+  }
+
+  /// Profit of `size` (negative when short) bought at `from` and valued at `to`, in the settlement currency.
+  fn pnl(&self, size: f64, from: f64, to: f64) -> f64 {
+    if self.inverse { size * (1.0 / from - 1.0 / to) } else { size * (to - from) }
+  }
+
+  /// Average price of `size` at `average` plus `amount` at `price`, both unsigned.
+  fn average(&self, size: f64, average: f64, amount: f64, price: f64) -> f64 {
+    if size == 0.0 {
+      price
+    } else if self.inverse {
+      (size + amount) / (size / average + amount / price)
+    } else {
+      (size * average + amount * price) / (size + amount)
+    }
+  }
+}
+
+/// Position on one instrument.
+#[derive(Debug, Clone, Default)]
+struct Holding {
+  /// Negative when short
+  size: f64,
+  average_price: f64,
+  realized: f64,
+}
+
+impl Holding {
+  /// Add a fill of `amount` (negative when selling) at `price`. Returns the realized profit.
+  fn trade(&mut self, contract: &Contract, amount: f64, price: f64) -> f64 {
+    let mut pnl = 0.0;
+    let mut opened = amount;
+    if self.size * amount < 0.0 {
+      // Part of the position that is closed, signed like the position
+      let closed = amount.abs().min(self.size.abs()) * self.size.signum();
+      pnl = contract.pnl(closed, self.average_price, price);
+      self.size -= closed;
+      opened += closed;
+      if self.size == 0.0 {
+        self.average_price = 0.0;
+      }
+    }
+    if opened != 0.0 {
+      self.average_price = contract.average(self.size.abs(), self.average_price, opened.abs(), price);
+      self.size += opened;
+    }
+    self.realized += pnl;
+    pnl
+  }
+}
+
+/// Totals of the positions settled in one currency.
+#[derive(Debug, Clone, Default)]
+struct Funds {
+  balance: f64,
+  floating: f64,
+  realized: f64,
+  futures_pl: f64,
+  options_pl: f64,
+  delta: f64,
+  initial_margin: f64,
+  maintenance_margin: f64,
+}
+
+impl Funds {
+  fn equity(&self) -> f64 {
+    self.balance + self.floating
+  }
+
+  fn available(&self) -> f64 {
+    self.equity() - self.initial_margin
+  }
+}
+
+struct Engine {
+  books: HashMap<String, LocalOrderBook>,
+  /// Mark prices from recorded tickers and snapshots
+  marks: HashMap<String, f64>,
+  last_prices: HashMap<String, f64>,
+  orders: HashMap<String, Order>,
+  /// IDs of the open orders, oldest first, so resting orders fill in time priority
+  open: Vec<String>,
+  holdings: BTreeMap<String, Holding>,
+  /// Deposits plus realized profits, minus fees
+  balances: HashMap<Currency, f64>,
+  /// Connections subscribed to each channel. A channel is dropped once its last connection unsubscribes.
+  subscribers: BTreeMap<String, BTreeSet<u64>>,
+  /// IDs of the orders changed since the last notifications
+  changed: Vec<String>,
+  /// Trades made since the last notifications
+  trades: Vec<Trade>,
+  maker_fee: f64,
+  taker_fee: f64,
+  initial_margin: f64,
+  maintenance_margin: f64,
+  next_id: u64,
+  /// Timestamp of the latest market data, used as the exchange clock
+  time: Option<i64>,
+}
+
+impl Default for Engine {
+  fn default() -> Self {
+    Engine {
+      books: HashMap::new(),
+      marks: HashMap::new(),
+      last_prices: HashMap::new(),
+      orders: HashMap::new(),
+      open: Vec::new(),
+      holdings: BTreeMap::new(),
+      balances: HashMap::new(),
+      subscribers: BTreeMap::new(),
+      changed: Vec::new(),
+      trades: Vec::new(),
+      maker_fee: 0.0,
+      taker_fee: 0.0005,
+      initial_margin: 0.02,
+      maintenance_margin: 0.01,
+      next_id: 0,
+      time: None,
+    }
+  }
+}
+
+#[derive(Deserialize)]
+struct PlaceParams {
+  instrument_name: String,
+  amount: Option<f64>,
+  contracts: Option<f64>,
+  #[serde(rename = "type")]
+  order_type: Option<OrderType>,
+  price: Option<f64>,
+  label: Option<String>,
+  time_in_force: Option<TimeInForce>,
+  max_show: Option<f64>,
+  #[serde(default)]
+  post_only: bool,
+  #[serde(default)]
+  reject_post_only: bool,
+  #[serde(default)]
+  reduce_only: bool,
+  #[serde(default)]
+  mmp: bool,
+}
+
+#[derive(Deserialize)]
+struct EditParams {
+  order_id: String,
+  amount: Option<f64>,
+  contracts: Option<f64>,
+  price: Option<f64>,
+  post_only: Option<bool>,
+  reject_post_only: Option<bool>,
+  reduce_only: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct OrderParams {
+  order_id: String,
+}
+
+#[derive(Deserialize)]
+struct InstrumentParams {
+  instrument_name: String,
+  depth: Option<usize>,
+}
+
+/// Filters of the `get_open_orders*`, `cancel_all_by_*` and `get_positions` methods
+#[derive(Deserialize)]
+struct FilterParams {
+  instrument_name: Option<String>,
+  currency: Option<Currency>,
+  kind: Option<Kind>,
+}
+
+impl FilterParams {
+  fn matches(&self, instrument_name: &str) -> bool {
+    let contract = Contract::of(instrument_name);
+    self.instrument_name.as_ref().is_none_or(|name| name == instrument_name)
+      && self.currency.as_ref().is_none_or(|currency| *currency == Currency::Any || *currency == contract.currency)
+      && self.kind.is_none_or(|kind| kind == contract.kind)
+  }
+}
+
+#[derive(Deserialize)]
+struct SummaryParams {
+  currency: Currency,
+  #[serde(default)]
+  extended: bool,
+}
+
+#[derive(Deserialize)]
+struct SubscribeParams {
+  channels: Vec<String>,
+}
+
+fn params<T: DeserializeOwned>(req: &MockRequest) -> Result<T, ApiError> {
+  serde_json::from_value(req.params.clone()).map_err(|err| ApiError {
+    code: DeribitErrorCode::InvalidParams.code(),
+    message: DeribitErrorCode::InvalidParams.as_str().to_string(),
+    data: Some(serde_json::json!({ "reason": err.to_string() })),
+  })
+}
+
+fn reply<T: Serialize>(result: &T) -> Result<Value, ApiError> {
+  serde_json::to_value(result).map_err(|_| api_error(DeribitErrorCode::InternalServerError))
+}
+
+/// Whether an order at `price` trades against a resting order at `opposite`.
+fn crosses(direction: Direction, price: f64, opposite: f64) -> bool {
+  match direction {
+    Direction::Buy => price >= opposite,
+    Direction::Sell => price <= opposite,
+  }
+}
+
+/// Whether a `user.*` channel target includes an instrument.
+fn covers(target: &Target, instrument_name: &str) -> bool {
+  match target {
+    Target::Instrument(name) => name == instrument_name,
+    Target::Kind(kind, currency) => {
+      let contract = Contract::of(instrument_name);
+      kind.is_none_or(|kind| kind == contract.kind) && (*currency == Currency::Any || *currency == contract.currency)
+    }
+  }
+}
+
+impl Engine {
+  fn now(&self) -> i64 {
+    self.time.unwrap_or_else(|| now_us() / 1000)
+  }
+
+  fn next_id(&mut self) -> u64 {
+    self.next_id += 1;
+    self.next_id
+  }
+
+  /// Recorded mark price, falling back to the book's mid price and then the last fill.
+  fn mark(&self, instrument_name: &str) -> Option<f64> {
+    self.marks.get(instrument_name).copied()
+      .or_else(|| self.books.get(instrument_name).and_then(|book| book.mid_price()))
+      .or_else(|| self.last_prices.get(instrument_name).copied())
+  }
+
+  /// Amount a `direction` order can trade without opening or increasing a position.
+  fn reducible(&self, instrument_name: &str, direction: Direction) -> f64 {
+    let size = self.holdings.get(instrument_name).map_or(0.0, |holding| holding.size);
+    match direction {
+      Direction::Buy => (-size).max(0.0),
+      Direction::Sell => size.max(0.0),
+    }
+  }
+
+  fn funds(&self, currency: &Currency) -> Funds {
+    let mut funds = Funds { balance: self.balances.get(currency).copied().unwrap_or(0.0), ..Funds::default() };
+    for (instrument_name, holding) in &self.holdings {
+      let contract = Contract::of(instrument_name);
+      if contract.currency != *currency {
+        continue;
+      }
+      funds.realized += holding.realized;
+      if holding.size == 0.0 {
+        continue;
+      }
+      let mark = self.mark(instrument_name).unwrap_or(holding.average_price);
+      let floating = contract.pnl(holding.size, holding.average_price, mark);
+      let notional = contract.notional(holding.size.abs(), mark);
+      funds.floating += floating;
+      match contract.kind {
+        Kind::Option | Kind::OptionCombo => funds.options_pl += floating + holding.realized,
+        _ => funds.futures_pl += floating + holding.realized,
+      }
+      funds.delta += contract.delta(holding.size, mark);
+      funds.initial_margin += notional * self.initial_margin;
+      funds.maintenance_margin += notional * self.maintenance_margin;
+    }
+    funds.initial_margin += self.orders_margin(|order| Contract::of(&order.instrument_name).currency == *currency);
+    funds
+  }
+
+  /// Initial margin of the open orders matching `filter`. Reduce-only orders need none.
+  fn orders_margin(&self, filter: impl Fn(&Order) -> bool) -> f64 {
+    self.open.iter()
+      .map(|order_id| &self.orders[order_id])
+      .filter(|order| !order.reduce_only && filter(order))
+      .filter_map(|order| {
+        let price = order.price.or_else(|| self.mark(&order.instrument_name))?;
+        Some(Contract::of(&order.instrument_name).notional(order.amount - order.filled_amount, price) * self.initial_margin)
+      })
+      .sum()
+  }
+
+  /// The price a post-only order rests at: as given, unless it would take liquidity.
+  /// Then it moves to the best price on its own side, or is rejected if `reject_post_only` is set.
+  fn post_only_price(&self, instrument_name: &str, direction: Direction, price: f64, reject_post_only: bool) -> Result<f64, ApiError> {
+    let Some(book) = self.books.get(instrument_name) else { return Ok(price) };
+    let (opposite, own) = match direction {
+      Direction::Buy => (book.best_ask(), book.best_bid()),
+      Direction::Sell => (book.best_bid(), book.best_ask()),
+    };
+    match opposite {
+      Some(best) if crosses(direction, price, best.price) => match own {
+        Some(level) if !reject_post_only => Ok(level.price),
+        _ => Err(api_error(DeribitErrorCode::PostOnlyReject)),
+      },
+      _ => Ok(price),
+    }
+  }
+
+  fn place(&mut self, direction: Direction, req: &MockRequest) -> Result<Value, ApiError> {
+    let params: PlaceParams = params(req)?;
+    let amount = params.amount.or(params.contracts)
+      .filter(|amount| *amount > 0.0)
+      .ok_or_else(|| api_error(DeribitErrorCode::InvalidAmount))?;
+    let order_type = params.order_type.unwrap_or(OrderType::Limit);
+    let Some(book) = self.books.get(&params.instrument_name) else {
+      return Err(api_error(DeribitErrorCode::InvalidOrUnsupportedInstrument));
+    };
+    let opposite = match direction {
+      Direction::Buy => book.best_ask(),
+      Direction::Sell => book.best_bid(),
+    };
+    let price = match order_type {
+      OrderType::Limit => {
+        let price = params.price.ok_or_else(|| api_error(DeribitErrorCode::InvalidPrice))?;
+        if params.post_only {
+          Some(self.post_only_price(&params.instrument_name, direction, price, params.reject_post_only)?)
+        } else {
+          Some(price)
+        }
+      }
+      OrderType::Market | OrderType::MarketLimit => None,
+      _ => return Err(api_error(DeribitErrorCode::NotImplemented)),
+    };
+
+    let reducible = self.reducible(&params.instrument_name, direction);
+    let amount = if params.reduce_only { amount.min(reducible) } else { amount };
+    let contract = Contract::of(&params.instrument_name);
+    let reference = price.or(opposite.map(|level| level.price)).or_else(|| self.mark(&params.instrument_name));
+    if let (false, Some(reference)) = (params.reduce_only, reference) {
+      let required = contract.notional((amount - reducible).max(0.0), reference) * self.initial_margin;
+      if required > 0.0 && self.funds(&contract.currency).available() < required {
+        return Err(api_error(DeribitErrorCode::NotEnoughFunds));
+      }
+    }
+
+    let now = self.now();
+    let order_id = format!("paper-{}", self.next_id());
+    let order = Order {
+      order_id: order_id.clone(),
+      order_state: OrderState::Open,
+      order_type,
+      instrument_name: params.instrument_name,
+      direction,
+      price,
+      amount,
+      contracts: None,
+      filled_amount: 0.0,
+      average_price: None,
+      label: params.label.unwrap_or_default(),
+      time_in_force: params.time_in_force.unwrap_or(TimeInForce::GoodTilCancelled),
+      post_only: params.post_only,
+      reject_post_only: params.reject_post_only,
+      reduce_only: params.reduce_only,
+      creation_timestamp: now,
+      last_update_timestamp: now,
+      api: true,
+      web: false,
+      replaced: false,
+      is_liquidation: false,
+      mmp: params.mmp,
+      max_show: params.max_show,
+      trigger: None,
+      trigger_price: None,
+      trigger_offset: None,
+      triggered: None,
+      advanced: None,
+      usd: None,
+      implv: None,
+      profit_loss: None,
+      cancel_reason: None,
+    };
+    self.orders.insert(order_id.clone(), order);
+    self.changed.push(order_id.clone());
+    let trades = if amount > 0.0 {
+      self.execute(&order_id)
+    } else {
+      // Nothing left to reduce
+      self.close(&order_id, "reduce_only");
+      Vec::new()
+    };
+    reply(&serde_json::json!({ "order": self.orders[&order_id], "trades": trades }))
+  }
+
+  /// Match an order as a taker, then rest what is left or cancel it, depending on its type and time in force.
+  fn execute(&mut self, order_id: &str) -> Vec<Trade> {
+    let order = &self.orders[order_id];
+    let (instrument_name, direction, limit) = (order.instrument_name.clone(), order.direction, order.price);
+    let (order_type, time_in_force, post_only) = (order.order_type, order.time_in_force, order.post_only);
+    let remaining = order.amount - order.filled_amount;
+    let fills = match self.books.get_mut(&instrument_name) {
+      Some(_) if post_only => Vec::new(),
+      Some(book) if time_in_force == TimeInForce::FillOrKill => {
+        let fillable: f64 = book.clone().take(direction, limit, remaining).iter().map(|level| level.amount).sum();
+        if fillable < remaining { Vec::new() } else { book.take(direction, limit, remaining) }
+      }
+      Some(book) => book.take(direction, limit, remaining),
+      None => Vec::new(),
+    };
+    let trades: Vec<Trade> = fills.into_iter()
+      .map(|fill| self.fill(order_id, fill.price, fill.amount, Liquidity::Taker))
+      .collect();
+    if self.orders[order_id].order_state != OrderState::Open {
+      return trades;
+    }
+    match (order_type, time_in_force) {
+      (OrderType::Market, _) | (_, TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill) => {
+        self.close(order_id, "immediate_or_cancel");
+      }
+      // The rest of a market limit order rests at the price of its last fill
+      (OrderType::MarketLimit, _) => match trades.last() {
+        Some(last) => {
+          self.orders.get_mut(order_id).unwrap().price = Some(last.price);
+          self.open.push(order_id.to_string());
+        }
+        None => self.close(order_id, "immediate_or_cancel"),
+      },
+      _ => {
+        if !self.open.iter().any(|id| id == order_id) {
+          self.open.push(order_id.to_string());
+        }
+      }
+    }
+    trades
+  }
+
+  /// Fill resting orders on an instrument that the book now crosses, at their own price.
+  fn match_resting(&mut self, instrument_name: &str) {
+    let resting: Vec<String> = self.open.iter()
+      .filter(|order_id| self.orders[*order_id].instrument_name == instrument_name)
+      .cloned()
+      .collect();
+    for order_id in resting {
+      let order = &self.orders[&order_id];
+      let (direction, reduce_only) = (order.direction, order.reduce_only);
+      let Some(price) = order.price else { continue };
+      let mut remaining = order.amount - order.filled_amount;
+      if reduce_only {
+        remaining = remaining.min(self.reducible(instrument_name, direction));
+        if remaining <= 0.0 {
+          self.close(&order_id, "reduce_only");
+          continue;
+        }
+      }
+      let Some(book) = self.books.get_mut(instrument_name) else { return };
+      let taken: f64 = book.take(direction, Some(price), remaining).iter().map(|level| level.amount).sum();
+      if taken > 0.0 {
+        self.fill(&order_id, price, taken, Liquidity::Maker);
+      }
+    }
+  }
+
+  /// Record a fill of an order: update the order, position and balance, and make the trade.
+  fn fill(&mut self, order_id: &str, price: f64, amount: f64, liquidity: Liquidity) -> Trade {
+    let now = self.now();
+    let trade_seq = self.next_id();
+    let order = self.orders.get_mut(order_id).unwrap();
+    let average_price = order.average_price.unwrap_or(0.0);
+    order.average_price = Some((average_price * order.filled_amount + price * amount) / (order.filled_amount + amount));
+    order.filled_amount += amount;
+    if order.filled_amount >= order.amount {
+      order.order_state = OrderState::Filled;
+    }
+    order.last_update_timestamp = now;
+    let order = order.clone();
+    if order.order_state == OrderState::Filled {
+      self.open.retain(|id| id != order_id);
+    }
+    if !self.changed.iter().any(|id| id == order_id) {
+      self.changed.push(order_id.to_string());
+    }
+
+    let contract = Contract::of(&order.instrument_name);
+    let signed = match order.direction {
+      Direction::Buy => amount,
+      Direction::Sell => -amount,
+    };
+    let pnl = self.holdings.entry(order.instrument_name.clone()).or_default().trade(&contract, signed, price);
+    let rate = match liquidity {
+      Liquidity::Maker => self.maker_fee,
+      Liquidity::Taker => self.taker_fee,
+    };
+    let fee = contract.notional(amount, price) * rate;
+    *self.balances.entry(contract.currency.clone()).or_default() += pnl - fee;
+
+    let tick_direction = match self.last_prices.insert(order.instrument_name.clone(), price) {
+      Some(last) if price < last => 2,
+      Some(last) if price == last => 1,
+      _ => 0,
+    };
+    let mark = self.mark(&order.instrument_name).unwrap_or(price);
+    let trade = Trade {
+      trade_id: format!("paper-trade-{}", trade_seq),
+      trade_seq: trade_seq as i64,
+      order_id: order.order_id,
+      instrument_name: order.instrument_name,
+      direction: order.direction,
+      price,
+      amount,
+      contracts: None,
+      fee,
+      fee_currency: contract.currency,
+      timestamp: now,
+      index_price: mark,
+      mark_price: mark,
+      state: order.order_state,
+      order_type: order.order_type,
+      tick_direction,
+      liquidity: Some(liquidity),
+      label: Some(order.label).filter(|label| !label.is_empty()),
+      post_only: Some(order.post_only),
+      reduce_only: Some(order.reduce_only),
+      mmp: Some(order.mmp),
+      api: Some(true),
+      matching_id: None,
+      block_trade_id: None,
+      combo_id: None,
+      profit_loss: Some(pnl),
+      iv: None,
+      underlying_price: None,
+    };
+    self.trades.push(trade.clone());
+    trade
+  }
+
+  /// Cancel an open order.
+  fn close(&mut self, order_id: &str, reason: &str) {
+    let now = self.now();
+    let order = self.orders.get_mut(order_id).unwrap();
+    order.order_state = OrderState::Cancelled;
+    order.cancel_reason = Some(reason.to_string());
+    order.last_update_timestamp = now;
+    self.open.retain(|id| id != order_id);
+    if !self.changed.iter().any(|id| id == order_id) {
+      self.changed.push(order_id.to_string());
+    }
+  }
+
+  fn open_order(&self, order_id: &str) -> Result<&Order, ApiError> {
+    match self.orders.get(order_id) {
+      None => Err(api_error(DeribitErrorCode::OrderNotFound)),
+      Some(order) if order.order_state != OrderState::Open => Err(api_error(DeribitErrorCode::NotOpenOrder)),
+      Some(order) => Ok(order),
+    }
+  }
+
+  fn edit(&mut self, req: &MockRequest) -> Result<Value, ApiError> {
+    let params: EditParams = params(req)?;
+    let order = self.open_order(&params.order_id)?;
+    let amount = params.amount.or(params.contracts).unwrap_or(order.amount);
+    if amount < order.filled_amount {
+      return Err(api_error(DeribitErrorCode::InvalidAmount));
+    }
+    let post_only = params.post_only.unwrap_or(order.post_only);
+    let reject_post_only = params.reject_post_only.unwrap_or(order.reject_post_only);
+    let price = match (params.price.or(order.price), post_only) {
+      (Some(price), true) => Some(self.post_only_price(&order.instrument_name, order.direction, price, reject_post_only)?),
+      (price, _) => price,
+    };
+    let now = self.now();
+    let order = self.orders.get_mut(&params.order_id).unwrap();
+    order.amount = amount;
+    order.price = price;
+    order.post_only = post_only;
+    order.reject_post_only = reject_post_only;
+    order.reduce_only = params.reduce_only.unwrap_or(order.reduce_only);
+    order.replaced = true;
+    order.last_update_timestamp = now;
+    if order.filled_amount >= amount {
+      order.order_state = OrderState::Filled;
+      self.open.retain(|id| *id != params.order_id);
+    }
+    if !self.changed.contains(&params.order_id) {
+      self.changed.push(params.order_id.clone());
+    }
+    let trades = if self.orders[&params.order_id].order_state == OrderState::Open { self.execute(&params.order_id) } else { Vec::new() };
+    reply(&serde_json::json!({ "order": self.orders[&params.order_id], "trades": trades }))
+  }
+
+  fn cancel(&mut self, req: &MockRequest) -> Result<Value, ApiError> {
+    let params: OrderParams = params(req)?;
+    self.open_order(&params.order_id)?;
+    self.close(&params.order_id, "user_request");
+    reply(&self.orders[&params.order_id])
+  }
+
+  /// Cancel the open orders matching `filter`. Returns how many were cancelled.
+  fn cancel_where(&mut self, filter: impl Fn(&Order) -> bool) -> u64 {
+    let cancelled: Vec<String> = self.open.iter().filter(|order_id| filter(&self.orders[*order_id])).cloned().collect();
+    for order_id in &cancelled {
+      self.close(order_id, "user_request");
+    }
+    cancelled.len() as u64
+  }
+
+  fn cancel_all_by_currency(&mut self, req: &MockRequest) -> Result<Value, ApiError> {
+    let filter: FilterParams = params(req)?;
+    Ok(Value::from(self.cancel_where(|order| filter.matches(&order.instrument_name))))
+  }
+
+  fn cancel_all_by_instrument(&mut self, req: &MockRequest) -> Result<Value, ApiError> {
+    let filter: FilterParams = params(req)?;
+    if filter.instrument_name.is_none() {
+      return Err(api_error(DeribitErrorCode::MissingParams));
+    }
+    Ok(Value::from(self.cancel_where(|order| filter.matches(&order.instrument_name))))
+  }
+
+  fn order_state(&mut self, req: &MockRequest) -> Result<Value, ApiError> {
+    let params: OrderParams = params(req)?;
+    let order = self.orders.get(&params.order_id).ok_or_else(|| api_error(DeribitErrorCode::OrderNotFound))?;
+    reply(order)
+  }
+
+  fn open_orders(&mut self, req: &MockRequest) -> Result<Value, ApiError> {
+    let filter: FilterParams = params(req)?;
+    let orders: Vec<&Order> = self.open.iter()
+      .map(|order_id| &self.orders[order_id])
+      .filter(|order| filter.matches(&order.instrument_name))
+      .collect();
+    reply(&orders)
+  }
+
+  fn position_json(&self, instrument_name: &str, holding: &Holding) -> Value {
+    let contract = Contract::of(instrument_name);
+    let mark = self.mark(instrument_name).unwrap_or(holding.average_price);
+    let (floating, notional) = if holding.size == 0.0 {
+      (0.0, 0.0)
+    } else {
+      (contract.pnl(holding.size, holding.average_price, mark), contract.notional(holding.size.abs(), mark))
+    };
+    let direction = match holding.size {
+      size if size > 0.0 => "buy",
+      size if size < 0.0 => "sell",
+      _ => "zero",
+    };
+    let delta = if mark > 0.0 { contract.delta(holding.size, mark) } else { 0.0 };
+    serde_json::json!({
+      "instrument_name": instrument_name,
+      "kind": contract.kind,
+      "direction": direction,
+      "size": holding.size,
+      "size_currency": delta,
+      "average_price": holding.average_price,
+      "mark_price": mark,
+      "index_price": mark,
+      "delta": delta,
+      "floating_profit_loss": floating,
+      "realized_profit_loss": holding.realized,
+      "total_profit_loss": floating + holding.realized,
+      "initial_margin": notional * self.initial_margin,
+      "maintenance_margin": notional * self.maintenance_margin,
+      "open_orders_margin": self.orders_margin(|order| order.instrument_name == instrument_name),
+    })
+  }
+
+  fn position(&mut self, req: &MockRequest) -> Result<Value, ApiError> {
+    let params: InstrumentParams = params(req)?;
+    if !self.books.contains_key(&params.instrument_name) && !self.holdings.contains_key(&params.instrument_name) {
+      return Err(api_error(DeribitErrorCode::InvalidOrUnsupportedInstrument));
+    }
+    let holding = self.holdings.get(&params.instrument_name).cloned().unwrap_or_default();
+    Ok(self.position_json(&params.instrument_name, &holding))
+  }
+
+  fn positions(&mut self, req: &MockRequest) -> Result<Value, ApiError> {
+    let filter: FilterParams = params(req)?;
+    let positions: Vec<Value> = self.holdings.iter()
+      .filter(|(instrument_name, _)| filter.matches(instrument_name))
+      .map(|(instrument_name, holding)| self.position_json(instrument_name, holding))
+      .collect();
+    Ok(Value::from(positions))
+  }
+
+  fn account_summary(&mut self, req: &MockRequest) -> Result<Value, ApiError> {
+    let params: SummaryParams = params(req)?;
+    let funds = self.funds(&params.currency);
+    let mut summary = serde_json::json!({
+      "currency": params.currency,
+      "balance": funds.balance,
+      "equity": funds.equity(),
+      "available_funds": funds.available(),
+      "available_withdrawal_funds": funds.available().min(funds.balance).max(0.0),
+      "margin_balance": funds.equity(),
+      "initial_margin": funds.initial_margin,
+      "maintenance_margin": funds.maintenance_margin,
+      "session_rpl": funds.realized,
+      "session_upl": funds.floating,
+      "total_pl": funds.realized + funds.floating,
+      "futures_pl": funds.futures_pl,
+      "options_pl": funds.options_pl,
+      "delta_total": funds.delta,
+    });
+    if params.extended {
+      summary["id"] = Value::from(1);
+      summary["username"] = Value::from("paper");
+      summary["system_name"] = Value::from("paper");
+      summary["type"] = Value::from("main");
+    }
+    Ok(summary)
+  }
+
+  fn order_book(&mut self, req: &MockRequest) -> Result<Value, ApiError> {
+    let params: InstrumentParams = params(req)?;
+    let book = self.books.get(&params.instrument_name).ok_or_else(|| api_error(DeribitErrorCode::InvalidOrUnsupportedInstrument))?;
+    let depth = params.depth.unwrap_or(usize::MAX);
+    let (best_bid, best_ask) = (book.best_bid(), book.best_ask());
+    let mark = self.mark(&params.instrument_name);
+    reply(&serde_json::json!({
+      "instrument_name": book.instrument_name,
+      "timestamp": book.timestamp,
+      "change_id": book.change_id,
+      "state": "open",
+      "bids": book.bids().take(depth).collect::<Vec<Level>>(),
+      "asks": book.asks().take(depth).collect::<Vec<Level>>(),
+      "best_bid_price": best_bid.map(|level| level.price),
+      "best_bid_amount": best_bid.map(|level| level.amount),
+      "best_ask_price": best_ask.map(|level| level.price),
+      "best_ask_amount": best_ask.map(|level| level.amount),
+      "mark_price": mark,
+      "index_price": mark,
+      "last_price": self.last_prices.get(&params.instrument_name),
+    }))
+  }
+
+  fn subscribe(&mut self, req: &MockRequest) -> Result<Value, ApiError> {
+    let params: SubscribeParams = params(req)?;
+    for channel in &params.channels {
+      self.subscribers.entry(channel.clone()).or_default().insert(req.connection);
+    }
+    reply(&params.channels)
+  }
+
+  fn unsubscribe(&mut self, req: &MockRequest) -> Result<Value, ApiError> {
+    let params: SubscribeParams = params(req)?;
+    for channel in &params.channels {
+      if let Some(connections) = self.subscribers.get_mut(channel) {
+        connections.remove(&req.connection);
+        if connections.is_empty() {
+          self.subscribers.remove(channel);
+        }
+      }
+    }
+    reply(&params.channels)
+  }
+
+  /// Send a notification to the connections subscribed to `channel`, forgetting the ones that are closed.
+  fn notify(&mut self, mock: &MockDeribit, channel: &str, data: Value) {
+    let Some(connections) = self.subscribers.get_mut(channel) else { return };
+    connections.retain(|connection| mock.notify_connection(*connection, channel, data.clone()));
+    if connections.is_empty() {
+      self.subscribers.remove(channel);
+    }
+  }
+
+  /// Apply a book notification and fill the resting orders it crosses.
+  fn apply_book(&mut self, change: &BookChange) -> Applied {
+    let book = match (self.books.get_mut(&change.instrument_name), change.kind) {
+      (Some(book), _) => book,
+      (None, BookChangeKind::Snapshot) => self.books.entry(change.instrument_name.clone()).or_insert_with(|| LocalOrderBook::new(&change.instrument_name)),
+      (None, BookChangeKind::Change) => return Applied::Gap,
+    };
+    let applied = book.apply(change);
+    if applied == Applied::Applied {
+      self.time = Some(change.timestamp);
+      self.match_resting(&change.instrument_name);
+    }
+    applied
+  }
+
+  /// Send the `user.orders` and `user.trades` notifications for the changes since the last call,
+  /// on every subscribed channel that covers them.
+  fn publish(&mut self, mock: &MockDeribit) {
+    let orders: Vec<Order> = self.changed.drain(..).filter_map(|order_id| self.orders.get(&order_id).cloned()).collect();
+    let trades = std::mem::take(&mut self.trades);
+    if orders.is_empty() && trades.is_empty() {
+      return;
+    }
+    let channels: Vec<String> = self.subscribers.keys().cloned().collect();
+    for channel in channels {
+      match channel.parse::<Channel>() {
+        Ok(Channel::UserOrders { target, interval }) => {
+          let orders: Vec<&Order> = orders.iter().filter(|order| covers(&target, &order.instrument_name)).collect();
+          let data: Vec<Value> = if interval == Interval::Raw {
+            orders.iter().map(|order| serde_json::to_value(order).unwrap_or_default()).collect()
+          } else if !orders.is_empty() {
+            vec![serde_json::json!(orders)]
+          } else {
+            Vec::new()
+          };
+          for data in data {
+            self.notify(mock, &channel, data);
+          }
+        }
+        Ok(Channel::UserTrades { target, .. }) => {
+          let trades: Vec<&Trade> = trades.iter().filter(|trade| covers(&target, &trade.instrument_name)).collect();
+          if !trades.is_empty() {
+            let data = serde_json::json!(trades);
+            self.notify(mock, &channel, data);
+          }
+        }
+        _ => {}
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::*;
+  use crate::trading::OrderRequest;
+
+  const INSTRUMENT: &str = "BTC_USDC-PERPETUAL";
+
+  fn book(kind: &str, change_id: i64, bids: &[(&str, f64, f64)], asks: &[(&str, f64, f64)]) -> BookChange {
+    serde_json::from_value(serde_json::json!({
+      "type": kind,
+      "instrument_name": INSTRUMENT,
+      "timestamp": 1700000000000i64 + change_id,
+      "change_id": change_id,
+      "prev_change_id": change_id - 1,
+      "bids": bids,
+      "asks": asks,
+    })).unwrap()
+  }
+
+  /// An exchange with 10 000 USDC, 1 bp maker and 10 bp taker fees, and a book of 99 / 100.
+  fn exchange() -> PaperExchange {
+    let paper = PaperExchange::new().fees(0.0001, 0.001);
+    paper.deposit(Currency::USDC, 10_000.0);
+    paper.feed_book(&book("snapshot", 1, &[("new", 99.0, 1.0)], &[("new", 100.0, 1.0), ("new", 101.0, 2.0)]));
+    paper
+  }
+
+  fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+  }
+
+  #[tokio::test]
+  async fn takers_walk_the_book_and_pay_taker_fees() {
+    let paper = exchange();
+    let mut client = paper.private_client().await.unwrap();
+    let response = client.buy(INSTRUMENT, OrderRequest::market(2.0)).await.unwrap();
+    assert_eq!(response.order.order_state, OrderState::Filled);
+    assert_eq!(response.order.average_price, Some(100.5));
+    let fills: Vec<(f64, f64)> = response.trades.iter().map(|trade| (trade.price, trade.amount)).collect();
+    assert_eq!(fills, vec![(100.0, 1.0), (101.0, 1.0)]);
+    assert!(response.trades.iter().all(|trade| trade.liquidity == Some(Liquidity::Taker)));
+    assert_close(response.trades.iter().map(|trade| trade.fee).sum(), 0.201);
+
+    let position = client.get_position(INSTRUMENT).await.unwrap();
+    assert_eq!(position.size, 2.0);
+    assert_eq!(position.average_price, 100.5);
+    let summary = client.get_account_summary(Currency::USDC, false).await.unwrap();
+    assert_close(summary.balance, 10_000.0 - 0.201);
+    // The liquidity taken is gone from the book
+    assert_eq!(paper.book(INSTRUMENT).unwrap().best_ask().map(|level| (level.price, level.amount)), Some((101.0, 1.0)));
+  }
+
+  #[tokio::test]
+  async fn resting_orders_fill_as_makers_once_the_market_trades_through() {
+    let paper = exchange();
+    let mut client = paper.private_client().await.unwrap();
+    let response = client.sell(INSTRUMENT, OrderRequest::limit(1.0, 100.5)).await.unwrap();
+    assert_eq!(response.order.order_state, OrderState::Open);
+    assert!(response.trades.is_empty());
+
+    paper.feed_book(&book("change", 2, &[("new", 100.5, 3.0)], &[]));
+    let order = client.get_order_state(&response.order.order_id).await.unwrap();
+    assert_eq!(order.order_state, OrderState::Filled);
+    assert_eq!(order.average_price, Some(100.5));
+    let position = client.get_position(INSTRUMENT).await.unwrap();
+    assert_eq!(position.size, -1.0);
+    let summary = client.get_account_summary(Currency::USDC, false).await.unwrap();
+    assert_close(summary.balance, 10_000.0 - 100.5 * 0.0001);
+  }
+
+  #[tokio::test]
+  async fn reduce_only_orders_never_open_or_flip_a_position() {
+    let paper = exchange();
+    let mut client = paper.private_client().await.unwrap();
+    let response = client.sell(INSTRUMENT, OrderRequest::market(1.0).reduce_only(true)).await.unwrap();
+    assert_eq!(response.order.order_state, OrderState::Cancelled);
+    assert_eq!(response.order.cancel_reason.as_deref(), Some("reduce_only"));
+    assert!(response.trades.is_empty());
+
+    client.buy(INSTRUMENT, OrderRequest::market(1.0)).await.unwrap();
+    let response = client.sell(INSTRUMENT, OrderRequest::market(3.0).reduce_only(true)).await.unwrap();
+    assert_eq!(response.order.filled_amount, 1.0);
+    assert_eq!(client.get_position(INSTRUMENT).await.unwrap().size, 0.0);
+  }
+
+  #[tokio::test]
+  async fn post_only_orders_never_take_liquidity() {
+    let paper = exchange();
+    let mut client = paper.private_client().await.unwrap();
+    let response = client.buy(INSTRUMENT, OrderRequest::limit(1.0, 100.0).post_only(true)).await.unwrap();
+    assert!(response.trades.is_empty());
+    assert_eq!(response.order.order_state, OrderState::Open);
+    assert_eq!(response.order.price, Some(99.0));
+
+    let err = client.buy(INSTRUMENT, OrderRequest::limit(1.0, 100.0).post_only(true).reject_post_only(true)).await.unwrap_err();
+    assert_eq!(err.api_code(), Some(DeribitErrorCode::PostOnlyReject));
+  }
+
+  #[tokio::test]
+  async fn closing_a_position_realizes_its_profit() {
+    let paper = exchange();
+    let mut client = paper.private_client().await.unwrap();
+    client.buy(INSTRUMENT, OrderRequest::market(1.0)).await.unwrap();
+    paper.feed_book(&book("change", 2, &[("new", 110.0, 1.0)], &[]));
+    let floating = client.get_position(INSTRUMENT).await.unwrap();
+    assert!(floating.floating_profit_loss > 0.0);
+
+    let response = client.sell(INSTRUMENT, OrderRequest::market(1.0)).await.unwrap();
+    assert_eq!(response.trades[0].price, 110.0);
+    assert_eq!(response.trades[0].profit_loss, Some(10.0));
+    let summary = client.get_account_summary(Currency::USDC, false).await.unwrap();
+    assert_close(summary.session_rpl.unwrap(), 10.0);
+    assert_close(summary.balance, 10_000.0 + 10.0 - (100.0 + 110.0) * 0.001);
+  }
+
+  #[tokio::test]
+  async fn notifications_only_reach_the_subscribed_connection() {
+    let paper = exchange();
+    let mut trader = paper.private_client().await.unwrap();
+    let mut watcher = paper.private_client().await.unwrap();
+    let orders_channel = format!("user.orders.{}.raw", INSTRUMENT);
+    let book_channel = format!("book.{}.agg2", INSTRUMENT);
+    let mut orders = trader.subscribe::<Value>(&[&orders_channel]).await.unwrap();
+    let watched_orders = watcher.subscribe::<Value>(&[&orders_channel]).await.unwrap();
+    let mut books = watcher.subscribe::<Value>(&[&book_channel]).await.unwrap();
+    drop(watched_orders);
+    paper.mock().wait_for("private/unsubscribe", 1, Duration::from_secs(1)).await.unwrap();
+
+    trader.buy(INSTRUMENT, OrderRequest::limit(1.0, 98.0)).await.unwrap();
+    let update = tokio::time::timeout(Duration::from_secs(1), orders.recv()).await.unwrap().unwrap().unwrap();
+    assert_eq!(update.data["order_state"], "open");
+
+    // The watcher unsubscribed from the orders, which must not end the trader's subscription
+    trader.buy(INSTRUMENT, OrderRequest::limit(1.0, 97.0)).await.unwrap();
+    let update = tokio::time::timeout(Duration::from_secs(1), orders.recv()).await.unwrap().unwrap().unwrap();
+    assert_eq!(update.data["price"], 97.0);
+
+    // Book changes reach every interval subscribed to, and only the connection that subscribed
+    paper.feed_book(&book("change", 2, &[("new", 98.5, 1.0)], &[]));
+    let update = tokio::time::timeout(Duration::from_secs(1), books.recv()).await.unwrap().unwrap().unwrap();
+    assert_eq!(update.data["change_id"], 2);
+  }
+
+  #[tokio::test]
+  async fn clients_are_not_rate_limited() {
+    let paper = exchange();
+    let mut client = paper.private_client().await.unwrap();
+    let started = std::time::Instant::now();
+    for _ in 0..200 {
+      client.get_position(INSTRUMENT).await.unwrap();
+    }
+    assert!(started.elapsed() < Duration::from_secs(2));
+  }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::{Error, PrivateClient, SocketClient};
+
+/// What cancel-on-disconnect applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodScope {
+  /// Cancel the orders of this connection when it drops.
+  Connection,
+  /// Cancel every order of the account when any of its connections drops.
+  Account,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelOnDisconnect {
+  pub scope: CodScope,
+  pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerVersion {
+  /// The API version, e.g. `"1.2.26"`
+  pub version: String,
+}
+
+impl SocketClient {
+  /// Identifies the client to the server. The identification is sent again after every reconnect.
+  /// - `client_name` - e.g. `"my-trading-bot"`
+  /// - `client_version` - e.g. `"1.0.0"`
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-hello)
+  pub async fn hello(&self, client_name: &str, client_version: &str) -> Result<ServerVersion, Error> {
+    let params = serde_json::json!({ "client_name": client_name, "client_version": client_version });
+    let version = self.call("public/hello", params).await?;
+    self.session.lock().unwrap().hello = Some((client_name.to_string(), client_version.to_string()));
+    Ok(version)
+  }
+}
+
+impl PrivateClient {
+  /// Cancels the user's orders when the connection drops. The setting is re-applied after every reconnect,
+  /// so it holds for the whole lifetime of the client. Requires `account:read_write`.
+  /// - `scope` - Cancel the orders of this connection only, or of the whole account.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-enable_cancel_on_disconnect)
+  pub async fn enable_cancel_on_disconnect(&mut self, scope: CodScope) -> Result<(), Error> {
+    self.scope().account().require_write("account:read_write")?;
+    let params = serde_json::json!({ "scope": scope });
+    self.authed_request("private/enable_cancel_on_disconnect", params).await?.value()?;
+    self.session.lock().unwrap().cancel_on_disconnect = Some(scope);
+    Ok(())
+  }
+
+  /// Stops cancelling orders when the connection drops, and stops re-applying it after reconnects if it was enabled with the same scope.
+  /// Requires `account:read_write`.
+  /// - `scope` - The scope to disable cancel-on-disconnect for.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-disable_cancel_on_disconnect)
+  pub async fn disable_cancel_on_disconnect(&mut self, scope: CodScope) -> Result<(), Error> {
+    self.scope().account().require_write("account:read_write")?;
+    let params = serde_json::json!({ "scope": scope });
+    self.authed_request("private/disable_cancel_on_disconnect", params).await?.value()?;
+    let mut session = self.session.lock().unwrap();
+    if session.cancel_on_disconnect == Some(scope) {
+      session.cancel_on_disconnect = None;
+    }
+    Ok(())
+  }
+
+  /// Retrieves the server's cancel-on-disconnect setting.
+  /// - `scope` - The scope to check.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_cancel_on_disconnect)
+  pub async fn get_cancel_on_disconnect(&mut self, scope: CodScope) -> Result<CancelOnDisconnect, Error> {
+    let params = serde_json::json!({ "scope": scope });
+    self.authed_call("private/get_cancel_on_disconnect", params).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+  use super::*;
+  use crate::core::{ConnectionState, Reconnect, Scope};
+  use crate::mock::MockDeribit;
+
+  #[tokio::test]
+  async fn disabling_another_scope_keeps_cancel_on_disconnect() {
+    let mock = MockDeribit::new();
+    mock.respond("private/enable_cancel_on_disconnect", serde_json::json!("ok"));
+    mock.respond("private/disable_cancel_on_disconnect", serde_json::json!("ok"));
+    let mut client = mock.private_client().await.unwrap();
+    client.enable_cancel_on_disconnect(CodScope::Account).await.unwrap();
+    assert_eq!(client.session.lock().unwrap().cancel_on_disconnect, Some(CodScope::Account));
+
+    client.disable_cancel_on_disconnect(CodScope::Connection).await.unwrap();
+    assert_eq!(mock.assert_requested("private/disable_cancel_on_disconnect").params["scope"], "connection");
+    assert_eq!(client.session.lock().unwrap().cancel_on_disconnect, Some(CodScope::Account));
+    client.disable_cancel_on_disconnect(CodScope::Account).await.unwrap();
+    assert_eq!(client.session.lock().unwrap().cancel_on_disconnect, None);
+  }
+
+  #[tokio::test]
+  async fn cancel_on_disconnect_is_restored_until_disabled() {
+    let mock = MockDeribit::new();
+    mock.respond("public/hello", serde_json::json!({ "version": "1.2.26" }));
+    mock.respond("private/enable_cancel_on_disconnect", serde_json::json!("ok"));
+    mock.respond("private/disable_cancel_on_disconnect", serde_json::json!("ok"));
+    let reconnect = Reconnect { initial_delay: Duration::from_millis(10), max_delay: Duration::from_millis(50), ..Reconnect::default() };
+    let socket = mock.reconnecting_client(reconnect).await;
+    let mut state = socket.state();
+    socket.hello("my-trading-bot", "1.0.0").await.unwrap();
+    let mut client = socket.authenticated("mock_client_id", "mock_client_secret", Scope::default()).await.unwrap();
+    client.enable_cancel_on_disconnect(CodScope::Account).await.unwrap();
+
+    mock.disconnect();
+    let restored = mock.wait_for("private/enable_cancel_on_disconnect", 2, Duration::from_secs(1)).await.expect("cancel-on-disconnect was not restored");
+    assert_eq!(restored.params["scope"], "account");
+    let hello = mock.assert_requested("public/hello");
+    assert_eq!((hello.connection, hello.params["client_name"].as_str()), (restored.connection, Some("my-trading-bot")));
+    tokio::time::timeout(Duration::from_secs(1), state.wait_for(|state| *state == ConnectionState::Connected)).await.unwrap().unwrap();
+
+    client.disable_cancel_on_disconnect(CodScope::Account).await.unwrap();
+    mock.disconnect();
+    // The refresh is sent on the new connection right before cancel-on-disconnect would be
+    mock.wait_for("public/auth", 3, Duration::from_secs(1)).await.expect("the client did not reconnect");
+    tokio::time::timeout(Duration::from_secs(1), state.wait_for(|state| *state == ConnectionState::Connected)).await.unwrap().unwrap();
+    assert_eq!(mock.requests_to("private/enable_cancel_on_disconnect").len(), 2);
+  }
+}
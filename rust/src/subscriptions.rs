@@ -0,0 +1,234 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_util::Stream;
+use serde::de::DeserializeOwned;
+
+use crate::channels::patterns;
+use crate::core::{notification_channel, parse_json, Backpressure, Error, ListenerId, Notification, NotificationReceiver, PrivateClient, SharedAuth, SocketClient};
+
+/// Number of notifications buffered per subscription before its backpressure policy applies.
+pub const DEFAULT_BUFFER: usize = 1024;
+
+/// A notification received on a subscribed channel, with its payload parsed into `T`.
+#[derive(Debug, Clone)]
+pub struct Update<T> {
+  pub channel: String,
+  pub data: T,
+}
+
+/// Stream of notifications for a set of channels. Dropping it unsubscribes from all of them.
+pub struct Subscription<T> {
+  channels: Vec<String>,
+  listeners: Vec<ListenerId>,
+  receiver: NotificationReceiver,
+  client: SocketClient,
+  unsubscribe_method: &'static str,
+  /// The credentials of a private subscription, read again when unsubscribing since the token may have been refreshed since
+  auth: Option<SharedAuth>,
+  parse: fn(Notification) -> Result<Update<T>, Error>,
+}
+
+impl<T> Subscription<T> {
+  /// The channels confirmed by the server for this subscription.
+  pub fn channels(&self) -> &[String] {
+    &self.channels
+  }
+
+  /// Number of notifications dropped or conflated so far because this subscription fell behind.
+  pub fn dropped(&self) -> u64 {
+    self.receiver.dropped()
+  }
+
+  pub fn policy(&self) -> Backpressure {
+    self.receiver.policy()
+  }
+}
+
+impl<T> Subscription<T> {
+  /// Wait for the next notification. Returns `None` once the connection is gone.
+  pub async fn recv(&mut self) -> Option<Result<Update<T>, Error>> {
+    self.receiver.recv().await.map(self.parse)
+  }
+}
+
+impl<T> Stream for Subscription<T> {
+  type Item = Result<Update<T>, Error>;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    let this = self.get_mut();
+    this.receiver.poll_recv(cx).map(|notif| notif.map(this.parse))
+  }
+}
+
+impl<T> Drop for Subscription<T> {
+  fn drop(&mut self) {
+    for id in &self.listeners {
+      self.client.handler.unsubscribe(*id);
+    }
+    // Other subscriptions may still be using some of the channels
+    let unused: Vec<&String> = {
+      let mut router = self.client.handler.subscriptions.lock().unwrap();
+      self.channels.iter().filter(|channel| router.release_channel(channel)).collect()
+    };
+    if unused.is_empty() {
+      return;
+    }
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+      return;
+    };
+    let client = self.client.clone();
+    let method = self.unsubscribe_method;
+    let mut params = serde_json::json!({ "channels": unused });
+    if let Some(auth) = &self.auth {
+      params["access_token"] = serde_json::Value::String(auth.lock().unwrap().response.access_token.clone());
+    }
+    runtime.spawn(async move {
+      let _ = client.send(method, params, 0).await; // nobody is waiting for the reply
+    });
+  }
+}
+
+fn parse_update<T: DeserializeOwned>(notif: Notification) -> Result<Update<T>, Error> {
+  let data = parse_json::<T>(notif.params.data)?;
+  Ok(Update { channel: notif.params.channel, data })
+}
+
+/// Sends the subscribe request and registers a listener for every confirmed channel. Notifications are parsed with `parse`
+/// and queued according to `policy`.
+/// Listeners are registered before sending so that no notification arriving right after the reply is lost.
+pub(crate) async fn subscribe_with<T>(
+  client: &SocketClient,
+  method: &str,
+  unsubscribe_method: &'static str,
+  channels: &[&str],
+  auth: Option<SharedAuth>,
+  parse: fn(Notification) -> Result<Update<T>, Error>,
+  policy: Backpressure,
+) -> Result<Subscription<T>, Error> {
+  let (tx, receiver) = notification_channel(DEFAULT_BUFFER, policy);
+  let listen = |channels: &[&str]| -> Vec<ListenerId> {
+    channels.iter()
+      .flat_map(|channel| patterns(channel))
+      .map(|pattern| client.listen(pattern, tx.clone()))
+      .collect()
+  };
+  let unlisten = |listeners: &[ListenerId]| {
+    for id in listeners {
+      client.handler.unsubscribe(*id);
+    }
+  };
+  let mut listeners = listen(channels);
+
+  let mut params = serde_json::json!({ "channels": channels });
+  if let Some(auth) = &auth {
+    params["access_token"] = serde_json::Value::String(auth.lock().unwrap().response.access_token.clone());
+  }
+  let confirmed = client.request(method, params).await
+    .and_then(|resp| resp.value())
+    .and_then(parse_json::<Vec<String>>);
+  let confirmed = match confirmed {
+    Ok(confirmed) => confirmed,
+    Err(e) => {
+      unlisten(&listeners);
+      return Err(e);
+    }
+  };
+
+  // The server may confirm a different set of channels than requested, e.g. when some are invalid
+  if confirmed.len() != channels.len() || !confirmed.iter().all(|c| channels.contains(&c.as_str())) {
+    let confirmed: Vec<&str> = confirmed.iter().map(|c| c.as_str()).collect();
+    let previous = std::mem::replace(&mut listeners, listen(&confirmed));
+    unlisten(&previous);
+  }
+  {
+    let mut router = client.handler.subscriptions.lock().unwrap();
+    for channel in &confirmed {
+      router.retain_channel(channel);
+    }
+  }
+
+  Ok(Subscription {
+    channels: confirmed,
+    listeners,
+    receiver,
+    client: client.clone(),
+    unsubscribe_method,
+    auth,
+    parse,
+  })
+}
+
+impl SocketClient {
+  /// Subscribe to one or more **public** channels. Notifications are parsed into `T`; use `serde_json::Value` to keep them raw.
+  /// A slow consumer is handled with the policy suited to the channels, see `Backpressure::for_channels`.
+  /// - `channels` - The channels to subscribe to, e.g. `["book.BTC-PERPETUAL.100ms"]`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-subscribe)
+  pub async fn subscribe<T: DeserializeOwned>(&self, channels: &[&str]) -> Result<Subscription<T>, Error> {
+    self.subscribe_with_policy(channels, Backpressure::for_channels(channels)).await
+  }
+
+  /// Subscribe to one or more **public** channels, handling a slow consumer with the given policy.
+  /// - `channels` - The channels to subscribe to, e.g. `["ticker.BTC-PERPETUAL.raw"]`.
+  /// - `policy` - What to do when the subscription's buffer is full, e.g. `Backpressure::Conflate`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-subscribe)
+  pub async fn subscribe_with_policy<T: DeserializeOwned>(&self, channels: &[&str], policy: Backpressure) -> Result<Subscription<T>, Error> {
+    subscribe_with(self, "public/subscribe", "public/unsubscribe", channels, None, parse_update::<T>, policy).await
+  }
+}
+
+impl PrivateClient {
+  /// Subscribe to one or more public or private channels. Notifications are parsed into `T`; use `serde_json::Value` to keep them raw.
+  /// A slow consumer is handled with the policy suited to the channels, see `Backpressure::for_channels`.
+  /// - `channels` - The channels to subscribe to, e.g. `["user.orders.BTC-PERPETUAL.raw"]`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-subscribe)
+  pub async fn subscribe<T: DeserializeOwned>(&mut self, channels: &[&str]) -> Result<Subscription<T>, Error> {
+    self.subscribe_with_policy(channels, Backpressure::for_channels(channels)).await
+  }
+
+  /// Subscribe to one or more public or private channels, handling a slow consumer with the given policy.
+  /// - `channels` - The channels to subscribe to, e.g. `["user.orders.BTC-PERPETUAL.raw"]`.
+  /// - `policy` - What to do when the subscription's buffer is full, e.g. `Backpressure::Block`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-subscribe)
+  pub async fn subscribe_with_policy<T: DeserializeOwned>(&mut self, channels: &[&str], policy: Backpressure) -> Result<Subscription<T>, Error> {
+    if self.auth().expired() {
+      self.refresh_token().await?;
+    }
+    subscribe_with(&self.client, "private/subscribe", "private/unsubscribe", channels, Some(self.shared_auth().clone()), parse_update::<T>, policy).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use crate::mock::{MockDeribit, MOCK_SCOPE};
+
+  #[tokio::test]
+  async fn dropping_a_subscription_unsubscribes_with_the_current_token() {
+    let mock = MockDeribit::new();
+    let mut client = mock.private_client().await.unwrap();
+    let orders = client.subscribe::<serde_json::Value>(&["user.orders.any.any.raw"]).await.unwrap();
+    let trades = client.subscribe::<serde_json::Value>(&["user.trades.any.any.raw"]).await.unwrap();
+    mock.respond("public/auth", serde_json::json!({
+      "access_token": "refreshed_access_token",
+      "refresh_token": "refreshed_refresh_token",
+      "expires_in": 31536000,
+      "scope": MOCK_SCOPE,
+      "token_type": "bearer",
+    }));
+    client.refresh_token().await.unwrap();
+
+    drop(orders);
+    let unsubscribe = mock.wait_for("private/unsubscribe", 1, Duration::from_secs(1)).await.expect("no unsubscribe was sent");
+    assert_eq!(unsubscribe.params["channels"], serde_json::json!(["user.orders.any.any.raw"]));
+    assert_eq!(unsubscribe.access_token(), Some("refreshed_access_token"));
+    assert_eq!(client.client.handler.subscriptions.lock().unwrap().channels(), ["user.trades.any.any.raw"]);
+    drop(trades);
+    assert!(client.client.handler.subscriptions.lock().unwrap().channels().is_empty());
+  }
+}
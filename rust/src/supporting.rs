@@ -0,0 +1,168 @@
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{now_us, parse_json, Error, RpcTransport};
+use crate::session::ServerVersion;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Locked {
+  #[serde(rename = "false")]
+  Unlocked,
+  /// Some currencies or indices are locked
+  Partial,
+  #[serde(rename = "true")]
+  Locked,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+  pub locked: Locked,
+  #[serde(default)]
+  pub locked_currencies: Vec<String>,
+  #[serde(default)]
+  pub locked_indices: Vec<String>,
+}
+
+/// Supporting endpoints, available on any transport.
+pub trait SupportingApi: RpcTransport {
+  /// Retrieves the current server time, in milliseconds since epoch.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-get_time)
+  fn get_time(&self) -> impl Future<Output = Result<i64, Error>> + Send {
+    self.call("public/get_time", serde_json::json!({}))
+  }
+
+  /// Retrieves the platform lock status, i.e. which currencies are currently locked.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-status)
+  fn status(&self) -> impl Future<Output = Result<Status, Error>> + Send {
+    self.call("public/status", serde_json::json!({}))
+  }
+
+  /// Tests the connection and retrieves the API version.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#public-test)
+  fn test(&self) -> impl Future<Output = Result<ServerVersion, Error>> + Send {
+    self.call("public/test", serde_json::json!({}))
+  }
+}
+
+impl<C: RpcTransport> SupportingApi for C {}
+
+/// Estimate of the server clock relative to the local clock, measured with `public/get_time`.
+///
+/// ```ignore
+/// let clock = ClockSync::measure(&client, 10).await?;
+/// let fill_time_us = clock.to_local(trade.timestamp * 1000);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSync {
+  /// Server clock minus local clock, in microseconds.
+  pub offset_us: i64,
+  /// Network round trip, excluding the server's processing time, in microseconds.
+  pub rtt_us: i64,
+}
+
+impl ClockSync {
+  /// Take a single sample.
+  pub async fn sample<C: RpcTransport>(client: &C) -> Result<ClockSync, Error> {
+    let resp = client.request("public/get_time", serde_json::json!({})).await?;
+    let server_ms = parse_json::<i64>(resp.value()?)?;
+    let metrics = resp.metrics.ok_or(Error::Logic("Reply is missing request metrics"))?;
+    let (sent, received) = (metrics.sent_us, metrics.received_us);
+    let (server_in, server_out) = match (metrics.server_in_us, metrics.server_out_us) {
+      (Some(us_in), Some(us_out)) => (us_in, us_out),
+      // Fall back to the millisecond server time as both receive and send time
+      _ => (server_ms * 1000, server_ms * 1000),
+    };
+    let rtt_us = (received - sent) - (server_out - server_in);
+    let offset_us = ((server_in - sent) + (server_out - received)) / 2;
+    Ok(ClockSync { offset_us, rtt_us })
+  }
+
+  /// Take `samples` samples and keep the one with the shortest round trip, which has the tightest error bound.
+  /// - `samples` - Number of requests to make; at least one is always made.
+  pub async fn measure<C: RpcTransport>(client: &C, samples: usize) -> Result<ClockSync, Error> {
+    let mut best = ClockSync::sample(client).await?;
+    for _ in 1..samples {
+      let sample = ClockSync::sample(client).await?;
+      if sample.rtt_us < best.rtt_us {
+        best = sample;
+      }
+    }
+    Ok(best)
+  }
+
+  /// Maximum error of `offset_us`, in microseconds.
+  pub fn uncertainty_us(&self) -> i64 {
+    self.rtt_us.max(0) / 2
+  }
+
+  /// Current server time, in microseconds since epoch.
+  pub fn server_now_us(&self) -> i64 {
+    self.to_server(now_us())
+  }
+
+  /// Convert a local timestamp to server time. Both in microseconds since epoch.
+  pub fn to_server(&self, local_us: i64) -> i64 {
+    local_us + self.offset_us
+  }
+
+  /// Convert a server timestamp (e.g. a trade's `timestamp * 1000`) to local time. Both in microseconds since epoch.
+  pub fn to_local(&self, server_us: i64) -> i64 {
+    server_us - self.offset_us
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::MockDeribit;
+
+  #[tokio::test]
+  async fn supporting_endpoints_are_parsed() {
+    let mock = MockDeribit::new();
+    mock.respond("public/get_time", serde_json::json!(1550147385946i64));
+    // The `public/status` example in the Deribit docs
+    mock.respond("public/status", serde_json::json!({ "locked_currencies": ["BTC", "ETH"], "locked": "true" }));
+    mock.respond("public/test", serde_json::json!({ "version": "1.2.26" }));
+    let client = mock.client();
+    assert_eq!(client.get_time().await.unwrap(), 1550147385946);
+    let status = client.status().await.unwrap();
+    assert_eq!(status.locked, Locked::Locked);
+    assert_eq!((status.locked_currencies, status.locked_indices.len()), (vec!["BTC".to_string(), "ETH".to_string()], 0));
+    assert_eq!(client.test().await.unwrap().version, "1.2.26");
+
+    mock.respond("public/status", serde_json::json!({ "locked_indices": ["btc_usdc"], "locked": "partial" }));
+    assert_eq!(client.status().await.unwrap().locked, Locked::Partial);
+    mock.respond("public/status", serde_json::json!({ "locked": "false" }));
+    assert_eq!(client.status().await.unwrap().locked, Locked::Unlocked);
+  }
+
+  #[tokio::test]
+  async fn clock_sync_uses_the_server_timestamps() {
+    let mock = MockDeribit::new();
+    // A server an hour ahead of the local clock, which spends 500µs on the request
+    let us_in = now_us() + 3_600_000_000;
+    let us_out = us_in + 500;
+    mock.server_times(us_in, us_out);
+    mock.respond("public/get_time", serde_json::json!(us_in / 1000));
+    let client = mock.client();
+
+    let before = now_us();
+    let clock = ClockSync::sample(&client).await.unwrap();
+    let after = now_us();
+    // The request was sent and its reply read at some local time between `before` and `after`
+    assert!((0..=after - before).contains(&(clock.rtt_us + 500)), "{:?}", clock);
+    assert!((us_in + 250 - after..=us_in + 250 - before).contains(&clock.offset_us), "{:?}", clock);
+    assert_eq!(clock.uncertainty_us(), clock.rtt_us.max(0) / 2);
+    assert_eq!(clock.to_local(clock.to_server(before)), before);
+    assert_eq!(clock.to_server(before), before + clock.offset_us);
+
+    let best = ClockSync::measure(&client, 3).await.unwrap();
+    assert_eq!(mock.requests_to("public/get_time").len(), 4);
+    assert!((us_in + 250 - now_us()..=us_in + 250 - before).contains(&best.offset_us), "{:?}", best);
+  }
+}
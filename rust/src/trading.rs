@@ -0,0 +1,625 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::core::{Error, PrivateClient, RpcTransport};
+use crate::types::{Currency, Direction, Kind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderType {
+  Limit,
+  StopLimit,
+  TakeLimit,
+  Market,
+  StopMarket,
+  TakeMarket,
+  MarketLimit,
+  TrailingStop,
+  /// Returned for orders created by the liquidation engine or for block trades.
+  #[serde(other)]
+  Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeInForce {
+  GoodTilCancelled,
+  GoodTilDay,
+  FillOrKill,
+  ImmediateOrCancel,
+}
+
+/// Price used to fire trigger (stop/take/trailing) orders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+  IndexPrice,
+  MarkPrice,
+  LastPrice,
+}
+
+/// Advanced option order type: price is given in USD or implied volatility instead of the base currency
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Advanced {
+  Usd,
+  Implv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderState {
+  Open,
+  Filled,
+  Rejected,
+  Cancelled,
+  Untriggered,
+  Triggered,
+  /// The order was archived, e.g. in order history.
+  Archive,
+  /// A state this client doesn't know about yet.
+  #[serde(other)]
+  Unknown,
+}
+
+/// Parameters of a new order, for `buy` and `sell`.
+///
+/// ```ignore
+/// let order = OrderRequest::limit(10.0, 50000.0).post_only(true).label("entry");
+/// client.buy("BTC-PERPETUAL", order).await?;
+/// ```
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OrderRequest {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub amount: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub contracts: Option<f64>,
+  #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+  pub order_type: Option<OrderType>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub price: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub label: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub time_in_force: Option<TimeInForce>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub max_show: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub post_only: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub reject_post_only: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub reduce_only: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub trigger_price: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub trigger_offset: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub trigger: Option<Trigger>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub advanced: Option<Advanced>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub mmp: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub valid_until: Option<i64>,
+}
+
+impl OrderRequest {
+  /// Limit order for `amount` (in USD for perpetuals and inverse futures, base currency otherwise) at `price`.
+  pub fn limit(amount: f64, price: f64) -> Self {
+    OrderRequest { amount: Some(amount), price: Some(price), order_type: Some(OrderType::Limit), ..Default::default() }
+  }
+
+  /// Market order for `amount`.
+  pub fn market(amount: f64) -> Self {
+    OrderRequest { amount: Some(amount), order_type: Some(OrderType::Market), ..Default::default() }
+  }
+
+  /// Order of the given type for `amount`; set the price and trigger with the builder methods.
+  pub fn new(order_type: OrderType, amount: f64) -> Self {
+    OrderRequest { amount: Some(amount), order_type: Some(order_type), ..Default::default() }
+  }
+
+  /// Size the order in contracts instead of `amount`.
+  pub fn contracts(mut self, contracts: f64) -> Self {
+    self.amount = None;
+    self.contracts = Some(contracts);
+    self
+  }
+
+  pub fn price(mut self, price: f64) -> Self {
+    self.price = Some(price);
+    self
+  }
+
+  pub fn label(mut self, label: &str) -> Self {
+    self.label = Some(label.to_string());
+    self
+  }
+
+  pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+    self.time_in_force = Some(time_in_force);
+    self
+  }
+
+  pub fn max_show(mut self, max_show: f64) -> Self {
+    self.max_show = Some(max_show);
+    self
+  }
+
+  pub fn post_only(mut self, post_only: bool) -> Self {
+    self.post_only = Some(post_only);
+    self
+  }
+
+  /// Reject the order instead of repricing it when `post_only` would cross the book.
+  pub fn reject_post_only(mut self, reject_post_only: bool) -> Self {
+    self.reject_post_only = Some(reject_post_only);
+    self
+  }
+
+  pub fn reduce_only(mut self, reduce_only: bool) -> Self {
+    self.reduce_only = Some(reduce_only);
+    self
+  }
+
+  /// Fire the order when `trigger` reaches `trigger_price`. For stop and take orders.
+  pub fn trigger(mut self, trigger: Trigger, trigger_price: f64) -> Self {
+    self.trigger = Some(trigger);
+    self.trigger_price = Some(trigger_price);
+    self
+  }
+
+  /// Distance the trigger price follows the market at. For trailing stop orders.
+  pub fn trigger_offset(mut self, trigger_offset: f64) -> Self {
+    self.trigger_offset = Some(trigger_offset);
+    self
+  }
+
+  pub fn advanced(mut self, advanced: Advanced) -> Self {
+    self.advanced = Some(advanced);
+    self
+  }
+
+  /// Protect the order with market maker protection.
+  pub fn mmp(mut self, mmp: bool) -> Self {
+    self.mmp = Some(mmp);
+    self
+  }
+
+  /// Reject the order if the server receives it after this timestamp (milliseconds since epoch).
+  pub fn valid_until(mut self, valid_until: i64) -> Self {
+    self.valid_until = Some(valid_until);
+    self
+  }
+}
+
+/// New parameters of an existing order, for `edit` and `edit_by_label`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EditRequest {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub amount: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub contracts: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub price: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub post_only: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub reject_post_only: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub reduce_only: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub trigger_price: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub trigger_offset: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub advanced: Option<Advanced>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub mmp: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub valid_until: Option<i64>,
+}
+
+impl EditRequest {
+  /// Change the order to `amount` at `price`.
+  pub fn new(amount: f64, price: f64) -> Self {
+    EditRequest { amount: Some(amount), price: Some(price), ..Default::default() }
+  }
+
+  pub fn post_only(mut self, post_only: bool) -> Self {
+    self.post_only = Some(post_only);
+    self
+  }
+
+  pub fn reject_post_only(mut self, reject_post_only: bool) -> Self {
+    self.reject_post_only = Some(reject_post_only);
+    self
+  }
+
+  pub fn reduce_only(mut self, reduce_only: bool) -> Self {
+    self.reduce_only = Some(reduce_only);
+    self
+  }
+
+  pub fn trigger_price(mut self, trigger_price: f64) -> Self {
+    self.trigger_price = Some(trigger_price);
+    self
+  }
+
+  pub fn trigger_offset(mut self, trigger_offset: f64) -> Self {
+    self.trigger_offset = Some(trigger_offset);
+    self
+  }
+
+  pub fn advanced(mut self, advanced: Advanced) -> Self {
+    self.advanced = Some(advanced);
+    self
+  }
+
+  pub fn mmp(mut self, mmp: bool) -> Self {
+    self.mmp = Some(mmp);
+    self
+  }
+
+  pub fn valid_until(mut self, valid_until: i64) -> Self {
+    self.valid_until = Some(valid_until);
+    self
+  }
+}
+
+/// Market orders report their price as `"market_price"`; that is mapped to `None`.
+fn price_or_market<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum Price {
+    Number(f64),
+    Text(#[allow(dead_code)] String),
+  }
+  Ok(match Option::<Price>::deserialize(deserializer)? {
+    Some(Price::Number(price)) => Some(price),
+    _ => None,
+  })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Order {
+  pub order_id: String,
+  pub order_state: OrderState,
+  pub order_type: OrderType,
+  pub instrument_name: String,
+  pub direction: Direction,
+  #[serde(default, deserialize_with = "price_or_market")]
+  pub price: Option<f64>,
+  pub amount: f64,
+  #[serde(default)]
+  pub contracts: Option<f64>,
+  #[serde(default)]
+  pub filled_amount: f64,
+  #[serde(default)]
+  pub average_price: Option<f64>,
+  #[serde(default)]
+  pub label: String,
+  pub time_in_force: TimeInForce,
+  #[serde(default)]
+  pub post_only: bool,
+  #[serde(default)]
+  pub reject_post_only: bool,
+  #[serde(default)]
+  pub reduce_only: bool,
+  pub creation_timestamp: i64,
+  pub last_update_timestamp: i64,
+  #[serde(default)]
+  pub api: bool,
+  #[serde(default)]
+  pub web: bool,
+  #[serde(default)]
+  pub replaced: bool,
+  #[serde(default)]
+  pub is_liquidation: bool,
+  #[serde(default)]
+  pub mmp: bool,
+  #[serde(default)]
+  pub max_show: Option<f64>,
+  #[serde(default)]
+  pub trigger: Option<Trigger>,
+  #[serde(default)]
+  pub trigger_price: Option<f64>,
+  #[serde(default)]
+  pub trigger_offset: Option<f64>,
+  #[serde(default)]
+  pub triggered: Option<bool>,
+  #[serde(default)]
+  pub advanced: Option<Advanced>,
+  #[serde(default)]
+  pub usd: Option<f64>,
+  #[serde(default)]
+  pub implv: Option<f64>,
+  #[serde(default)]
+  pub profit_loss: Option<f64>,
+  #[serde(default)]
+  pub cancel_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Liquidity {
+  #[serde(rename = "M")]
+  Maker,
+  #[serde(rename = "T")]
+  Taker,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trade {
+  pub trade_id: String,
+  pub trade_seq: i64,
+  pub order_id: String,
+  pub instrument_name: String,
+  pub direction: Direction,
+  pub price: f64,
+  pub amount: f64,
+  #[serde(default)]
+  pub contracts: Option<f64>,
+  pub fee: f64,
+  pub fee_currency: Currency,
+  pub timestamp: i64,
+  pub index_price: f64,
+  pub mark_price: f64,
+  pub state: OrderState,
+  pub order_type: OrderType,
+  pub tick_direction: i64,
+  #[serde(default)]
+  pub liquidity: Option<Liquidity>,
+  #[serde(default)]
+  pub label: Option<String>,
+  #[serde(default)]
+  pub post_only: Option<bool>,
+  #[serde(default)]
+  pub reduce_only: Option<bool>,
+  #[serde(default)]
+  pub mmp: Option<bool>,
+  #[serde(default)]
+  pub api: Option<bool>,
+  #[serde(default)]
+  pub matching_id: Option<String>,
+  #[serde(default)]
+  pub block_trade_id: Option<String>,
+  #[serde(default)]
+  pub combo_id: Option<String>,
+  #[serde(default)]
+  pub profit_loss: Option<f64>,
+  #[serde(default)]
+  pub iv: Option<f64>,
+  #[serde(default)]
+  pub underlying_price: Option<f64>,
+}
+
+/// An order together with the trades it produced on placement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderResponse {
+  pub order: Order,
+  pub trades: Vec<Trade>,
+}
+
+impl<C: RpcTransport> PrivateClient<C> {
+  /// Places a buy order.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  /// - `order` - The order parameters, e.g. `OrderRequest::limit(10.0, 50000.0).post_only(true)`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-buy)
+  pub async fn buy(&mut self, instrument_name: &str, order: OrderRequest) -> Result<OrderResponse, Error> {
+    let mut params = serde_json::to_value(order)?;
+    params["instrument_name"] = serde_json::Value::from(instrument_name);
+    self.authed_call("private/buy", params).await
+  }
+
+  /// Places a sell order.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  /// - `order` - The order parameters, e.g. `OrderRequest::market(10.0)`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-sell)
+  pub async fn sell(&mut self, instrument_name: &str, order: OrderRequest) -> Result<OrderResponse, Error> {
+    let mut params = serde_json::to_value(order)?;
+    params["instrument_name"] = serde_json::Value::from(instrument_name);
+    self.authed_call("private/sell", params).await
+  }
+
+  /// Changes the price, amount and/or other properties of an order.
+  /// - `order_id` - The ID of the order to edit.
+  /// - `edit` - The new parameters, e.g. `EditRequest::new(10.0, 50100.0)`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-edit)
+  pub async fn edit(&mut self, order_id: &str, edit: EditRequest) -> Result<OrderResponse, Error> {
+    let mut params = serde_json::to_value(edit)?;
+    params["order_id"] = serde_json::Value::from(order_id);
+    self.authed_call("private/edit", params).await
+  }
+
+  /// Changes the price, amount and/or other properties of the order with the given label.
+  /// - `label` - The label of the order to edit. Exactly one open order must carry it.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  /// - `edit` - The new parameters, e.g. `EditRequest::new(10.0, 50100.0)`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-edit_by_label)
+  pub async fn edit_by_label(&mut self, label: &str, instrument_name: &str, edit: EditRequest) -> Result<OrderResponse, Error> {
+    let mut params = serde_json::to_value(edit)?;
+    params["label"] = serde_json::Value::from(label);
+    params["instrument_name"] = serde_json::Value::from(instrument_name);
+    self.authed_call("private/edit_by_label", params).await
+  }
+
+  /// Cancels an order.
+  /// - `order_id` - The ID of the order to cancel.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-cancel)
+  pub async fn cancel(&mut self, order_id: &str) -> Result<Order, Error> {
+    let params = serde_json::json!({ "order_id": order_id });
+    self.authed_call("private/cancel", params).await
+  }
+
+  /// Cancels all orders and trigger orders, in all currencies. Returns the number of cancelled orders.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-cancel_all)
+  pub async fn cancel_all(&mut self) -> Result<u64, Error> {
+    self.authed_call("private/cancel_all", serde_json::json!({})).await
+  }
+
+  /// Cancels all orders in a currency. Returns the number of cancelled orders.
+  /// - `currency` - The currency of the instruments.
+  /// - `kind` - Only cancel orders on instruments of this kind, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-cancel_all_by_currency)
+  pub async fn cancel_all_by_currency(&mut self, currency: Currency, kind: Option<Kind>) -> Result<u64, Error> {
+    let mut params = serde_json::json!({ "currency": currency });
+    if let Some(kind) = kind {
+      params["kind"] = serde_json::to_value(kind)?;
+    }
+    self.authed_call("private/cancel_all_by_currency", params).await
+  }
+
+  /// Cancels all orders on an instrument. Returns the number of cancelled orders.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-cancel_all_by_instrument)
+  pub async fn cancel_all_by_instrument(&mut self, instrument_name: &str) -> Result<u64, Error> {
+    let params = serde_json::json!({ "instrument_name": instrument_name });
+    self.authed_call("private/cancel_all_by_instrument", params).await
+  }
+
+  /// Cancels all orders with the given label. Returns the number of cancelled orders.
+  /// - `label` - The label of the orders to cancel.
+  /// - `currency` - Only cancel orders in this currency, if given.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-cancel_by_label)
+  pub async fn cancel_by_label(&mut self, label: &str, currency: Option<Currency>) -> Result<u64, Error> {
+    let mut params = serde_json::json!({ "label": label });
+    if let Some(currency) = currency {
+      params["currency"] = serde_json::to_value(currency)?;
+    }
+    self.authed_call("private/cancel_by_label", params).await
+  }
+
+  /// Closes the position on an instrument with a reduce-only order.
+  /// - `instrument_name` - e.g. `"BTC-PERPETUAL"`
+  /// - `price` - Limit price; `None` closes with a market order.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-close_position)
+  pub async fn close_position(&mut self, instrument_name: &str, price: Option<f64>) -> Result<OrderResponse, Error> {
+    let mut params = serde_json::json!({ "instrument_name": instrument_name });
+    match price {
+      Some(price) => {
+        params["type"] = serde_json::to_value(OrderType::Limit)?;
+        params["price"] = serde_json::Value::from(price);
+      }
+      None => params["type"] = serde_json::to_value(OrderType::Market)?,
+    }
+    self.authed_call("private/close_position", params).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::MockDeribit;
+
+  /// Shaped like the `order` of the `private/buy` example in the Deribit docs.
+  fn order() -> serde_json::Value {
+    serde_json::json!({
+      "web": false,
+      "time_in_force": "good_til_cancelled",
+      "replaced": false,
+      "reduce_only": false,
+      "price": 0.0028,
+      "post_only": false,
+      "order_type": "limit",
+      "order_state": "open",
+      "order_id": "ETH-349249",
+      "max_show": 40.0,
+      "last_update_timestamp": 1550657341322i64,
+      "label": "market0000234",
+      "is_liquidation": false,
+      "instrument_name": "ETH-PERPETUAL",
+      "filled_amount": 0.0,
+      "direction": "buy",
+      "creation_timestamp": 1550657341322i64,
+      "average_price": 0.0,
+      "api": true,
+      "amount": 40.0,
+    })
+  }
+
+  #[test]
+  fn order_request_builders_set_only_what_they_name() {
+    let order = OrderRequest::limit(10.0, 50000.0).post_only(true).label("entry").time_in_force(TimeInForce::GoodTilDay);
+    assert_eq!(serde_json::to_value(&order).unwrap(), serde_json::json!({
+      "amount": 10.0,
+      "type": "limit",
+      "price": 50000.0,
+      "post_only": true,
+      "label": "entry",
+      "time_in_force": "good_til_day",
+    }));
+    let stop = OrderRequest::new(OrderType::StopMarket, 10.0).trigger(Trigger::MarkPrice, 48000.0).reduce_only(true).contracts(1.0);
+    assert_eq!(serde_json::to_value(&stop).unwrap(), serde_json::json!({
+      "contracts": 1.0,
+      "type": "stop_market",
+      "trigger": "mark_price",
+      "trigger_price": 48000.0,
+      "reduce_only": true,
+    }));
+    assert_eq!(serde_json::to_value(OrderRequest::market(5.0)).unwrap(), serde_json::json!({ "amount": 5.0, "type": "market" }));
+  }
+
+  #[test]
+  fn edit_request_builders_set_only_what_they_name() {
+    let edit = EditRequest::new(20.0, 51000.0).reject_post_only(true).post_only(true).advanced(Advanced::Usd).valid_until(1700000000000);
+    assert_eq!(serde_json::to_value(&edit).unwrap(), serde_json::json!({
+      "amount": 20.0,
+      "price": 51000.0,
+      "post_only": true,
+      "reject_post_only": true,
+      "advanced": "usd",
+      "valid_until": 1700000000000i64,
+    }));
+  }
+
+  #[test]
+  fn market_orders_have_no_price() {
+    let mut market = order();
+    market["price"] = serde_json::json!("market_price");
+    market["order_type"] = serde_json::json!("market");
+    let market: Order = serde_json::from_value(market).unwrap();
+    assert_eq!((market.price, market.order_type), (None, OrderType::Market));
+    let limit: Order = serde_json::from_value(order()).unwrap();
+    assert_eq!(limit.price, Some(0.0028));
+  }
+
+  #[test]
+  fn unknown_order_types_and_states_are_kept_apart() {
+    let mut liquidation = order();
+    liquidation["order_type"] = serde_json::json!("liquidation");
+    liquidation["order_state"] = serde_json::json!("pending_settlement");
+    let liquidation: Order = serde_json::from_value(liquidation).unwrap();
+    assert_eq!((liquidation.order_type, liquidation.order_state), (OrderType::Other, OrderState::Unknown));
+  }
+
+  #[test]
+  fn orders_survive_a_round_trip() {
+    let order: Order = serde_json::from_value(order()).unwrap();
+    let json = serde_json::to_value(&order).unwrap();
+    let again: Order = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&again).unwrap(), json);
+    assert_eq!((again.order_id.as_str(), again.max_show, again.time_in_force), ("ETH-349249", Some(40.0), TimeInForce::GoodTilCancelled));
+  }
+
+  #[tokio::test]
+  async fn buy_sends_the_order_with_the_instrument() {
+    let mock = MockDeribit::new();
+    mock.respond("private/buy", serde_json::json!({ "order": order(), "trades": [] }));
+    let mut client = mock.private_client().await.unwrap();
+    let placed = client.buy("ETH-PERPETUAL", OrderRequest::limit(40.0, 0.0028).label("market0000234")).await.unwrap();
+    assert_eq!((placed.order.order_state, placed.trades.len()), (OrderState::Open, 0));
+    let req = mock.assert_requested("private/buy");
+    assert_eq!((req.params["instrument_name"].as_str(), req.params["type"].as_str()), (Some("ETH-PERPETUAL"), Some("limit")));
+    assert_eq!(req.access_token(), Some(crate::mock::MOCK_ACCESS_TOKEN));
+  }
+}
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+
+/// Currency accepted by Deribit. Unknown currencies are kept verbatim, so new listings don't break parsing.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Currency {
+  BTC,
+  ETH,
+  USDC,
+  USDT,
+  EURR,
+  SOL,
+  XRP,
+  /// All currencies, where the endpoint accepts it.
+  Any,
+  Other(String),
+}
+
+impl Currency {
+  pub fn as_str(&self) -> &str {
+    match self {
+      Currency::BTC => "BTC",
+      Currency::ETH => "ETH",
+      Currency::USDC => "USDC",
+      Currency::USDT => "USDT",
+      Currency::EURR => "EURR",
+      Currency::SOL => "SOL",
+      Currency::XRP => "XRP",
+      Currency::Any => "any",
+      Currency::Other(s) => s,
+    }
+  }
+
+  /// Known currencies are matched regardless of case, since some endpoints (e.g. subaccount portfolios) use lowercase.
+  pub fn parse(currency: &str) -> Self {
+    match currency.to_ascii_uppercase().as_str() {
+      "BTC" => Currency::BTC,
+      "ETH" => Currency::ETH,
+      "USDC" => Currency::USDC,
+      "USDT" => Currency::USDT,
+      "EURR" => Currency::EURR,
+      "SOL" => Currency::SOL,
+      "XRP" => Currency::XRP,
+      "ANY" => Currency::Any,
+      _ => Currency::Other(currency.to_string()),
+    }
+  }
+}
+
+impl std::fmt::Display for Currency {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl<'a> Deserialize<'a> for Currency {
+  fn deserialize<D: serde::Deserializer<'a>>(deserializer: D) -> Result<Self, D::Error> {
+    let currency = String::deserialize(deserializer)?;
+    Ok(Currency::parse(&currency))
+  }
+}
+
+impl Serialize for Currency {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.as_str())
+  }
+}
+
+/// Instrument kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+  Future,
+  Option,
+  Spot,
+  FutureCombo,
+  OptionCombo,
+}
+
+impl Kind {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Kind::Future => "future",
+      Kind::Option => "option",
+      Kind::Spot => "spot",
+      Kind::FutureCombo => "future_combo",
+      Kind::OptionCombo => "option_combo",
+    }
+  }
+
+  pub fn parse(kind: &str) -> Option<Self> {
+    match kind {
+      "future" => Some(Kind::Future),
+      "option" => Some(Kind::Option),
+      "spot" => Some(Kind::Spot),
+      "future_combo" => Some(Kind::FutureCombo),
+      "option_combo" => Some(Kind::OptionCombo),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptionType {
+  Call,
+  Put,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+  Buy,
+  Sell,
+}
+
+impl Direction {
+  pub fn opposite(self) -> Self {
+    match self {
+      Direction::Buy => Direction::Sell,
+      Direction::Sell => Direction::Buy,
+    }
+  }
+}
+
+/// ID of a (sub)account, as accepted by `PrivateClient::exchange_token` and `switch_subaccount`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SubaccountId(pub i64);
+
+impl From<i64> for SubaccountId {
+  fn from(id: i64) -> Self {
+    SubaccountId(id)
+  }
+}
+
+impl std::fmt::Display for SubaccountId {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    self.0.fmt(f)
+  }
+}
@@ -0,0 +1,368 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{Error, PrivateClient, RpcTransport};
+use crate::types::{Currency, SubaccountId};
+
+/// One page of a listing endpoint, with the total number of items
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+  pub count: u64,
+  pub data: Vec<T>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepositState {
+  Pending,
+  Completed,
+  Rejected,
+  Replaced,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Deposit {
+  pub address: String,
+  #[serde(with = "rust_decimal::serde::float")]
+  pub amount: Decimal,
+  pub currency: Currency,
+  pub state: DepositState,
+  pub received_timestamp: i64,
+  pub updated_timestamp: i64,
+  #[serde(default)]
+  pub transaction_id: Option<String>,
+  #[serde(default)]
+  pub note: Option<String>,
+}
+
+/// Withdrawal fee priority
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+  Insane,
+  ExtremeHigh,
+  VeryHigh,
+  High,
+  Mid,
+  Low,
+  VeryLow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WithdrawalState {
+  Unconfirmed,
+  Confirmed,
+  Cancelled,
+  Completed,
+  Interrupted,
+  Rejected,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Withdrawal {
+  pub id: i64,
+  pub address: String,
+  #[serde(with = "rust_decimal::serde::float")]
+  pub amount: Decimal,
+  pub currency: Currency,
+  #[serde(with = "rust_decimal::serde::float")]
+  pub fee: Decimal,
+  pub state: WithdrawalState,
+  #[serde(default, with = "rust_decimal::serde::float_option")]
+  pub priority: Option<Decimal>,
+  pub created_timestamp: i64,
+  #[serde(default)]
+  pub confirmed_timestamp: Option<i64>,
+  pub updated_timestamp: i64,
+  #[serde(default)]
+  pub transaction_id: Option<String>,
+  #[serde(default)]
+  pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepositAddress {
+  pub address: String,
+  pub currency: Currency,
+  pub creation_timestamp: i64,
+  #[serde(rename = "type")]
+  pub address_type: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferState {
+  Prepared,
+  Confirmed,
+  Cancelled,
+  WaitingForAdmin,
+  InsufficientFunds,
+  WithdrawalLimit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferDirection {
+  Payment,
+  Income,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferType {
+  User,
+  Subaccount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transfer {
+  pub id: i64,
+  #[serde(with = "rust_decimal::serde::float")]
+  pub amount: Decimal,
+  pub currency: Currency,
+  pub direction: TransferDirection,
+  /// Username or address of the counterparty
+  pub other_side: String,
+  pub state: TransferState,
+  #[serde(rename = "type")]
+  pub transfer_type: TransferType,
+  pub created_timestamp: i64,
+  pub updated_timestamp: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressBookType {
+  Transfer,
+  Withdrawal,
+  DepositSource,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressBookEntry {
+  pub address: String,
+  pub currency: Currency,
+  pub label: String,
+  #[serde(rename = "type")]
+  pub address_type: AddressBookType,
+  pub creation_timestamp: i64,
+  #[serde(default)]
+  pub status: Option<String>,
+  #[serde(default)]
+  pub requires_confirmation: Option<bool>,
+  #[serde(default)]
+  pub waiting_timestamp: Option<i64>,
+  #[serde(default)]
+  pub personal: Option<bool>,
+  #[serde(default)]
+  pub agreed: Option<bool>,
+  #[serde(default)]
+  pub beneficiary_vasp_name: Option<String>,
+  #[serde(default)]
+  pub beneficiary_first_name: Option<String>,
+  #[serde(default)]
+  pub beneficiary_last_name: Option<String>,
+  #[serde(default)]
+  pub beneficiary_company_name: Option<String>,
+}
+
+/// `amount` as a JSON number, like the amounts Deribit returns.
+fn number(amount: Decimal) -> serde_json::Value {
+  rust_decimal::serde::float::serialize(&amount, serde_json::value::Serializer).unwrap_or_default()
+}
+
+impl<C: RpcTransport> PrivateClient<C> {
+  /// Retrieves one page of deposits, most recent first.
+  /// - `currency` - The currency of the deposits.
+  /// - `count` - Page size.
+  /// - `offset` - Number of deposits to skip.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_deposits)
+  pub async fn get_deposits(&mut self, currency: Currency, count: u32, offset: u32) -> Result<Page<Deposit>, Error> {
+    let params = serde_json::json!({ "currency": currency, "count": count, "offset": offset });
+    self.authed_call("private/get_deposits", params).await
+  }
+
+  /// Retrieves one page of withdrawals, most recent first.
+  /// - `currency` - The currency of the withdrawals.
+  /// - `count` - Page size.
+  /// - `offset` - Number of withdrawals to skip.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_withdrawals)
+  pub async fn get_withdrawals(&mut self, currency: Currency, count: u32, offset: u32) -> Result<Page<Withdrawal>, Error> {
+    let params = serde_json::json!({ "currency": currency, "count": count, "offset": offset });
+    self.authed_call("private/get_withdrawals", params).await
+  }
+
+  /// Creates a withdrawal to an address in the address book. Requires `wallet:read_write`.
+  /// - `currency` - The currency to withdraw.
+  /// - `address` - The destination address; it must be in the withdrawal address book.
+  /// - `amount` - The amount to withdraw.
+  /// - `priority` - Fee priority, if given. Only used for BTC.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-withdraw)
+  pub async fn withdraw(&mut self, currency: Currency, address: &str, amount: Decimal, priority: Option<Priority>) -> Result<Withdrawal, Error> {
+    self.scope().wallet().require_write("wallet:read_write")?;
+    let mut params = serde_json::json!({ "currency": currency, "address": address, "amount": number(amount) });
+    if let Some(priority) = priority {
+      params["priority"] = serde_json::to_value(priority)?;
+    }
+    self.authed_call("private/withdraw", params).await
+  }
+
+  /// Cancels a withdrawal that hasn't been confirmed yet. Requires `wallet:read_write`.
+  /// - `currency` - The currency of the withdrawal.
+  /// - `id` - The ID of the withdrawal.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-cancel_withdrawal)
+  pub async fn cancel_withdrawal(&mut self, currency: Currency, id: i64) -> Result<Withdrawal, Error> {
+    self.scope().wallet().require_write("wallet:read_write")?;
+    let params = serde_json::json!({ "currency": currency, "id": id });
+    self.authed_call("private/cancel_withdrawal", params).await
+  }
+
+  /// Retrieves the current deposit address, if one has been created.
+  /// - `currency` - The currency of the address.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_current_deposit_address)
+  pub async fn get_current_deposit_address(&mut self, currency: Currency) -> Result<Option<DepositAddress>, Error> {
+    let params = serde_json::json!({ "currency": currency });
+    self.authed_call("private/get_current_deposit_address", params).await
+  }
+
+  /// Creates a new deposit address. Requires `wallet:read_write`.
+  /// - `currency` - The currency of the address.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-create_deposit_address)
+  pub async fn create_deposit_address(&mut self, currency: Currency) -> Result<Option<DepositAddress>, Error> {
+    self.scope().wallet().require_write("wallet:read_write")?;
+    let params = serde_json::json!({ "currency": currency });
+    self.authed_call("private/create_deposit_address", params).await
+  }
+
+  /// Retrieves one page of transfers, most recent first.
+  /// - `currency` - The currency of the transfers.
+  /// - `count` - Page size.
+  /// - `offset` - Number of transfers to skip.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_transfers)
+  pub async fn get_transfers(&mut self, currency: Currency, count: u32, offset: u32) -> Result<Page<Transfer>, Error> {
+    let params = serde_json::json!({ "currency": currency, "count": count, "offset": offset });
+    self.authed_call("private/get_transfers", params).await
+  }
+
+  /// Transfers funds from the current account to one of its subaccounts. Requires `wallet:read_write`.
+  /// - `currency` - The currency to transfer.
+  /// - `amount` - The amount to transfer.
+  /// - `destination` - The ID of the subaccount, as returned by `get_subaccounts`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-submit_transfer_to_subaccount)
+  pub async fn submit_transfer_to_subaccount(&mut self, currency: Currency, amount: Decimal, destination: impl Into<SubaccountId>) -> Result<Transfer, Error> {
+    self.scope().wallet().require_write("wallet:read_write")?;
+    let params = serde_json::json!({ "currency": currency, "amount": number(amount), "destination": destination.into() });
+    self.authed_call("private/submit_transfer_to_subaccount", params).await
+  }
+
+  /// Transfers funds to another user. Requires `wallet:read_write`.
+  /// - `currency` - The currency to transfer.
+  /// - `amount` - The amount to transfer.
+  /// - `destination` - The destination wallet address; it must be in the transfer address book.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-submit_transfer_to_user)
+  pub async fn submit_transfer_to_user(&mut self, currency: Currency, amount: Decimal, destination: &str) -> Result<Transfer, Error> {
+    self.scope().wallet().require_write("wallet:read_write")?;
+    let params = serde_json::json!({ "currency": currency, "amount": number(amount), "destination": destination });
+    self.authed_call("private/submit_transfer_to_user", params).await
+  }
+
+  /// Transfers funds between two subaccounts of the same main account. Requires `wallet:read_write`.
+  /// - `currency` - The currency to transfer.
+  /// - `amount` - The amount to transfer.
+  /// - `destination` - The ID of the receiving subaccount.
+  /// - `source` - The ID of the sending subaccount; the current one if `None`.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-submit_transfer_between_subaccounts)
+  pub async fn submit_transfer_between_subaccounts(
+    &mut self,
+    currency: Currency,
+    amount: Decimal,
+    destination: impl Into<SubaccountId>,
+    source: Option<SubaccountId>,
+  ) -> Result<Transfer, Error> {
+    self.scope().wallet().require_write("wallet:read_write")?;
+    let mut params = serde_json::json!({ "currency": currency, "amount": number(amount), "destination": destination.into() });
+    if let Some(source) = source {
+      params["source"] = serde_json::to_value(source)?;
+    }
+    self.authed_call("private/submit_transfer_between_subaccounts", params).await
+  }
+
+  /// Cancels a pending transfer. Requires `wallet:read_write`.
+  /// - `currency` - The currency of the transfer.
+  /// - `id` - The ID of the transfer.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-cancel_transfer_by_id)
+  pub async fn cancel_transfer_by_id(&mut self, currency: Currency, id: i64) -> Result<Transfer, Error> {
+    self.scope().wallet().require_write("wallet:read_write")?;
+    let params = serde_json::json!({ "currency": currency, "id": id });
+    self.authed_call("private/cancel_transfer_by_id", params).await
+  }
+
+  /// Retrieves the address book of a given type.
+  /// - `currency` - The currency of the addresses.
+  /// - `address_type` - Which address book to read.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-get_address_book)
+  pub async fn get_address_book(&mut self, currency: Currency, address_type: AddressBookType) -> Result<Vec<AddressBookEntry>, Error> {
+    let params = serde_json::json!({ "currency": currency, "type": address_type });
+    self.authed_call("private/get_address_book", params).await
+  }
+
+  /// Adds an address to the address book. Requires `wallet:read_write`.
+  /// New withdrawal addresses must usually be confirmed by email before they can be used.
+  /// - `currency` - The currency of the address.
+  /// - `address_type` - Which address book to add it to.
+  /// - `address` - The address.
+  /// - `label` - A label for the address.
+  ///
+  /// Source: [Deribit docs](https://docs.deribit.com/#private-add_to_address_book)
+  pub async fn add_to_address_book(&mut self, currency: Currency, address_type: AddressBookType, address: &str, label: &str) -> Result<AddressBookEntry, Error> {
+    self.scope().wallet().require_write("wallet:read_write")?;
+    let params = serde_json::json!({ "currency": currency, "type": address_type, "address": address, "label": label });
+    self.authed_call("private/add_to_address_book", params).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::str::FromStr;
+
+  use super::*;
+  use crate::mock::MockDeribit;
+
+  #[test]
+  fn withdrawals_keep_their_decimal_amounts() {
+    // Shaped like the `private/withdraw` example in the Deribit docs
+    let withdrawal = r#"{"address":"2NBqqD5GRJ8wHy1PYyCXTe9ke5226FhavBz","amount":0.4,"confirmed_timestamp":null,"created_timestamp":1550574558607,"currency":"BTC","fee":0.0001,"id":4,"priority":1,"state":"unconfirmed","transaction_id":null,"updated_timestamp":1550574558607}"#;
+    let withdrawal: Withdrawal = serde_json::from_str(withdrawal).unwrap();
+    assert_eq!(withdrawal.amount, Decimal::from_str("0.4").unwrap());
+    assert_eq!(withdrawal.fee, Decimal::from_str("0.0001").unwrap());
+    assert_eq!(withdrawal.priority, Some(Decimal::ONE));
+    assert_eq!(serde_json::to_value(&withdrawal).unwrap()["fee"], serde_json::json!(0.0001));
+  }
+
+  #[tokio::test]
+  async fn withdrawals_send_amounts_as_numbers() {
+    let mock = MockDeribit::new();
+    mock.respond("private/withdraw", serde_json::json!({
+      "address": "addr", "amount": 0.123456789, "currency": "BTC", "fee": 0.0001, "id": 5, "state": "unconfirmed",
+      "created_timestamp": 1, "updated_timestamp": 1,
+    }));
+    let mut client = mock.private_client().await.unwrap();
+    let amount = Decimal::from_str("0.123456789").unwrap();
+    let withdrawal = client.withdraw(Currency::BTC, "addr", amount, None).await.unwrap();
+    assert_eq!(withdrawal.amount, amount);
+    assert_eq!(mock.assert_requested("private/withdraw").params["amount"], serde_json::json!(0.123456789));
+  }
+}